@@ -0,0 +1,59 @@
+use std::io::{self, Write};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use poop_scoreboard::stats::Stats;
+
+#[path = "../src/fixture.rs"]
+mod fixture;
+
+/// The csv writer before records and buffers were reused, with a String
+/// per cell and a search through the objective's scores for every cell
+fn write_csv_allocating(stats: &Stats, w: impl Write) {
+    let mut titles: Vec<&String> = stats.objectives().keys().collect();
+    titles.sort_unstable();
+
+    let mut w = csv::Writer::from_writer(w);
+
+    let mut top_row = vec!["Players".to_string()];
+    for title in &titles {
+        top_row.push(stats.objectives()[*title].display_name().to_string());
+    }
+    w.write_record(top_row).unwrap();
+
+    for player in stats.get_player_list() {
+        let mut row = vec![player.clone()];
+
+        for title in &titles {
+            row.push(
+                stats
+                    .player_scores()
+                    .get(*title)
+                    .and_then(|x| x.iter().find(|x| x.player_name() == player))
+                    .map_or(String::from("0"), |x| x.score().to_string()),
+            );
+        }
+
+        w.write_record(row).unwrap();
+    }
+
+    w.flush().unwrap();
+}
+
+fn csv_write(c: &mut Criterion) {
+    let stats = Stats::from_gzip_reader(&fixture::generated(1_000, 20)[..]).unwrap();
+
+    let mut group = c.benchmark_group("csv_write");
+    group.sample_size(20);
+
+    group.bench_function("write_csv", |b| {
+        b.iter(|| stats.write_csv(io::sink()).unwrap())
+    });
+    group.bench_function("allocating", |b| {
+        b.iter(|| write_csv_allocating(&stats, io::sink()))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, csv_write);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{
+    criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+    Criterion, Throughput,
+};
+use poop_scoreboard::stats::Stats;
+
+#[path = "../src/fixture.rs"]
+mod fixture;
+
+/// Counts the bytes ever allocated and freed, so the difference is what is
+/// still held
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static FREED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        FREED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Bytes still allocated at the end of the measurement, together with
+/// `iter_with_large_drop` the memory held by whatever the routine returns
+struct RetainedBytes;
+
+impl Measurement for RetainedBytes {
+    type Intermediate = (usize, usize);
+    type Value = f64;
+
+    fn start(&self) -> Self::Intermediate {
+        (
+            ALLOCATED.load(Ordering::Relaxed),
+            FREED.load(Ordering::Relaxed),
+        )
+    }
+
+    fn end(&self, (allocated, freed): Self::Intermediate) -> Self::Value {
+        let allocated = ALLOCATED.load(Ordering::Relaxed) - allocated;
+        let freed = FREED.load(Ordering::Relaxed) - freed;
+        allocated as f64 - freed as f64
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0.0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (divisor, unit) = match typical_value.abs() {
+            x if x < 1024.0 => (1.0, "B"),
+            x if x < 1024.0 * 1024.0 => (1024.0, "KiB"),
+            _ => (1024.0 * 1024.0, "MiB"),
+        };
+        for value in values {
+            *value /= divisor;
+        }
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let elements = match throughput {
+            Throughput::Elements(x) => *x as f64,
+            Throughput::Bytes(x) | Throughput::BytesDecimal(x) => *x as f64,
+            _ => 1.0,
+        };
+        for value in values {
+            *value /= elements;
+        }
+        "B/score"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// The scores laid out like before player names were interned, with a
+/// String of the name in every score
+type StringPerScore = HashMap<String, Vec<(String, i64, i8)>>;
+
+fn string_per_score(stats: &Stats) -> StringPerScore {
+    stats
+        .player_scores()
+        .iter()
+        .map(|(objective, scores)| {
+            (
+                objective.clone(),
+                scores
+                    .iter()
+                    .map(|x| (x.player_name().to_string(), x.score(), 0))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+const PLAYERS: usize = 1_000;
+const OBJECTIVES: usize = 80;
+
+fn parse_memory(c: &mut Criterion<RetainedBytes>) {
+    let file = fixture::generated(PLAYERS, OBJECTIVES);
+
+    let mut group = c.benchmark_group("parse_memory");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((PLAYERS * OBJECTIVES) as u64));
+
+    group.bench_function("interned", |b| {
+        b.iter_with_large_drop(|| Stats::from_gzip_reader(&file[..]).unwrap())
+    });
+    group.bench_function("string_per_score", |b| {
+        b.iter_with_large_drop(|| string_per_score(&Stats::from_gzip_reader(&file[..]).unwrap()))
+    });
+
+    group.finish();
+}
+
+fn parse_time(c: &mut Criterion) {
+    let file = fixture::generated(PLAYERS, OBJECTIVES);
+
+    let mut group = c.benchmark_group("parse_time");
+    group.sample_size(10);
+
+    group.bench_function("from_gzip_reader", |b| {
+        b.iter(|| Stats::from_gzip_reader(black_box(&file[..])).unwrap())
+    });
+    group.bench_function("objectives_only", |b| {
+        b.iter(|| Stats::objectives_only(black_box(&file[..])).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = memory;
+    config = Criterion::default().with_measurement(RetainedBytes);
+    targets = parse_memory
+}
+criterion_group!(time, parse_time);
+criterion_main!(memory, time);
@@ -0,0 +1,93 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+/// A file written under a temporary name in the destination's directory
+/// and renamed over the destination by [`AtomicFile::commit`].
+///
+/// Readers see either the old file or the complete new one. The
+/// temporary file is removed if the `AtomicFile` is dropped before
+/// committing, leaving any existing destination untouched.
+pub struct AtomicFile {
+    file: File,
+    temp_path: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Starts writing a replacement for `path`, which may or may not exist.
+    /// On Unix the permissions of an existing destination are kept.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(
+            ".tmp-{}",
+            &Uuid::new_v4().simple().to_string()[..8]
+        ));
+        let temp_path = path.with_file_name(temp_name);
+
+        let file = File::create_new(&temp_path)?;
+
+        let atomic_file = Self {
+            file,
+            temp_path,
+            path,
+            committed: false,
+        };
+
+        #[cfg(unix)]
+        if let Ok(metadata) = fs::metadata(&atomic_file.path) {
+            atomic_file.file.set_permissions(metadata.permissions())?;
+        }
+
+        Ok(atomic_file)
+    }
+
+    /// Syncs the written data to disk and renames it over the destination
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+        fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+
+        //the rename itself is only durable once the directory is synced
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent() {
+            let dir = match dir.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => dir,
+            };
+            File::open(dir)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
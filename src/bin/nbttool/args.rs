@@ -0,0 +1,1108 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+#[cfg(feature = "http")]
+use poop_scoreboard::post::PostFormat;
+use poop_scoreboard::stats::{
+    ColumnOrder, CombineMode, HeaderCollision, HeaderNames, NormalForm, PlayerOrder, SqlDialect,
+    TeamAggregate,
+};
+use regex::Regex;
+
+/// The exit codes of an export, `check` has its own
+pub const EXIT_CODES: &str = "Exit codes:
+  0  everything was written
+  1  reading the input or every destination failed
+  3  only posting the stats failed
+  4  some destinations failed, the rest were written
+  5  fewer players or objectives than --min-players or --min-objectives
+  6  no scores to export with --fail-on-empty";
+
+#[derive(Debug, Parser)]
+#[command(version,about,long_about= None, after_help = EXIT_CODES)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(required = true)]
+    pub input_file: Option<PathBuf>,
+    /// Write a csv file, the default output next to the input file when
+    /// no other output is given
+    #[arg(short, long)]
+    pub output_file: Option<PathBuf>,
+    /// Write the csv with a player,objective,score row per score instead
+    /// of a column per objective, the other csv options don't apply to it
+    #[arg(long)]
+    pub long: bool,
+    /// Report on stderr what the filters did, like how many players
+    /// --drop-empty-players dropped and --include-players-regex and
+    /// --exclude-players-regex patterns that match nobody
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Replace the csv file when it already exists. Without it nbttool
+    /// fails before reading the input.
+    #[arg(long)]
+    pub force: bool,
+    /// Refuse to replace an existing csv file, the default without --force
+    #[arg(long, conflicts_with = "force")]
+    pub no_clobber: bool,
+    /// Exit with 6 without writing anything when there are no scores to
+    /// export. Like the other minimums it is checked after --objectives,
+    /// --players and --include-namespace are applied.
+    #[arg(long, conflicts_with = "streaming")]
+    pub fail_on_empty: bool,
+    /// Exit with 5 without writing anything when fewer players have scores,
+    /// counted after filtering so a narrow --players needs a lower minimum
+    #[arg(long, value_name = "N", conflicts_with = "streaming")]
+    pub min_players: Option<usize>,
+    /// Exit with 5 without writing anything when fewer objectives are left
+    /// after filtering
+    #[arg(long, value_name = "N", conflicts_with = "streaming")]
+    pub min_objectives: Option<usize>,
+    /// How failures are printed to stderr: text or json, a single object
+    /// with the exit code and the details of every error. Subcommands take
+    /// it after their name.
+    #[arg(long, global = true, default_value = "text", value_parser = parse_error_format)]
+    pub error_format: ErrorFormat,
+    /// Write the scores to the database and the --long csv as they are
+    /// read instead of parsing the whole file first. The csv with a column
+    /// per objective needs every score and can't be streamed.
+    #[arg(long)]
+    pub streaming: bool,
+    /// Reuse the parsed scoreboard from this directory while the input file is unchanged
+    #[arg(long, conflicts_with = "streaming")]
+    pub cache_dir: Option<PathBuf>,
+    /// Read the input file through a memory map
+    #[cfg(feature = "mmap")]
+    #[arg(long, conflicts_with_all = ["streaming", "cache_dir", "from_csv"])]
+    pub mmap: bool,
+    /// The input is a wide csv exported earlier instead of nbt, for example
+    /// to backfill a database
+    #[arg(long, conflicts_with_all = ["streaming", "cache_dir"])]
+    pub from_csv: bool,
+    /// A .toml file with exact csv headers for objectives, keyed by
+    /// objective name in an [objectives] table, and for the first column
+    /// as players. Other columns keep their headers, and --from-csv reads
+    /// the headers back. A .json file instead maps csv headers to
+    /// objective names for --from-csv.
+    #[arg(long, value_name = "TOML_OR_JSON_FILE")]
+    pub header_map: Option<PathBuf>,
+    /// Cells of the --from-csv input holding this have no score
+    #[arg(long, requires = "from_csv", default_value = "")]
+    pub missing_score: String,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    #[command(flatten)]
+    pub teams: TeamArgs,
+    #[command(flatten)]
+    pub csv: CsvArgs,
+    #[command(flatten)]
+    pub snapshots: SnapshotArgs,
+    #[command(flatten)]
+    pub files: FileArgs,
+    #[command(flatten)]
+    pub sql: SqlArgs,
+    #[command(flatten)]
+    pub sql_files: SqlFileArgs,
+    #[cfg(feature = "http")]
+    #[command(flatten)]
+    pub http: HttpArgs,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Players and objectives")]
+pub struct FilterArgs {
+    /// Order of the players in every output: lexical, natural or none for
+    /// the order of the input, csv rows are lexical when not given
+    #[arg(long, value_parser = parse_player_order)]
+    pub sort_players: Option<PlayerOrder>,
+    /// Unicode normalize player names, nfc or nfkc, merging names that end
+    /// up the same
+    #[arg(long, conflicts_with = "streaming", value_parser = parse_normal_form)]
+    pub normalize_names: Option<NormalForm>,
+    /// Drop zero-width characters from player names, merging names that end
+    /// up the same
+    #[arg(long, conflicts_with = "streaming")]
+    pub strip_invisible: bool,
+    /// Only export these objectives
+    #[arg(long, value_delimiter = ',')]
+    pub objectives: Option<Vec<String>>,
+    /// Only export these players
+    #[arg(long, value_delimiter = ',')]
+    pub players: Option<Vec<String>>,
+    /// Only export players whose name matches one of these regexes, may be
+    /// repeated
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex)]
+    pub include_players_regex: Vec<Regex>,
+    /// Leave out players whose name matches this regex, like '^Bot_\d+$',
+    /// may be repeated
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex)]
+    pub exclude_players_regex: Vec<Regex>,
+    /// Leave out scores below this, they are empty in the csv instead of 0
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    pub min_score: Option<i64>,
+    /// Leave out scores of one objective below its own threshold, as
+    /// OBJECTIVE=SCORE overriding --min-score, may be repeated
+    #[arg(long, value_name = "OBJECTIVE=SCORE", value_parser = parse_threshold)]
+    pub min_score_objective: Vec<(String, i64)>,
+    /// Leave out players whose every exported score and --computed value is
+    /// 0, after the other filters
+    #[arg(long, conflicts_with = "streaming")]
+    pub drop_empty_players: bool,
+    /// Only export objectives in namespaces matching these globs, like bac_*
+    #[arg(long, value_delimiter = ',', value_parser = parse_namespace_pattern)]
+    pub include_namespace: Option<Vec<glob::Pattern>>,
+    /// Only export objectives with these criteria: statistic, a category
+    /// like minecraft.mined or a criteria like dummy or trigger, see the
+    /// categories with summary --stats
+    #[arg(long, value_delimiter = ',', conflicts_with = "streaming")]
+    pub criteria: Vec<String>,
+    /// Leave out objectives with these criteria, wins over --criteria
+    #[arg(long, value_delimiter = ',', conflicts_with = "streaming")]
+    pub exclude_criteria: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Teams")]
+pub struct TeamArgs {
+    /// Export a row per scoreboard team instead of per player, players on
+    /// no team share an Unassigned row
+    #[arg(
+        long,
+        value_parser = ["team"],
+        conflicts_with_all = ["streaming", "groups", "uuid_map"]
+    )]
+    pub group_by: Option<String>,
+    /// How the scores of a team's members are combined: sum, max or mean
+    #[arg(
+        long,
+        requires = "group_by",
+        default_value = "sum",
+        value_parser = parse_team_aggregate
+    )]
+    pub team_agg: TeamAggregate,
+    /// Add a Members column with the number of players with scores on
+    /// every team
+    #[arg(long, requires = "group_by")]
+    pub team_size_column: bool,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Csv")]
+#[command(group = clap::ArgGroup::new("scale_or_divide").args(["scale", "divide"]).multiple(true))]
+pub struct CsvArgs {
+    /// JSON file mapping player names to group labels, adds a Group column
+    #[arg(long, value_name = "JSON_FILE")]
+    pub groups: Option<PathBuf>,
+    /// usercache.json to read player UUIDs from, adds a leading UUID column
+    #[arg(long, value_name = "USERCACHE_JSON")]
+    pub uuid_map: Option<PathBuf>,
+    /// Look up the UUIDs of players missing from --uuid-map by sending
+    /// their names to the Mojang API, adds a leading UUID column. Lookups
+    /// are remembered in --cache-dir. Only mojang is supported.
+    #[cfg(feature = "http")]
+    #[arg(long, value_parser = ["mojang"], conflicts_with = "streaming")]
+    pub resolve_uuids: Option<String>,
+    /// Multiply the scores of an objective in the csv, as OBJECTIVE=FACTOR,
+    /// may be repeated. The factor may be fractional like 0.01 but not
+    /// negative. Only the csv with a column per objective and
+    /// --scale-sql scaled use it, other outputs keep the raw scores.
+    #[arg(long, value_name = "OBJECTIVE=FACTOR", value_parser = parse_factor)]
+    pub scale: Vec<(String, f64)>,
+    /// Divide the scores of an objective in the csv, as OBJECTIVE=DIVISOR,
+    /// may be repeated. What --scale OBJECTIVE=DIVISOR used to do. Used by
+    /// the same outputs as --scale.
+    #[arg(long, value_name = "OBJECTIVE=DIVISOR", value_parser = parse_factor)]
+    pub divide: Vec<(String, f64)>,
+    /// Decimal places written for --scale and --divide
+    #[arg(
+        long,
+        visible_alias = "scale-precision",
+        requires = "scale_or_divide",
+        default_value_t = 2
+    )]
+    pub scale_decimals: u8,
+    /// Write the scores of these objectives, counted in game ticks, as HH:MM:SS
+    #[arg(long, value_name = "OBJECTIVE", value_delimiter = ',')]
+    pub ticks_to_time: Vec<String>,
+    /// Add a column computed from objectives, as NAME=EXPRESSION using + - * / and
+    /// parentheses, may be repeated
+    #[arg(long, value_name = "NAME=EXPRESSION", value_parser = parse_computed)]
+    pub computed: Vec<(String, String)>,
+    /// Objective names used as csv headers: display, internal or friendly
+    #[arg(long, default_value = "display", value_parser = parse_header_names)]
+    pub header_names: HeaderNames,
+    /// When two columns get the same header: disambiguate with the objective
+    /// name in parentheses or error
+    #[arg(long, default_value = "disambiguate", value_parser = parse_header_collision)]
+    pub on_header_collision: HeaderCollision,
+    /// Append (outlier) to scores outside 1.5 interquartile ranges of their
+    /// objective's quartiles
+    #[arg(long)]
+    pub annotate_outliers: bool,
+    /// Start the csv with a UTF-8 byte order mark so Excel reads it as UTF-8
+    #[arg(long)]
+    pub bom: bool,
+    /// Add a column after an objective's scores with the label of the first
+    /// bin whose bound is above the score, as OBJECTIVE=BOUND:LABEL,...
+    /// where the last bound may be max, may be repeated
+    #[arg(long, value_name = "OBJECTIVE=BOUND:LABEL,...", value_parser = parse_classify)]
+    pub classify: Vec<(String, Vec<(i64, String)>)>,
+    /// Add a column summing or averaging the scores of objectives, as
+    /// NAME=sum:OBJECTIVE,... or NAME=average:OBJECTIVE,..., may be repeated
+    #[arg(long, value_name = "NAME=MODE:OBJECTIVE,...", value_parser = parse_combine)]
+    pub combine: Vec<(String, (Vec<String>, CombineMode))>,
+    /// Order of the objective columns: name, display, criteria to group
+    /// them by criteria category, or total-desc for the highest summed
+    /// scores first
+    #[arg(long, default_value = "name", value_parser = parse_column_order)]
+    pub order_columns: ColumnOrder,
+    /// Decimal places of --computed columns, averaged --combine columns and
+    /// --average-with scores
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    pub float_precision: u8,
+    /// Add a last _sha256 column hashing the player name and raw scores of
+    /// every row, to find rows repeated across exports
+    #[arg(long)]
+    pub row_hash: bool,
+    /// Leave out the columns of objectives used by --combine
+    #[arg(long, requires = "combine")]
+    pub hide_sources: bool,
+    /// Add a column after every objective's scores with the percentile rank
+    /// of the score from 0 to 100
+    #[arg(long)]
+    pub percentiles: bool,
+    /// Add a column after every objective's scores with the dense rank of
+    /// the score, 1 for the highest and ties sharing a rank
+    #[arg(long)]
+    pub dense_rank: bool,
+    /// Write an extra header row grouping objectives named like pvp.kills by
+    /// their prefix
+    #[arg(long)]
+    pub group_by_prefix: bool,
+    /// Write player and display names with their § formatting codes and
+    /// non-printable characters instead of removing them
+    #[arg(long)]
+    pub preserve_raw_names: bool,
+    /// Write the Players column base64 encoded
+    #[arg(long)]
+    pub base64_player_names: bool,
+    /// Mark scores above the threshold with a *, as OBJECTIVE=SCORE, may be repeated
+    #[arg(long, value_name = "OBJECTIVE=SCORE", value_parser = parse_threshold)]
+    pub flag_above: Vec<(String, i64)>,
+    /// Write scores as bars of block characters scaled to each objective's top score
+    #[arg(long)]
+    pub unicode_bars: bool,
+    /// Also write a Frictionless descriptor of the csv next to it, named
+    /// like the csv with a .datapackage.json extension
+    #[arg(long, conflicts_with = "average_with")]
+    pub with_schema: bool,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Snapshots")]
+pub struct SnapshotArgs {
+    /// Older snapshots of the input, the csv gets each score averaged over
+    /// these and the input
+    #[arg(long, value_name = "SNAPSHOT", value_delimiter = ',', conflicts_with_all = ["groups", "uuid_map", "unicode_bars"])]
+    pub average_with: Option<Vec<PathBuf>>,
+    /// Older snapshots of the input from oldest to newest, the csv gets a
+    /// Braille sparkline of every score over these and the input
+    #[arg(long, value_name = "SNAPSHOT", value_delimiter = ',', conflicts_with_all = ["groups", "uuid_map", "unicode_bars", "average_with", "with_schema"])]
+    pub trend_with: Option<Vec<PathBuf>>,
+    /// A previous snapshot of the input, the csv gets an Active column
+    /// telling whether any score of the player changed since then
+    #[arg(long, value_name = "PREVIOUS_FILE", conflicts_with_all = ["groups", "uuid_map", "unicode_bars", "average_with", "trend_with", "with_schema"])]
+    pub activity_flag: Option<PathBuf>,
+    /// Older snapshots of the input, the csv gets how much every score
+    /// changed per day since the oldest of these after it
+    #[arg(long, value_name = "SNAPSHOT", value_delimiter = ',', conflicts_with_all = ["groups", "uuid_map", "unicode_bars", "average_with", "trend_with", "activity_flag", "with_schema"])]
+    pub velocity_with: Option<Vec<PathBuf>>,
+    /// Append an arrow to every score showing how it changed since this snapshot
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub compare_with: Option<PathBuf>,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Other files")]
+pub struct FileArgs {
+    /// Write one player,score csv per objective into --output-dir instead of a single csv
+    #[arg(long, requires = "output_dir", conflicts_with_all = ["output_file", "force", "no_clobber", "average_with"])]
+    pub split_by_objective: bool,
+    #[arg(long, requires = "split_by_objective")]
+    pub output_dir: Option<PathBuf>,
+    /// Also write files for objectives without any scores
+    #[arg(long, requires = "split_by_objective")]
+    pub include_empty: bool,
+    /// Also write a csv counting the scores of every objective in equal
+    /// width bins from its lowest to its highest score
+    #[arg(long, value_name = "FILE")]
+    pub histogram: Option<PathBuf>,
+    /// Write the output with a minijinja template instead of as csv,
+    /// either a template file or the builtin leaderboard.txt or
+    /// leaderboard.html. Without --output-file the output gets the
+    /// extension of the template.
+    #[cfg(feature = "templates")]
+    #[arg(
+        long,
+        value_name = "FILE|NAME",
+        conflicts_with_all = ["streaming", "split_by_objective"]
+    )]
+    pub template: Option<String>,
+    /// Number of bins in --histogram
+    #[arg(long, requires = "histogram", default_value_t = 10, value_parser = parse_bins)]
+    pub histogram_bins: usize,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Database")]
+pub struct SqlArgs {
+    /// Import into this database, can be combined with --output-file
+    #[arg(short, long)]
+    pub sql_url: Option<String>,
+    #[arg(short, long, requires = "sql_url", value_parser = parse_time)]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[arg(long, requires = "sql_url", default_value_t = 30)]
+    pub connect_timeout_secs: u64,
+    /// Use tables named {prefix}_players, {prefix}_objectives and {prefix}_stats
+    #[arg(long, requires = "sql_url")]
+    pub schema_prefix: Option<String>,
+    /// Create any missing tables before inserting
+    #[arg(long, requires = "sql_url")]
+    pub create_tables: bool,
+    /// Insert into monthly stats_YYYYMM tables joined by a stats view
+    #[arg(long, requires = "sql_url")]
+    pub partition_by_date: bool,
+    /// Append the scores to the score_events table as one event instead
+    #[arg(long, requires = "sql_url", conflicts_with_all = ["streaming", "partition_by_date", "region"])]
+    pub event_log: bool,
+    /// Insert a row per player with a column per objective into the
+    /// player_scores_wide table instead, adding columns for new objectives
+    #[arg(long, requires = "sql_url", conflicts_with_all = ["streaming", "partition_by_date", "region", "event_log", "ttl_days"])]
+    pub wide_table: bool,
+    /// Also insert the total, player count and highest score of every
+    /// objective into the objective_totals table
+    #[arg(long, requires = "sql_url", conflicts_with_all = ["streaming", "event_log", "wide_table"])]
+    pub aggregates: bool,
+    /// Create stats tables with ROW_FORMAT=COMPRESSED instead of page compression
+    #[arg(long, requires = "sql_url")]
+    pub row_compression: bool,
+    /// Create the score column of stats tables as VARCHAR(255) instead of BIGINT
+    #[arg(long, requires = "sql_url", overrides_with = "strict_schema")]
+    pub soft_schema: bool,
+    /// Create a FULLTEXT index on player names for searching by part of a name
+    #[arg(long, requires = "create_tables")]
+    pub fulltext_index: bool,
+    /// Encrypt player names in the database with the AES-256 key in this
+    /// file, written as 64 hex digits
+    #[arg(long, value_name = "KEY_FILE", requires = "sql_url")]
+    pub encryption_key_file: Option<PathBuf>,
+    /// Give stats tables an auto increment id primary key
+    #[arg(long, requires = "sql_url")]
+    pub auto_increment_id: bool,
+    /// After inserting, delete stats older than this many days
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "sql_url",
+        conflicts_with = "event_log"
+    )]
+    pub ttl_days: Option<u32>,
+    /// Create the score column as BIGINT, the default
+    #[arg(long, overrides_with = "soft_schema")]
+    pub strict_schema: bool,
+    /// KEY_BLOCK_SIZE for --row-compression: 1, 2, 4, 8 or 16
+    #[arg(long, requires = "row_compression", default_value_t = 4)]
+    pub key_block_size: u8,
+    /// Tag the inserted scores with this server region, older stats tables
+    /// get the region column from --create-tables
+    #[arg(long, value_name = "NAME", requires = "sql_url")]
+    pub region: Option<String>,
+    /// Tag every inserted row with this tenant for row level security,
+    /// older tables get the tenant_id column from --create-tables
+    #[arg(long, value_name = "NAME", requires = "sql_url", value_parser = parse_tenant_id)]
+    pub tenant_id: Option<String>,
+    /// Whether sql stores only the raw scores or also the ones changed by
+    /// --scale and --divide in a scaled_score column: raw or scaled
+    #[arg(
+        long,
+        value_name = "raw|scaled",
+        default_value = "raw",
+        value_parser = parse_scale_sql,
+        action = clap::ArgAction::Set,
+        requires_all = ["scale_or_divide", "sql_url"],
+        conflicts_with = "event_log"
+    )]
+    pub scale_sql: bool,
+    /// Insert a row per player with all their scores as JSONB into the
+    /// player_snapshots table of this PostgreSQL database
+    #[cfg(feature = "postgres")]
+    #[arg(long, conflicts_with = "streaming")]
+    pub postgres_url: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Sql files")]
+pub struct SqlFileArgs {
+    /// Write statements creating a stats_changes table and triggers logging
+    /// every change to the stats table into it
+    #[arg(long, value_name = "FILE")]
+    pub cdc_triggers: Option<PathBuf>,
+    /// Write statements creating a leaderboard_summary of the latest score
+    /// and rank of every player, running them again refreshes it
+    #[arg(long, value_name = "FILE")]
+    pub create_materialized_view: Option<PathBuf>,
+    /// Write statements creating a cross_server_leaderboard view putting
+    /// the top players of every --compare-regions region side by side
+    #[arg(long, value_name = "FILE", requires = "compare_regions")]
+    pub cross_server_view: Option<PathBuf>,
+    /// The regions of the stats table compared by --cross-server-view
+    #[arg(
+        long,
+        value_name = "REGION",
+        value_delimiter = ',',
+        requires = "cross_server_view"
+    )]
+    pub compare_regions: Vec<String>,
+    /// Write statements adding a tenant_id column to every table and
+    /// policies limiting sessions to the rows of their app.tenant_id, only
+    /// for --sql-dialect postgres
+    #[arg(long, value_name = "FILE")]
+    pub row_level_security: Option<PathBuf>,
+    /// Database the written statements are for: mysql or postgres
+    #[arg(long, default_value = "mysql", value_parser = parse_sql_dialect)]
+    pub sql_dialect: SqlDialect,
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Http")]
+pub struct HttpArgs {
+    /// POST the exported stats to this url, exits with 3 if only this fails
+    #[arg(long, conflicts_with = "streaming")]
+    pub post_url: Option<String>,
+    /// Body format for --post-url: json or ndjson
+    #[arg(long, requires = "post_url", default_value = "json", value_parser = parse_post_format)]
+    pub post_format: PostFormat,
+    /// Extra header for --post-url as 'Name: value', may be repeated
+    #[arg(long, requires = "post_url", value_parser = parse_header)]
+    pub header: Vec<(String, String)>,
+    /// How many times to retry the post after a 5xx response
+    #[arg(long, requires = "post_url", default_value_t = 0)]
+    pub post_retries: u32,
+    /// Timeout for a single post in seconds
+    #[arg(long, requires = "post_url", default_value_t = 30)]
+    pub post_timeout: u64,
+    /// Insert the scores into ClickHouse over http, like
+    /// http://host:8123?database=mc, creating the table when it is missing
+    #[arg(long, conflicts_with = "streaming")]
+    pub clickhouse_url: Option<String>,
+    /// ClickHouse user, overrides the user in --clickhouse-url
+    #[arg(long, requires = "clickhouse_url")]
+    pub clickhouse_user: Option<String>,
+    /// ClickHouse password, overrides the password in --clickhouse-url
+    #[arg(long, requires = "clickhouse_url")]
+    pub clickhouse_password: Option<String>,
+    #[arg(long, requires = "clickhouse_url", default_value = "stats")]
+    pub clickhouse_table: String,
+    /// How many scores are inserted per request
+    #[arg(long, requires = "clickhouse_url", default_value_t = 10_000)]
+    pub clickhouse_batch_size: usize,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the scores over http, parsing the input file on demand
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long, default_value = "127.0.0.1:8337")]
+        listen: String,
+    },
+    /// Import the scoreboard into the database on an interval until stopped
+    #[cfg(feature = "daemon")]
+    Daemon {
+        /// A scoreboard file or a world directory
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        sql_url: String,
+        /// Time between imports, like 90s, 15m or 1h
+        #[arg(long, value_parser = parse_interval)]
+        every: Duration,
+        /// Import once right away instead of waiting for the first interval
+        #[arg(long)]
+        run_once_on_start: bool,
+        /// Create any missing tables before each import
+        #[arg(long)]
+        create_tables: bool,
+        /// Use tables named {prefix}_players, {prefix}_objectives and {prefix}_stats
+        #[arg(long)]
+        schema_prefix: Option<String>,
+        /// Insert into monthly stats_YYYYMM tables joined by a stats view
+        #[arg(long)]
+        partition_by_date: bool,
+        /// Tag the inserted scores with this server region
+        #[arg(long, value_name = "NAME")]
+        region: Option<String>,
+    },
+    /// Create the tables of a new database, or with --upgrade bring an
+    /// existing one up to the current schema
+    InitDb {
+        #[arg(long)]
+        sql_url: String,
+        /// Apply the migrations missing from a database initialized by an
+        /// older version
+        #[arg(long)]
+        upgrade: bool,
+        /// Print the statements of the pending migrations without running them
+        #[arg(long)]
+        dry_run: bool,
+        /// Use tables named {prefix}_players, {prefix}_objectives and {prefix}_stats
+        #[arg(long)]
+        schema_prefix: Option<String>,
+        /// The stats are in monthly stats_YYYYMM tables
+        #[arg(long)]
+        partition_by_date: bool,
+        /// Insert the objectives of this scoreboard, updating the names of
+        /// the ones already in the database. Its scores aren't read.
+        #[arg(long, value_name = "FILE")]
+        objectives_from: Option<PathBuf>,
+    },
+    /// Print the name, criteria and display name of every objective without
+    /// reading the scores
+    ListObjectives {
+        input: PathBuf,
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Leave a player out of every export to the database
+    ExcludePlayer {
+        player: String,
+        #[arg(long)]
+        sql_url: String,
+        /// Use the {prefix}_excluded_players table
+        #[arg(long)]
+        schema_prefix: Option<String>,
+    },
+    /// Export a player excluded with exclude-player again
+    IncludePlayer {
+        player: String,
+        #[arg(long)]
+        sql_url: String,
+        /// Use the {prefix}_excluded_players table
+        #[arg(long)]
+        schema_prefix: Option<String>,
+    },
+    /// Write the scores of one objective across dated snapshot files
+    Timeseries {
+        /// The snapshot files, like 'backups/*.dat'
+        #[arg(long)]
+        glob: String,
+        #[arg(long)]
+        objective: String,
+        /// Format of the date in the snapshot file names
+        #[arg(long, default_value = "%Y-%m-%d")]
+        date_from_filename: String,
+        #[arg(short, long)]
+        output_file: PathBuf,
+        /// Write player,date,score rows instead of a column per date
+        #[arg(long)]
+        long: bool,
+    },
+    /// Compare the scoreboard with an earlier export, exiting with 1 when
+    /// they differ and 2 on errors
+    Check {
+        input: PathBuf,
+        /// The exported file to compare against
+        #[arg(long)]
+        against: PathBuf,
+        /// Format of the exported file, csv or json
+        #[arg(long, default_value = "csv", value_parser = parse_check_format)]
+        format: CheckFormat,
+        /// Also fail when the exported file was modified longer ago than
+        /// this, like 90s, 15m or 2h
+        #[arg(long, value_parser = parse_interval)]
+        max_age: Option<Duration>,
+        /// Print the differences as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print how many players and objectives have scores, or with --stats
+    /// how the scores of every objective are distributed
+    Summary {
+        input: PathBuf,
+        /// A row per objective with the number of players with a score and
+        /// the min, max, mean, median and total score
+        #[arg(long)]
+        stats: bool,
+        /// Print json instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Only count these objectives, like the export
+        #[arg(long, value_delimiter = ',')]
+        objectives: Option<Vec<String>>,
+        /// Only count these players, like the export
+        #[arg(long, value_delimiter = ',')]
+        players: Option<Vec<String>>,
+        /// Only count objectives in namespaces matching these globs, like the export
+        #[arg(long, value_delimiter = ',', value_parser = parse_namespace_pattern)]
+        include_namespace: Option<Vec<glob::Pattern>>,
+        /// Only count objectives with these criteria, like the export
+        #[arg(long, value_delimiter = ',')]
+        criteria: Vec<String>,
+        /// Leave out objectives with these criteria, like the export
+        #[arg(long, value_delimiter = ',')]
+        exclude_criteria: Vec<String>,
+    },
+    /// Print the raw NBT tree of a file with the type of every value, also
+    /// for files that can't be read as a scoreboard
+    Inspect {
+        input: PathBuf,
+        /// Only print the value at this path of keys and list indices, like
+        /// data.Objectives.0
+        #[arg(long)]
+        path: Option<String>,
+        /// Levels below the printed value that are expanded
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Print every element of long lists and arrays
+        #[arg(long)]
+        full: bool,
+        /// Print json instead of a tree
+        #[arg(long)]
+        json: bool,
+    },
+    /// Set the score of a player and write the scoreboard as a new file
+    Set {
+        input: PathBuf,
+        #[arg(long)]
+        player: String,
+        /// An existing objective
+        #[arg(long)]
+        objective: String,
+        #[arg(long, allow_negative_numbers = true)]
+        value: i32,
+        #[command(flatten)]
+        output: EditOutput,
+    },
+    /// Remove every score of a player and write the scoreboard as a new file
+    RemovePlayer {
+        input: PathBuf,
+        name: String,
+        #[command(flatten)]
+        output: EditOutput,
+    },
+    /// Remove an objective with its scores and write the scoreboard as a
+    /// new file
+    RemoveObjective {
+        input: PathBuf,
+        name: String,
+        #[command(flatten)]
+        output: EditOutput,
+    },
+    /// Export the scoreboards of several worlds together with a world column
+    Worlds(WorldsArgs),
+    /// Post the leaderboard of an objective to a Discord webhook
+    #[cfg(feature = "http")]
+    Announce {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        webhook_url: String,
+        #[arg(long)]
+        objective: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Defaults to the display name of the objective
+        #[arg(long)]
+        title: Option<String>,
+        /// Send a code block instead of an embed
+        #[arg(long)]
+        plain: bool,
+    },
+}
+
+/// Where an edited scoreboard is written
+#[derive(Debug, clap::Args)]
+#[group(required = true, multiple = false)]
+pub struct EditOutput {
+    /// The edited scoreboard, the input is left untouched
+    #[arg(short, long)]
+    pub output_file: Option<PathBuf>,
+    /// Replace the input with the edited scoreboard
+    #[arg(long)]
+    pub in_place: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct WorldsArgs {
+    /// A server root, every */data/scoreboard.dat beneath it is a world
+    /// named after its directory
+    #[arg(required_unless_present = "worlds")]
+    pub root: Option<PathBuf>,
+    /// A world as NAME=PATH where the path is a scoreboard file or a world
+    /// directory, may be repeated and is added to the worlds of the root
+    #[arg(long = "world", value_name = "NAME=PATH", value_parser = parse_world)]
+    pub worlds: Vec<(String, PathBuf)>,
+    /// Write a world,player,objective,display_name,score row per score
+    #[arg(short, long)]
+    pub output_file: Option<PathBuf>,
+    /// Write the rows of --output-file as json lines
+    #[arg(long, value_name = "FILE")]
+    pub ndjson: Option<PathBuf>,
+    /// Write a csv like the default export for every world into this
+    /// directory, named after the world
+    #[arg(long, value_name = "DIR")]
+    pub per_world_files: Option<PathBuf>,
+    /// Insert the scores of every world tagged with its name
+    #[arg(long)]
+    pub sql_url: Option<String>,
+    #[arg(long, requires = "sql_url")]
+    pub create_tables: bool,
+    /// Use tables named {prefix}_players, {prefix}_objectives and {prefix}_stats
+    #[arg(long, requires = "sql_url")]
+    pub schema_prefix: Option<String>,
+    /// Tag the inserted scores with this server region too
+    #[arg(long, value_name = "NAME", requires = "sql_url")]
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Text,
+    Json,
+}
+
+fn parse_bins(arg: &str) -> Result<usize, String> {
+    match arg.parse() {
+        Ok(0) => Err("a histogram needs at least one bin".to_string()),
+        Ok(bins) => Ok(bins),
+        Err(_) => Err(format!("expected a number of bins, got {}", arg)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckFormat {
+    Csv,
+    Json,
+}
+
+fn parse_check_format(arg: &str) -> Result<CheckFormat, String> {
+    match arg {
+        "csv" => Ok(CheckFormat::Csv),
+        "json" => Ok(CheckFormat::Json),
+        _ => Err(format!("unknown format {}, expected csv or json", arg)),
+    }
+}
+
+fn parse_sql_dialect(arg: &str) -> Result<SqlDialect, String> {
+    match arg {
+        "mysql" => Ok(SqlDialect::MySql),
+        "postgres" => Ok(SqlDialect::Postgres),
+        _ => Err(format!(
+            "unknown dialect {}, expected mysql or postgres",
+            arg
+        )),
+    }
+}
+
+fn parse_scale_sql(arg: &str) -> Result<bool, String> {
+    match arg {
+        "raw" => Ok(false),
+        "scaled" => Ok(true),
+        _ => Err(format!("unknown mode {}, expected raw or scaled", arg)),
+    }
+}
+
+#[cfg(feature = "http")]
+fn parse_post_format(arg: &str) -> Result<PostFormat, String> {
+    match arg {
+        "json" => Ok(PostFormat::Json),
+        "ndjson" => Ok(PostFormat::Ndjson),
+        _ => Err(format!("unknown format {}, expected json or ndjson", arg)),
+    }
+}
+
+#[cfg(feature = "http")]
+fn parse_header(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err("expected a header as 'Name: value'".to_string()),
+    }
+}
+
+fn parse_interval(arg: &str) -> Result<Duration, String> {
+    let split = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (amount, unit) = arg.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected an interval like 15m, got {}", arg))?;
+
+    let unit_secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {}, expected s, m, h or d", unit)),
+    };
+
+    match amount {
+        0 => Err("the interval can't be zero".to_string()),
+        _ => Ok(Duration::from_secs(amount * unit_secs)),
+    }
+}
+
+fn parse_regex(arg: &str) -> Result<Regex, String> {
+    Regex::new(arg).map_err(|e| e.to_string())
+}
+
+fn parse_namespace_pattern(arg: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(arg).map_err(|e| e.to_string())
+}
+
+fn parse_header_collision(arg: &str) -> Result<HeaderCollision, String> {
+    match arg {
+        "disambiguate" => Ok(HeaderCollision::Disambiguate),
+        "error" => Ok(HeaderCollision::Error),
+        _ => Err(format!(
+            "unknown header collision handling {}, expected disambiguate or error",
+            arg
+        )),
+    }
+}
+
+fn parse_normal_form(arg: &str) -> Result<NormalForm, String> {
+    match arg {
+        "nfc" => Ok(NormalForm::Nfc),
+        "nfkc" => Ok(NormalForm::Nfkc),
+        _ => Err(format!("unknown normal form {}, expected nfc or nfkc", arg)),
+    }
+}
+
+fn parse_classify(arg: &str) -> Result<(String, Vec<(i64, String)>), String> {
+    let (objective, bins) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("{} is not OBJECTIVE=BOUND:LABEL,...", arg))?;
+
+    let mut bins = bins
+        .split(',')
+        .map(|bin| {
+            let (bound, label) = bin
+                .split_once(':')
+                .ok_or_else(|| format!("{} is not BOUND:LABEL", bin))?;
+
+            let bound = match bound {
+                "max" => i64::MAX,
+                _ => bound
+                    .parse()
+                    .map_err(|_| format!("{} is not an integer bound", bound))?,
+            };
+
+            Ok((bound, label.to_string()))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    bins.sort_by_key(|(bound, _)| *bound);
+
+    Ok((objective.to_string(), bins))
+}
+
+fn parse_column_order(arg: &str) -> Result<ColumnOrder, String> {
+    match arg {
+        "name" => Ok(ColumnOrder::Name),
+        "display" => Ok(ColumnOrder::Display),
+        "criteria" => Ok(ColumnOrder::Criteria),
+        "total-desc" => Ok(ColumnOrder::TotalDesc),
+        _ => Err(format!(
+            "unknown order {}, expected name, display, criteria or total-desc",
+            arg
+        )),
+    }
+}
+
+fn parse_team_aggregate(arg: &str) -> Result<TeamAggregate, String> {
+    match arg {
+        "sum" => Ok(TeamAggregate::Sum),
+        "max" => Ok(TeamAggregate::Max),
+        "mean" => Ok(TeamAggregate::Mean),
+        _ => Err(format!(
+            "unknown aggregate {}, expected sum, max or mean",
+            arg
+        )),
+    }
+}
+
+fn parse_combine(arg: &str) -> Result<(String, (Vec<String>, CombineMode)), String> {
+    let (name, combined) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("{} is not NAME=MODE:OBJECTIVE,...", arg))?;
+    let (mode, objectives) = combined
+        .split_once(':')
+        .ok_or_else(|| format!("{} is not MODE:OBJECTIVE,...", combined))?;
+
+    let mode = match mode {
+        "sum" => CombineMode::Sum,
+        "average" => CombineMode::Average,
+        _ => return Err(format!("unknown mode {}, expected sum or average", mode)),
+    };
+
+    Ok((
+        name.to_string(),
+        (objectives.split(',').map(String::from).collect(), mode),
+    ))
+}
+
+fn parse_error_format(arg: &str) -> Result<ErrorFormat, String> {
+    match arg {
+        "text" => Ok(ErrorFormat::Text),
+        "json" => Ok(ErrorFormat::Json),
+        _ => Err(format!(
+            "unknown error format {}, expected text or json",
+            arg
+        )),
+    }
+}
+
+fn parse_player_order(arg: &str) -> Result<PlayerOrder, String> {
+    match arg {
+        "lexical" => Ok(PlayerOrder::Lexical),
+        "natural" => Ok(PlayerOrder::Natural),
+        "none" => Ok(PlayerOrder::None),
+        _ => Err(format!(
+            "unknown player order {}, expected natural, lexical or none",
+            arg
+        )),
+    }
+}
+
+fn parse_header_names(arg: &str) -> Result<HeaderNames, String> {
+    match arg {
+        "display" => Ok(HeaderNames::Display),
+        "internal" => Ok(HeaderNames::Internal),
+        "friendly" => Ok(HeaderNames::Friendly),
+        _ => Err(format!(
+            "unknown header names {}, expected display, internal or friendly",
+            arg
+        )),
+    }
+}
+
+fn parse_computed(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, expr)) if !name.is_empty() => Ok((name.to_string(), expr.to_string())),
+        _ => Err("expected NAME=EXPRESSION".to_string()),
+    }
+}
+
+fn parse_factor(arg: &str) -> Result<(String, f64), String> {
+    match arg.split_once('=') {
+        Some((objective, factor)) if !objective.is_empty() => match factor.parse::<f64>() {
+            Ok(factor) if factor.is_normal() && factor > 0.0 => Ok((objective.to_string(), factor)),
+            _ => Err(format!("{} is not a positive number", factor)),
+        },
+        _ => Err("expected OBJECTIVE=NUMBER".to_string()),
+    }
+}
+
+fn parse_tenant_id(arg: &str) -> Result<String, String> {
+    match arg.chars().count() {
+        0 => Err("the tenant can't be empty".to_string()),
+        1..=64 => Ok(arg.to_string()),
+        _ => Err("the tenant can be at most 64 characters".to_string()),
+    }
+}
+
+fn parse_world(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => Err("expected NAME=PATH".to_string()),
+    }
+}
+
+fn parse_threshold(arg: &str) -> Result<(String, i64), String> {
+    match arg.split_once('=') {
+        Some((objective, score)) if !objective.is_empty() => Ok((
+            objective.to_string(),
+            score
+                .parse()
+                .map_err(|_| format!("{} is not a score", score))?,
+        )),
+        _ => Err("expected OBJECTIVE=SCORE".to_string()),
+    }
+}
+
+fn parse_time(arg: &str) -> Result<DateTime<Utc>, String> {
+    match DateTime::parse_from_rfc3339(arg) {
+        Ok(dt) => Ok(dt.to_utc()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn error_format_is_read_after_a_subcommand() {
+        let args = Args::parse_from(["nbttool", "summary", "in.dat", "--error-format", "json"]);
+
+        assert_eq!(args.error_format, ErrorFormat::Json);
+    }
+
+    #[test]
+    fn scale_multiplies_and_divide_divides() {
+        let args = Args::parse_from([
+            "nbttool",
+            "in.dat",
+            "--scale",
+            "money=0.01",
+            "--divide",
+            "walk=100",
+            "--scale-precision",
+            "1",
+        ]);
+
+        assert_eq!(args.csv.scale, [("money".to_string(), 0.01)]);
+        assert_eq!(args.csv.divide, [("walk".to_string(), 100.0)]);
+        assert!(Args::try_parse_from(["nbttool", "in.dat", "--scale-precision", "1"]).is_err());
+        for factor in ["money=0", "money=-2", "money=inf", "money=NaN"] {
+            assert!(Args::try_parse_from(["nbttool", "in.dat", "--scale", factor]).is_err());
+            assert!(Args::try_parse_from(["nbttool", "in.dat", "--divide", factor]).is_err());
+        }
+    }
+
+    #[test]
+    fn flag_thresholds_are_objective_and_score() {
+        let args = Args::parse_from([
+            "nbttool",
+            "in.dat",
+            "--flag-above",
+            "kills=10000",
+            "--flag-above",
+            "deaths=-1",
+        ]);
+
+        assert_eq!(
+            args.csv.flag_above,
+            [("kills".to_string(), 10000), ("deaths".to_string(), -1)]
+        );
+        assert!(Args::try_parse_from(["nbttool", "in.dat", "--flag-above", "kills"]).is_err());
+        assert!(Args::try_parse_from(["nbttool", "in.dat", "--flag-above", "=5"]).is_err());
+        assert!(Args::try_parse_from(["nbttool", "in.dat", "--flag-above", "kills=lots"]).is_err());
+    }
+}
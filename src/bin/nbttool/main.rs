@@ -0,0 +1,2098 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
+use futures::executor::block_on;
+#[cfg(feature = "http")]
+use poop_scoreboard::clickhouse::ClickHouseOptions;
+#[cfg(feature = "daemon")]
+use poop_scoreboard::daemon::{self, DaemonOptions};
+#[cfg(feature = "http")]
+use poop_scoreboard::mojang::{self, MojangOptions};
+#[cfg(feature = "http")]
+use poop_scoreboard::post::PostOptions;
+#[cfg(feature = "server")]
+use poop_scoreboard::server::ScoreServer;
+use poop_scoreboard::{
+    atomic_file::AtomicFile,
+    edit::Scoreboard,
+    error::Error,
+    inspect::{find_path, read_nbt, to_json, write_tree, InspectOptions},
+    stats::{
+        scoreboard_path, stream_csv_long, stream_scores, write_objectives_to_sql, ComputedColumn,
+        CsvImportOptions, CsvOptions, EncryptionKey, ExportOptions, FlagCondition, HeaderMap,
+        NameMerge, NameNormalization, ObjectiveFormatter, ScoreDiff, SqlDialect, SqlOptions, Stats,
+        TimeSeries, UniqueNames, UuidMap, Worlds,
+    },
+};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{Connection, MySqlConnection};
+use uuid::Uuid;
+
+use args::{Args, CheckFormat, Command, EditOutput, ErrorFormat, WorldsArgs};
+
+mod args;
+
+/// Exit code when reading the input or every destination failed
+const EXIT_EXPORT_FAILED: u8 = 1;
+/// Exit code when everything but posting the stats succeeded
+const EXIT_POST_FAILED: u8 = 3;
+/// Exit code when some of the destinations failed and the rest were written
+const EXIT_PARTIAL_FAILURE: u8 = 4;
+/// Exit code when the scoreboard is smaller than --min-players or
+/// --min-objectives, nothing is written
+const EXIT_TOO_SMALL: u8 = 5;
+/// Exit code when there are no scores to export with --fail-on-empty,
+/// nothing is written
+const EXIT_EMPTY: u8 = 6;
+
+/// Exit code of check when the scoreboard differs from the export or the
+/// export is too old
+const EXIT_CHECK_DIFFERS: u8 = 1;
+/// Exit code of check when either file couldn't be read
+const EXIT_CHECK_FAILED: u8 = 2;
+
+/// Reported for scoreboards without a single score
+const EMPTY_MESSAGE: &str = "The scoreboard has no scores to export";
+
+enum Failure {
+    Export(Error),
+    /// There were no scores to export with --fail-on-empty
+    Empty,
+    /// Fewer players or objectives than required, like `("players", 3, 50)`
+    TooSmall(&'static str, usize, usize),
+    /// The destinations that failed out of `total`
+    Destinations(Vec<(&'static str, Error)>, usize),
+}
+
+impl<E: Into<Error>> From<E> for Failure {
+    fn from(value: E) -> Self {
+        Self::Export(value.into())
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+
+    let result = match args.command.take() {
+        Some(Command::Check {
+            input,
+            against,
+            format,
+            max_age,
+            json,
+        }) => {
+            return match check(&input, &against, format, max_age, json) {
+                Ok(true) => ExitCode::SUCCESS,
+                Ok(false) => ExitCode::from(EXIT_CHECK_DIFFERS),
+                Err(e) => {
+                    match args.error_format {
+                        ErrorFormat::Text => eprintln!("Error: {:?}", e),
+                        ErrorFormat::Json => {
+                            let mut report = error_json(&e);
+                            report["code"] = json!(EXIT_CHECK_FAILED);
+                            report["source"] = json!(input);
+                            eprintln!("{}", report);
+                        }
+                    }
+                    ExitCode::from(EXIT_CHECK_FAILED)
+                }
+            };
+        }
+        Some(command) => run_command(command).map_err(Failure::Export),
+        None => run(&args),
+    };
+
+    let Err(failure) = result else {
+        return ExitCode::SUCCESS;
+    };
+
+    let code = exit_code(&failure);
+
+    match (args.error_format, failure) {
+        (ErrorFormat::Text, Failure::Export(e)) => eprintln!("Error: {:?}", e),
+        (ErrorFormat::Text, Failure::Empty) => eprintln!("Error: {}", EMPTY_MESSAGE),
+        (ErrorFormat::Text, Failure::TooSmall(what, observed, required)) => {
+            eprintln!("Error: {}", too_small_message(what, observed, required))
+        }
+        (ErrorFormat::Text, Failure::Destinations(failed, total)) => {
+            for (destination, e) in failed.iter() {
+                eprintln!("Error writing {}: {:?}", destination, e);
+            }
+            eprintln!("{} of {} destinations failed", failed.len(), total);
+        }
+        (ErrorFormat::Json, failure) => {
+            let mut report = match failure {
+                Failure::Export(e) => error_json(&e),
+                Failure::Empty => json!({
+                    "kind": "empty",
+                    "message": EMPTY_MESSAGE,
+                    "details": {},
+                }),
+                Failure::TooSmall(what, observed, required) => json!({
+                    "kind": "too_small",
+                    "message": too_small_message(what, observed, required),
+                    "details": {
+                        "count": what,
+                        "observed": observed,
+                        "required": required,
+                    },
+                }),
+                Failure::Destinations(failed, total) => json!({
+                    "message": format!("{} of {} destinations failed", failed.len(), total),
+                    "errors": failed
+                        .iter()
+                        .map(|(destination, e)| {
+                            let mut error = error_json(e);
+                            error["destination"] = json!(destination);
+                            error
+                        })
+                        .collect::<Vec<_>>(),
+                }),
+            };
+
+            report["code"] = json!(code);
+            report["source"] = json!(args.input_file);
+            eprintln!("{}", report);
+        }
+    }
+
+    ExitCode::from(code)
+}
+
+/// The exit code of a failed export, listed in the help by [`args::EXIT_CODES`]
+fn exit_code(failure: &Failure) -> u8 {
+    match failure {
+        Failure::Export(_) => EXIT_EXPORT_FAILED,
+        Failure::Empty => EXIT_EMPTY,
+        Failure::TooSmall(..) => EXIT_TOO_SMALL,
+        Failure::Destinations(failed, total) => match failed.len() == *total {
+            true => EXIT_EXPORT_FAILED,
+            false if failed.iter().all(|(destination, _)| *destination == "post") => {
+                EXIT_POST_FAILED
+            }
+            false => EXIT_PARTIAL_FAILURE,
+        },
+    }
+}
+
+fn too_small_message(what: &str, observed: usize, required: usize) -> String {
+    format!(
+        "The scoreboard has {} {} after filtering, at least {} are required",
+        observed, what, required
+    )
+}
+
+/// The kind, message and whatever structured details an error carries
+fn error_json(e: &Error) -> serde_json::Value {
+    let (kind, details) = error_details(e);
+
+    json!({
+        "kind": kind,
+        "message": e.to_string(),
+        "details": details,
+    })
+}
+
+fn error_details(e: &Error) -> (&'static str, serde_json::Value) {
+    match e {
+        Error::NBTError(_) => ("nbt", json!({})),
+        Error::IOError(e) => ("io", json!({ "io_kind": format!("{:?}", e.kind()) })),
+        Error::SerdeJsonError(e) => ("json", json!({ "line": e.line(), "column": e.column() })),
+        Error::CSVError(e) => (
+            "csv",
+            json!({
+                "line": e.position().map(|x| x.line()),
+                "record": e.position().map(|x| x.record()),
+            }),
+        ),
+        Error::SQLXError(e) => ("sql", sql_details(e)),
+        Error::SQLStatement { statement, error } => {
+            let mut details = sql_details(error);
+            details["statement"] = json!(statement);
+            ("sql", details)
+        }
+        Error::BincodeError(_) => ("cache", json!({})),
+        Error::TOMLError(e) => ("toml", json!({ "span": e.span() })),
+        #[cfg(feature = "http")]
+        Error::HTTPError(_) => ("http", json!({})),
+        Error::HTTPStatus(status, body) => {
+            ("http_status", json!({ "status": status, "body": body }))
+        }
+        Error::UnknownObjective(name) => ("unknown_objective", json!({ "objective": name })),
+        Error::UnknownPlayer(name) => ("unknown_player", json!({ "player": name })),
+        Error::InvalidExpression(_) => ("invalid_expression", json!({})),
+        Error::PostBodyTooLarge(max) => ("post_body_too_large", json!({ "max_len": max })),
+        Error::NBTMissingField(field) => ("nbt_missing_field", json!({ "field": field })),
+        Error::NBTWrongType { field, expected } => (
+            "nbt_wrong_type",
+            json!({ "field": field, "expected": expected }),
+        ),
+        Error::NBTAt { path, index, error } => {
+            let (kind, mut details) = error_details(error);
+            details["path"] = json!(path);
+            details["index"] = json!(index);
+            (kind, details)
+        }
+        Error::UnknownNBTPath(path) => ("unknown_nbt_path", json!({ "path": path })),
+        Error::SchemaTooNew(found, supported) => (
+            "schema_too_new",
+            json!({ "version": found, "supported_version": supported }),
+        ),
+        Error::SQLTimeout(operation) => ("sql_timeout", json!({ "operation": operation })),
+        Error::InvalidSchemaPrefix(prefix) => {
+            ("invalid_schema_prefix", json!({ "prefix": prefix }))
+        }
+        Error::InvalidKeyBlockSize(size) => ("invalid_key_block_size", json!({ "size": size })),
+        Error::InvalidEncryptionKey => ("invalid_encryption_key", json!({})),
+        Error::UndecryptablePlayerName(name) => {
+            ("undecryptable_player_name", json!({ "player_name": name }))
+        }
+        Error::DuplicateHeader(header) => ("duplicate_header", json!({ "header": header })),
+        Error::Template {
+            name, line, column, ..
+        } => (
+            "template",
+            json!({ "template": name, "line": line, "column": column }),
+        ),
+        #[allow(deprecated)]
+        Error::IncorrectFlags | Error::IncorrecFlags => ("incorrect_flags", json!({})),
+        #[allow(deprecated)]
+        Error::LOLError | Error::NOTLOLError => ("invalid_nbt", json!({})),
+    }
+}
+
+fn sql_details(e: &sqlx::Error) -> serde_json::Value {
+    match e.as_database_error() {
+        Some(db) => json!({ "sql_state": db.code(), "database_message": db.message() }),
+        None => json!({}),
+    }
+}
+
+/// Parses the input once and writes it to every destination given, a
+/// failed destination doesn't stop the others from being written
+fn run(args: &Args) -> Result<(), Failure> {
+    let input_file = args
+        .input_file
+        .as_deref()
+        .expect("clap requires the input file without a subcommand");
+
+    let sql_opts = SqlOptions {
+        connect_timeout: Duration::from_secs(args.sql.connect_timeout_secs),
+        schema_prefix: args.sql.schema_prefix.clone(),
+        partition_by_date: args.sql.partition_by_date,
+        region: args.sql.region.clone(),
+        tenant_id: args.sql.tenant_id.clone(),
+        use_compression: args.sql.row_compression,
+        key_block_size: args.sql.key_block_size,
+        soft_schema: args.sql.soft_schema,
+        fulltext_player_index: args.sql.fulltext_index,
+        auto_increment_id: args.sql.auto_increment_id,
+        encrypt_player_names: match &args.sql.encryption_key_file {
+            Some(path) => Some(EncryptionKey::from_hex(&fs::read_to_string(path)?)?),
+            None => None,
+        },
+        player_order: args.filter.sort_players.unwrap_or_default(),
+        ttl_days: args.sql.ttl_days,
+        aggregates: args.sql.aggregates,
+        scaled_scores: match args.sql.scale_sql {
+            true => scale_factors(args).collect(),
+            false => HashMap::new(),
+        },
+        scaled_decimals: args.csv.scale_decimals,
+        ..Default::default()
+    };
+
+    let timestamp = match args.sql.timestamp {
+        Some(t) => t,
+        None => Utc::now(),
+    };
+
+    let template = load_template(args)?;
+
+    //csv stays the default when no other output is given
+    let output_path = match (&args.output_file, &args.sql.sql_url) {
+        _ if args.files.split_by_objective => None,
+        (Some(path), _) => Some(path.clone()),
+        (None, None) => Some(match &template {
+            Some((name, _)) => input_file.with_extension(template_extension(name)),
+            None => input_file.with_extension("csv"),
+        }),
+        (None, Some(_)) => None,
+    };
+
+    if output_path.as_deref() == Some(input_file) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the output would replace the input {}",
+                input_file.display()
+            ),
+        )
+        .into());
+    }
+
+    if let (false, Some(path)) = (args.force, &output_path) {
+        let schema_path = args.csv.with_schema.then(|| datapackage_path(path));
+
+        for path in [Some(path), schema_path.as_ref()].into_iter().flatten() {
+            if path.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "{} already exists, use --force to replace it",
+                        path.display()
+                    ),
+                )
+                .into());
+            }
+        }
+    }
+
+    if args.streaming {
+        return stream(
+            args,
+            input_file,
+            output_path.as_deref(),
+            timestamp,
+            &sql_opts,
+        );
+    }
+
+    let mut stats = load_stats(args, input_file)?;
+
+    if args.filter.drop_empty_players {
+        let computed = computed_columns(args, &stats)?;
+        let dropped = stats.drop_empty_players(&computed);
+        if args.verbose {
+            eprintln!("Dropped {} players without any nonzero score", dropped);
+        }
+    }
+
+    if stats.is_empty() {
+        match args.fail_on_empty {
+            true => return Err(Failure::Empty),
+            false => eprintln!("Warning: {}, only headers are written", EMPTY_MESSAGE),
+        }
+    }
+
+    let counts = [
+        ("players", stats.player_count(), args.min_players),
+        ("objectives", stats.objectives().len(), args.min_objectives),
+    ];
+    for (what, observed, required) in counts {
+        match required {
+            Some(required) if observed < required => {
+                return Err(Failure::TooSmall(what, observed, required))
+            }
+            _ => {}
+        }
+    }
+
+    //every output sees the teams as players
+    if args.teams.group_by.is_some() {
+        stats = stats.aggregate_by_team(args.teams.team_agg);
+        if args.verbose {
+            eprintln!("Grouped the scores into {} teams", stats.teams().len());
+        }
+    }
+
+    let mut results = Vec::new();
+
+    match (&output_path, &template) {
+        (Some(path), Some((name, source))) => results.push((
+            "template",
+            write_template(path, &stats, name, source, timestamp),
+        )),
+        (Some(path), None) => results.push(("csv", export_csv(args, path, &stats))),
+        (None, _) => {}
+    }
+
+    if let (true, Some(dir)) = (args.files.split_by_objective, &args.files.output_dir) {
+        results.push(("csv", export_split(dir, args.files.include_empty, &stats)));
+    }
+
+    if let Some(path) = &args.sql_files.cdc_triggers {
+        results.push((
+            "cdc triggers",
+            write_cdc_triggers(path, &stats, args.sql_files.sql_dialect),
+        ));
+    }
+
+    if let Some(path) = &args.sql_files.create_materialized_view {
+        results.push((
+            "materialized view",
+            write_materialized_view(path, &stats, args.sql_files.sql_dialect),
+        ));
+    }
+
+    if let Some(path) = &args.sql_files.row_level_security {
+        results.push((
+            "row level security",
+            write_row_level_security(path, &stats, &sql_opts, args.sql_files.sql_dialect),
+        ));
+    }
+
+    if let Some(path) = &args.sql_files.cross_server_view {
+        results.push((
+            "cross server view",
+            write_cross_server_view(
+                path,
+                &stats,
+                &args.sql_files.compare_regions,
+                args.sql_files.sql_dialect,
+            ),
+        ));
+    }
+
+    if let Some(path) = &args.files.histogram {
+        results.push((
+            "histogram",
+            write_histogram(path, &stats, args.files.histogram_bins),
+        ));
+    }
+
+    if let Some(sql) = &args.sql.sql_url {
+        let result = match (args.sql.event_log, args.sql.wide_table) {
+            (true, _) => write_event_log(&stats, sql, timestamp, &sql_opts, args.sql.create_tables),
+            (false, true) => write_wide_table(&stats, sql, timestamp, &sql_opts),
+            (false, false) => write_sql(&stats, sql, timestamp, &sql_opts, args.sql.create_tables),
+        };
+        results.push(("sql", result));
+    }
+
+    #[cfg(feature = "postgres")]
+    if let Some(url) = &args.sql.postgres_url {
+        results.push((
+            "postgres",
+            write_postgres_jsonb(&stats, url, timestamp, &sql_opts),
+        ));
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(url) = &args.http.post_url {
+        results.push(("post", post(args, url, &stats)));
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(url) = &args.http.clickhouse_url {
+        results.push(("clickhouse", write_clickhouse(args, url, &stats, timestamp)));
+    }
+
+    destinations_failure(results)
+}
+
+/// The UUIDs of players missing from --uuid-map according to the Mojang
+/// API, when asked for. Failed lookups only warn.
+#[cfg(feature = "http")]
+fn mojang_uuids(args: &Args, stats: &Stats) -> Result<Option<UuidMap>, Error> {
+    if args.csv.resolve_uuids.is_none() {
+        return Ok(None);
+    }
+
+    let known = match &args.csv.uuid_map {
+        Some(path) => poop_scoreboard::stats::read_usercache(path)?,
+        None => UuidMap::new(),
+    };
+    let players = stats.get_player_list();
+    let names: Vec<&str> = players
+        .iter()
+        .map(String::as_str)
+        .filter(|x| !known.contains_key(*x))
+        .collect();
+
+    println!("Looking up {} UUIDs with the Mojang API", names.len());
+
+    let lookup = mojang::resolve_uuids(
+        &names,
+        &MojangOptions {
+            cache_dir: args.cache_dir.clone(),
+            ..Default::default()
+        },
+    );
+
+    for name in lookup.not_found.iter() {
+        eprintln!(
+            "Warning: {} has no Mojang profile, its UUID is left blank",
+            name
+        );
+    }
+    for e in lookup.errors.iter() {
+        eprintln!("Warning: looking up UUIDs failed: {}", e);
+    }
+    if !lookup.failed.is_empty() {
+        eprintln!(
+            "Warning: the UUIDs of {} players are left blank after failed lookups",
+            lookup.failed.len()
+        );
+    }
+
+    Ok(Some(lookup.uuids))
+}
+
+#[cfg(not(feature = "http"))]
+fn mojang_uuids(_: &Args, _: &Stats) -> Result<Option<UuidMap>, Error> {
+    Ok(None)
+}
+
+/// The multiplier of every objective given to --scale or --divide
+fn scale_factors(args: &Args) -> impl Iterator<Item = (String, f64)> + '_ {
+    args.csv.scale.iter().cloned().chain(
+        args.csv
+            .divide
+            .iter()
+            .map(|(objective, divisor)| (objective.clone(), 1.0 / divisor)),
+    )
+}
+
+fn export_csv(args: &Args, path: &Path, stats: &Stats) -> Result<(), Error> {
+    if args.long {
+        let mut file = AtomicFile::create(path)?;
+        stats.write_csv_long(&mut file)?;
+        return Ok(file.commit()?);
+    }
+
+    if let Some(snapshots) = &args.snapshots.average_with {
+        return write_moving_average(args, path, stats, snapshots);
+    }
+
+    if let Some(snapshots) = &args.snapshots.velocity_with {
+        return write_velocity(args, path, stats, snapshots);
+    }
+
+    if let Some(previous) = &args.snapshots.activity_flag {
+        return write_activity_flag(args, path, stats, previous);
+    }
+
+    if let Some(snapshots) = &args.snapshots.trend_with {
+        return write_sparklines(args, path, stats, snapshots);
+    }
+
+    if !args.csv.preserve_raw_names {
+        for (raw, stripped) in stats.formatted_names() {
+            eprintln!(
+                "Warning: writing {:?} as {:?} without formatting",
+                raw, stripped
+            );
+        }
+    }
+
+    let uuids = mojang_uuids(args, stats)?;
+
+    let header_map = export_header_map(args)?;
+    for objective in header_map.unknown_objectives(stats) {
+        eprintln!(
+            "Warning: --header-map renames {:?}, which isn't an objective",
+            objective
+        );
+    }
+
+    let opts = CsvOptions {
+        player_groups: match &args.csv.groups {
+            Some(path) => Some(serde_json::from_reader(File::open(path)?)?),
+            None => None,
+        },
+        use_uuid_as_key: args.csv.uuid_map.is_some() || uuids.is_some(),
+        uuid_map_path: args.csv.uuid_map.clone(),
+        uuids,
+        unicode_bars: args.csv.unicode_bars,
+        formatters: scale_factors(args)
+            .map(|(objective, scale)| {
+                (
+                    objective,
+                    ObjectiveFormatter::Scale {
+                        scale,
+                        suffix: String::new(),
+                        decimal_places: args.csv.scale_decimals,
+                    },
+                )
+            })
+            .chain(
+                args.csv
+                    .ticks_to_time
+                    .iter()
+                    .map(|objective| (objective.clone(), ObjectiveFormatter::TicksToTime)),
+            )
+            .collect(),
+        base64_player_names: args.csv.base64_player_names,
+        header_names: args.csv.header_names,
+        header_map,
+        group_objectives_by_prefix: args.csv.group_by_prefix,
+        on_header_collision: args.csv.on_header_collision,
+        player_order: args.filter.sort_players.unwrap_or_default(),
+        annotate_outliers: args.csv.annotate_outliers,
+        bom: args.csv.bom,
+        classify: args.csv.classify.iter().cloned().collect(),
+        percentile_columns: args.csv.percentiles,
+        combine_objectives: args.csv.combine.iter().cloned().collect(),
+        hide_sources: args.csv.hide_sources,
+        column_order: args.csv.order_columns,
+        float_precision: args.csv.float_precision,
+        row_hash: args.csv.row_hash,
+        team_rows: args.teams.group_by.is_some(),
+        team_size_column: args.teams.team_size_column,
+        dense_rank_columns: args.csv.dense_rank,
+        preserve_raw_names: args.csv.preserve_raw_names,
+        empty_missing_scores: args.filter.min_score.is_some()
+            || !args.filter.min_score_objective.is_empty(),
+        computed_columns: computed_columns(args, stats)?,
+        change_arrows: match &args.snapshots.compare_with {
+            Some(path) => Some(Arc::new(load_stats(args, path)?)),
+            None => None,
+        },
+        flag_condition: match args.csv.flag_above.is_empty() {
+            true => None,
+            false => {
+                let thresholds: HashMap<String, i64> =
+                    args.csv.flag_above.iter().cloned().collect();
+                Some(FlagCondition::new(move |_, objective, score| {
+                    thresholds.get(objective).is_some_and(|max| score > *max)
+                }))
+            }
+        },
+    };
+
+    write_csv(stats, &opts, AtomicFile::create(path)?)?;
+
+    //only a csv that was written gets a descriptor
+    if args.csv.with_schema {
+        let mut schema_file = AtomicFile::create(datapackage_path(path))?;
+        stats.write_datapackage(&mut schema_file, path, &opts, Utc::now())?;
+        schema_file.commit()?;
+    }
+
+    Ok(())
+}
+
+/// The descriptor of `stats.csv` is `stats.datapackage.json`, so csvs in
+/// the same directory don't overwrite each other's
+fn datapackage_path(csv_path: &Path) -> PathBuf {
+    csv_path.with_extension("datapackage.json")
+}
+
+#[cfg(feature = "http")]
+fn post(args: &Args, url: &str, stats: &Stats) -> Result<(), Error> {
+    let opts = PostOptions {
+        url: url.to_string(),
+        format: args.http.post_format,
+        headers: args.http.header.clone(),
+        retries: args.http.post_retries,
+        timeout: Duration::from_secs(args.http.post_timeout),
+        ..Default::default()
+    };
+
+    opts.post(stats)?;
+    println!("Posted stats to {}", url);
+
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+fn write_clickhouse(
+    args: &Args,
+    url: &str,
+    stats: &Stats,
+    timestamp: DateTime<Utc>,
+) -> Result<(), Error> {
+    let opts = ClickHouseOptions {
+        url: url.to_string(),
+        user: args.http.clickhouse_user.clone(),
+        password: args.http.clickhouse_password.clone(),
+        table: args.http.clickhouse_table.clone(),
+        batch_size: args.http.clickhouse_batch_size,
+        ..Default::default()
+    };
+
+    let inserted = opts.insert(stats, timestamp)?;
+    println!("Inserted {} scores into ClickHouse", inserted);
+
+    Ok(())
+}
+
+fn run_command(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Check { .. } => unreachable!("check is run by main"),
+        Command::Summary {
+            input,
+            stats,
+            json,
+            objectives,
+            players,
+            include_namespace,
+            criteria,
+            exclude_criteria,
+        } => summarize(
+            &input,
+            stats,
+            json,
+            &ExportOptions {
+                objectives,
+                players,
+                namespaces: include_namespace,
+                criteria,
+                exclude_criteria,
+                ..Default::default()
+            },
+        ),
+        Command::Inspect {
+            input,
+            path,
+            depth,
+            full,
+            json,
+        } => inspect(
+            &input,
+            path.as_deref(),
+            &InspectOptions { depth, full },
+            json,
+        ),
+        Command::Set {
+            input,
+            player,
+            objective,
+            value,
+            output,
+        } => edit_scoreboard(&input, &output, |scoreboard| {
+            match scoreboard.set_score(&player, &objective, value)? {
+                true => println!("Set {} of {} to {}", objective, player, value),
+                false => println!("Added {} of {} as {}", objective, player, value),
+            }
+            Ok(())
+        }),
+        Command::RemovePlayer {
+            input,
+            name,
+            output,
+        } => edit_scoreboard(&input, &output, |scoreboard| {
+            let removed = scoreboard.remove_player(&name)?;
+            println!("Removed {} scores of {}", removed, name);
+            Ok(())
+        }),
+        Command::RemoveObjective {
+            input,
+            name,
+            output,
+        } => edit_scoreboard(&input, &output, |scoreboard| {
+            let removed = scoreboard.remove_objective(&name)?;
+            println!("Removed {} with {} scores", name, removed);
+            Ok(())
+        }),
+        Command::Worlds(args) => export_worlds(&args),
+        Command::InitDb {
+            sql_url,
+            upgrade,
+            dry_run,
+            schema_prefix,
+            partition_by_date,
+            objectives_from,
+        } => init_db(
+            &sql_url,
+            upgrade,
+            dry_run,
+            objectives_from.as_deref(),
+            &SqlOptions {
+                schema_prefix,
+                partition_by_date,
+                ..Default::default()
+            },
+        ),
+        Command::ListObjectives { input, json } => list_objectives(&input, json),
+        Command::ExcludePlayer {
+            player,
+            sql_url,
+            schema_prefix,
+        } => set_player_excluded(&sql_url, &player, true, schema_prefix),
+        Command::IncludePlayer {
+            player,
+            sql_url,
+            schema_prefix,
+        } => set_player_excluded(&sql_url, &player, false, schema_prefix),
+        Command::Timeseries {
+            glob,
+            objective,
+            date_from_filename,
+            output_file,
+            long,
+        } => write_timeseries(&glob, objective, &date_from_filename, &output_file, long),
+        #[cfg(feature = "server")]
+        Command::Serve { input, listen } => serve(&input, &listen),
+        #[cfg(feature = "daemon")]
+        Command::Daemon {
+            input,
+            sql_url,
+            every,
+            run_once_on_start,
+            create_tables,
+            schema_prefix,
+            partition_by_date,
+            region,
+        } => daemon::run_with_events(
+            &DaemonOptions {
+                input,
+                sql_url,
+                every,
+                run_once_on_start,
+                create_tables,
+                sql: SqlOptions {
+                    schema_prefix,
+                    partition_by_date,
+                    region,
+                    ..Default::default()
+                },
+            },
+            |event| {
+                println!(
+                    "time={} {}",
+                    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    event
+                )
+            },
+        ),
+        #[cfg(feature = "http")]
+        Command::Announce {
+            input,
+            webhook_url,
+            objective,
+            limit,
+            title,
+            plain,
+        } => {
+            let stats = Stats::from_gzip_reader(File::open(input)?)?;
+            let entries = stats
+                .leaderboard(&objective, limit)
+                .ok_or_else(|| Error::UnknownObjective(objective.clone()))?;
+            let title =
+                title.unwrap_or_else(|| stats.objectives()[&objective].display_name().to_string());
+
+            poop_scoreboard::discord::post_leaderboard(&webhook_url, &title, &entries, plain)?;
+            println!("Posted {} scores to Discord", entries.len());
+
+            Ok(())
+        }
+    }
+}
+
+/// What `check --json` prints, the scores are `old` in the input and `new`
+/// in the export
+#[derive(Serialize)]
+struct CheckReport<'a> {
+    /// Whether every score is the same
+    matches: bool,
+    /// Whether the export is older than --max-age
+    stale: bool,
+    /// Seconds since the export was modified
+    age_secs: u64,
+    differences: &'a [ScoreDiff],
+}
+
+/// Compares the scoreboard with an export of it, printing a summary of the
+/// differences. Returns whether they match and the export is new enough.
+fn check(
+    input: &Path,
+    against: &Path,
+    format: CheckFormat,
+    max_age: Option<Duration>,
+    json: bool,
+) -> Result<bool, Error> {
+    let stats = Stats::from_gzip_reader(File::open(input)?)?;
+    let modified = fs::metadata(against)?.modified()?;
+
+    let mut diffs = match format {
+        CheckFormat::Json => stats.diff(&serde_json::from_reader(File::open(against)?)?),
+        CheckFormat::Csv => {
+            //the csv headers are display names
+            let opts = CsvImportOptions {
+                header_map: stats
+                    .objectives()
+                    .iter()
+                    .map(|(name, objective)| (objective.display_name().to_string(), name.clone()))
+                    .collect(),
+                ..Default::default()
+            };
+
+            stats.diff(&Stats::from_csv_reader_with(File::open(against)?, &opts)?)
+        }
+    };
+
+    //the csv writes 0 for missing scores
+    if format == CheckFormat::Csv {
+        diffs.retain(|diff| !matches!((diff.old, diff.new), (None, Some(0)) | (Some(0), None)));
+    }
+
+    let age = modified.elapsed().unwrap_or_default();
+    let stale = max_age.is_some_and(|max_age| age > max_age);
+
+    if json {
+        let report = CheckReport {
+            matches: diffs.is_empty(),
+            stale,
+            age_secs: age.as_secs(),
+            differences: &diffs,
+        };
+        println!("{}", serde_json::to_string(&report)?);
+
+        return Ok(report.matches && !stale);
+    }
+
+    match diffs.len() {
+        0 => println!("{} matches {}", input.display(), against.display()),
+        n => {
+            println!(
+                "{} scores differ between {} and {}",
+                n,
+                input.display(),
+                against.display()
+            );
+
+            let score = |score: Option<i64>| score.map_or("-".to_string(), |x| x.to_string());
+            for diff in diffs.iter().take(10) {
+                println!(
+                    "  {} {}: {} -> {}",
+                    diff.objective,
+                    diff.player,
+                    score(diff.old),
+                    score(diff.new)
+                );
+            }
+        }
+    }
+
+    if let (true, Some(max_age)) = (stale, max_age) {
+        println!(
+            "{} was modified {}s ago, more than the allowed {}s",
+            against.display(),
+            age.as_secs(),
+            max_age.as_secs()
+        );
+    }
+
+    Ok(diffs.is_empty() && !stale)
+}
+
+fn summarize(
+    input: &Path,
+    per_objective: bool,
+    json: bool,
+    opts: &ExportOptions,
+) -> Result<(), Error> {
+    let mut stats = Stats::from_gzip_reader(File::open(input)?)?;
+    stats.filter(opts);
+
+    if !per_objective {
+        let summary = stats.summary();
+
+        match json {
+            true => println!("{}", serde_json::to_string(&summary)?),
+            false => println!(
+                "{} players with scores in {} objectives",
+                summary.players, summary.objectives
+            ),
+        }
+
+        return Ok(());
+    }
+
+    let summaries = stats.objective_summaries();
+
+    if json {
+        println!("{}", serde_json::to_string(&summaries)?);
+
+        return Ok(());
+    }
+
+    let mut rows = vec![[
+        "Objective",
+        "Category",
+        "Players",
+        "Min",
+        "Max",
+        "Mean",
+        "Median",
+        "Total",
+    ]
+    .map(String::from)];
+
+    for summary in summaries {
+        rows.push(match summary.stats {
+            Some(x) => [
+                summary.objective,
+                summary.category,
+                x.count.to_string(),
+                x.min.to_string(),
+                x.max.to_string(),
+                format!("{:.2}", x.mean),
+                x.median.to_string(),
+                x.total.to_string(),
+            ],
+            None => {
+                let mut row = ["-"; 8].map(String::from);
+                row[0] = summary.objective;
+                row[1] = summary.category;
+                row[2] = "0".to_string();
+                row
+            }
+        });
+    }
+
+    let widths: Vec<usize> = (0..8)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for row in rows {
+        let mut line = format!("{:<2$}  {:<3$}", row[0], row[1], widths[0], widths[1]);
+        for (cell, width) in row.iter().zip(&widths).skip(2) {
+            line.push_str(&format!("  {:>1$}", cell, width));
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn write_timeseries(
+    pattern: &str,
+    objective: String,
+    date_format: &str,
+    output_file: &Path,
+    long: bool,
+) -> Result<(), Error> {
+    let paths = glob::glob(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut series = TimeSeries::new(objective);
+
+    for path in paths {
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Skipping {}: {:?}", e.path().display(), e.error());
+                continue;
+            }
+        };
+
+        let Some(date) = date_in_file_name(&path, date_format) else {
+            eprintln!("Skipping {}: no date in the file name", path.display());
+            continue;
+        };
+
+        match File::open(&path)
+            .map_err(Error::from)
+            .and_then(Stats::from_gzip_reader)
+        {
+            Ok(stats) => series.add(date, &stats),
+            Err(e) => {
+                eprintln!("Skipping {}: {:?}", path.display(), e);
+                series.add_missing(date);
+            }
+        }
+    }
+
+    let mut file = AtomicFile::create(output_file)?;
+    match long {
+        true => series.write_csv_long(&mut file)?,
+        false => series.write_csv(&mut file)?,
+    }
+    file.commit()?;
+
+    println!("Wrote time series to {}", output_file.display());
+
+    Ok(())
+}
+
+/// The first date in the file name that matches `format`
+fn date_in_file_name(path: &Path, format: &str) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_string_lossy();
+
+    //a separator in front of the year would otherwise be read as its sign
+    name.char_indices()
+        .filter(|(_, c)| !matches!(c, '-' | '+'))
+        .find_map(|(i, _)| NaiveDate::parse_and_remainder(&name[i..], format).ok())
+        .map(|(date, _)| date)
+}
+
+fn export_options(args: &Args) -> ExportOptions {
+    ExportOptions {
+        objectives: args.filter.objectives.clone(),
+        players: args.filter.players.clone(),
+        include_players: args.filter.include_players_regex.clone(),
+        exclude_players: args.filter.exclude_players_regex.clone(),
+        namespaces: args.filter.include_namespace.clone(),
+        criteria: args.filter.criteria.clone(),
+        exclude_criteria: args.filter.exclude_criteria.clone(),
+        min_score: args.filter.min_score,
+        min_scores: args.filter.min_score_objective.iter().cloned().collect(),
+    }
+}
+
+fn load_stats(args: &Args, input_file: &Path) -> Result<Stats, Error> {
+    let normalization = NameNormalization {
+        form: args.filter.normalize_names,
+        strip_invisible: args.filter.strip_invisible,
+    };
+
+    let (mut stats, merges) = read_stats(args, input_file, &normalization)?;
+
+    for merge in merges {
+        eprintln!(
+            "Merged {:?} into {:?} in {}",
+            merge.merged,
+            merge.name,
+            input_file.display()
+        );
+    }
+
+    //patterns see the normalized names
+    let export_options = export_options(args);
+    if args.verbose {
+        for pattern in export_options.unmatched_player_patterns(&stats) {
+            eprintln!("No player in {} matches {}", input_file.display(), pattern);
+        }
+    }
+
+    stats.filter(&export_options);
+
+    //the json and sql scores follow the same order as the csv rows
+    if let Some(order) = args.filter.sort_players {
+        stats.sort_scores(order);
+    }
+
+    Ok(stats)
+}
+
+fn computed_columns(args: &Args, stats: &Stats) -> Result<Vec<ComputedColumn>, Error> {
+    args.csv
+        .computed
+        .iter()
+        .map(|(name, expr)| Ok((name.clone(), stats.computed_column(name, expr)?)))
+        .collect()
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|x| x == "toml")
+}
+
+/// The csv headers of a toml --header-map, json maps are only for
+/// reading --from-csv
+fn export_header_map(args: &Args) -> Result<HeaderMap, Error> {
+    match &args.header_map {
+        Some(path) if is_toml(path) => HeaderMap::from_toml(&fs::read_to_string(path)?),
+        Some(_) if !args.from_csv => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a json --header-map only applies to --from-csv, csv headers are mapped with a .toml file",
+        )
+        .into()),
+        _ => Ok(HeaderMap::default()),
+    }
+}
+
+/// Reads the scoreboard, normalizing the player names as they are read
+fn read_stats(
+    args: &Args,
+    input_file: &Path,
+    normalization: &NameNormalization,
+) -> Result<(Stats, Vec<NameMerge>), Error> {
+    if args.from_csv {
+        let opts = CsvImportOptions {
+            missing_score: args.missing_score.clone(),
+            ..Default::default()
+        };
+        let opts = match &args.header_map {
+            //the headers the toml map writes are read back as its objectives
+            Some(path) if is_toml(path) => {
+                let map = HeaderMap::from_toml(&fs::read_to_string(path)?)?;
+                CsvImportOptions {
+                    header_map: map
+                        .objectives
+                        .into_iter()
+                        .map(|(objective, header)| (header, objective))
+                        .collect(),
+                    players_header: map.players,
+                    ..opts
+                }
+            }
+            Some(path) => CsvImportOptions {
+                header_map: serde_json::from_reader(File::open(path)?)?,
+                ..opts
+            },
+            None => opts,
+        };
+
+        let mut stats = Stats::from_csv_reader_with(File::open(input_file)?, &opts)?;
+        let merges = stats.normalize_player_names(normalization);
+        return Ok((stats, merges));
+    }
+
+    #[cfg(feature = "mmap")]
+    if args.mmap {
+        return Stats::from_path_mmap_normalized(input_file, normalization);
+    }
+
+    match &args.cache_dir {
+        Some(cache_dir) => {
+            let (stats, merges, warning) =
+                Stats::from_path_cached_normalized(input_file, cache_dir, normalization)?;
+            if let Some(e) = warning {
+                eprintln!(
+                    "Warning: couldn't write the cache in {}: {}",
+                    cache_dir.display(),
+                    e
+                );
+            }
+            Ok((stats, merges))
+        }
+        None => Stats::from_gzip_reader_normalized(File::open(input_file)?, normalization),
+    }
+}
+
+fn write_csv(stats: &Stats, opts: &CsvOptions, mut output_file: AtomicFile) -> Result<(), Error> {
+    stats.write_csv_with_options(&mut output_file, opts)?;
+    output_file.commit()?;
+
+    println!("Converted nbt to csv");
+
+    Ok(())
+}
+
+fn export_split(dir: &Path, include_empty: bool, stats: &Stats) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+
+    let files = stats.write_csv_per_objective(dir, include_empty)?;
+
+    for file in files.iter() {
+        println!(
+            "{} -> {} ({} rows)",
+            file.objective,
+            file.path.display(),
+            file.rows
+        );
+    }
+    println!("Wrote {} csv files", files.len());
+
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+fn serve(input: &Path, listen: &str) -> Result<(), Error> {
+    let server = ScoreServer::bind(input, listen)?;
+
+    match server.local_addr() {
+        Some(addr) => println!("Serving {} on {}", input.display(), addr),
+        None => println!("Serving {} on {}", input.display(), listen),
+    }
+
+    server.run()
+}
+
+fn list_objectives(input: &Path, json: bool) -> Result<(), Error> {
+    let objectives = Stats::objectives_only(File::open(input)?)?;
+
+    let mut names: Vec<&String> = objectives.keys().collect();
+    names.sort_unstable();
+
+    if json {
+        let list: Vec<_> = names.iter().map(|x| &objectives[*x]).collect();
+        println!("{}", serde_json::to_string_pretty(&list)?);
+        return Ok(());
+    }
+
+    let rows: Vec<[String; 3]> = names
+        .iter()
+        .map(|x| {
+            let objective = &objectives[*x];
+            [
+                objective.name().to_string(),
+                objective.criteria().to_string(),
+                objective.display_name().to_string(),
+            ]
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..2)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for row in rows {
+        println!(
+            "{:<3$}  {:<4$}  {}",
+            row[0], row[1], row[2], widths[0], widths[1]
+        );
+    }
+
+    Ok(())
+}
+
+fn inspect(
+    input: &Path,
+    path: Option<&str>,
+    opts: &InspectOptions,
+    json: bool,
+) -> Result<(), Error> {
+    let (root_name, root) = read_nbt(File::open(input)?)?;
+
+    let (name, value) = match path {
+        Some(path) => (path, find_path(&root, path)?),
+        None if root_name.is_empty() => ("root", &root),
+        None => (root_name.as_str(), &root),
+    };
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&to_json(value, opts))?),
+        false => write_tree(io::stdout().lock(), name, value, opts)?,
+    }
+
+    Ok(())
+}
+
+/// Reads the scoreboard, applies `edit` and writes it where `output` says
+/// once it reads back
+fn edit_scoreboard(
+    input: &Path,
+    output: &EditOutput,
+    edit: impl FnOnce(&mut Scoreboard) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let path = match (&output.output_file, output.in_place) {
+        (Some(path), _) if path == input => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "use --in-place to replace the input",
+            )
+            .into())
+        }
+        (Some(path), _) => path.as_path(),
+        (None, _) => input,
+    };
+
+    let mut scoreboard = Scoreboard::from_reader(File::open(input)?)?;
+    edit(&mut scoreboard)?;
+
+    let (bytes, _) = scoreboard.to_gzip_bytes()?;
+
+    let mut file = AtomicFile::create(path)?;
+    io::Write::write_all(&mut file, &bytes)?;
+    file.commit()?;
+
+    println!("Wrote {}", path.display());
+
+    Ok(())
+}
+
+fn export_worlds(args: &WorldsArgs) -> Result<(), Error> {
+    if args.output_file.is_none()
+        && args.ndjson.is_none()
+        && args.per_world_files.is_none()
+        && args.sql_url.is_none()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "give --output-file, --ndjson, --per-world-files or --sql-url",
+        )
+        .into());
+    }
+
+    let mut paths = match &args.root {
+        Some(root) => Worlds::discover(root)?,
+        None => Vec::new(),
+    };
+    paths.extend(
+        args.worlds
+            .iter()
+            .map(|(name, path)| (name.clone(), scoreboard_path(path))),
+    );
+
+    let mut worlds = Worlds::new();
+    for (name, path) in paths {
+        println!("{} -> {}", name, path.display());
+        worlds.add(name, Stats::from_gzip_reader(File::open(&path)?)?);
+    }
+
+    if worlds.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no worlds found").into());
+    }
+
+    for conflict in worlds.conflicts() {
+        let names: Vec<String> = conflict
+            .display_names
+            .iter()
+            .map(|(world, display_name)| format!("{:?} in {}", display_name, world))
+            .collect();
+        eprintln!(
+            "Warning: objective {} has different display names: {}",
+            conflict.objective,
+            names.join(", ")
+        );
+    }
+
+    if let Some(path) = &args.output_file {
+        let mut file = AtomicFile::create(path)?;
+        worlds.write_csv_long(&mut file)?;
+        file.commit()?;
+    }
+
+    if let Some(path) = &args.ndjson {
+        let mut file = AtomicFile::create(path)?;
+        worlds.write_ndjson(&mut file)?;
+        file.commit()?;
+    }
+
+    if let Some(dir) = &args.per_world_files {
+        fs::create_dir_all(dir)?;
+
+        let mut names = UniqueNames::default();
+        for (world, stats) in worlds.iter() {
+            let path = dir.join(format!("{}.csv", names.file_name(world)));
+            let mut file = AtomicFile::create(&path)?;
+            stats.write_csv(&mut file)?;
+            file.commit()?;
+        }
+    }
+
+    if let Some(url) = &args.sql_url {
+        let timestamp = Utc::now();
+
+        //the objectives table has a single display name per objective
+        if let Some((last, _)) = worlds.iter().last() {
+            if !worlds.conflicts().is_empty() {
+                eprintln!(
+                    "Warning: the objectives table keeps the display names of {}",
+                    last
+                );
+            }
+        }
+
+        for (world, stats) in worlds.iter() {
+            let opts = SqlOptions {
+                schema_prefix: args.schema_prefix.clone(),
+                region: args.region.clone(),
+                world: Some(world.to_string()),
+                ..Default::default()
+            };
+            write_sql(stats, url, timestamp, &opts, args.create_tables)?;
+        }
+    }
+
+    println!("Exported {} worlds", worlds.len());
+
+    Ok(())
+}
+
+fn write_moving_average(
+    args: &Args,
+    path: &Path,
+    stats: &Stats,
+    snapshots: &[PathBuf],
+) -> Result<(), Error> {
+    let input_file = args
+        .input_file
+        .as_deref()
+        .expect("clap requires the input file without a subcommand");
+
+    let mut window = vec![(modified_time(input_file)?, stats.clone())];
+    for snapshot in snapshots {
+        window.push((modified_time(snapshot)?, load_stats(args, snapshot)?));
+    }
+
+    let mut output_file = AtomicFile::create(path)?;
+    Stats::write_csv_moving_average(&mut output_file, &window, args.csv.float_precision)?;
+    output_file.commit()?;
+
+    println!("Averaged {} snapshots into csv", window.len());
+
+    Ok(())
+}
+
+fn write_velocity(
+    args: &Args,
+    path: &Path,
+    stats: &Stats,
+    snapshots: &[PathBuf],
+) -> Result<(), Error> {
+    let input_file = args
+        .input_file
+        .as_deref()
+        .expect("clap requires the input file without a subcommand");
+
+    let mut history = Vec::new();
+    for snapshot in snapshots {
+        history.push((modified_time(snapshot)?, load_stats(args, snapshot)?));
+    }
+
+    let mut window: Vec<(DateTime<Utc>, &Stats)> =
+        history.iter().map(|(time, stats)| (*time, stats)).collect();
+    window.push((modified_time(input_file)?, stats));
+
+    let mut output_file = AtomicFile::create(path)?;
+    Stats::write_csv_with_velocity(&mut output_file, &window, args.csv.float_precision)?;
+    output_file.commit()?;
+
+    println!(
+        "Wrote score changes per day over {} snapshots into csv",
+        window.len()
+    );
+
+    Ok(())
+}
+
+fn write_sparklines(
+    args: &Args,
+    path: &Path,
+    stats: &Stats,
+    snapshots: &[PathBuf],
+) -> Result<(), Error> {
+    let history = snapshots
+        .iter()
+        .map(|snapshot| load_stats(args, snapshot))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_csv_with_sparklines(&mut output_file, &history)?;
+    output_file.commit()?;
+
+    println!("Wrote trends over {} snapshots into csv", history.len() + 1);
+
+    Ok(())
+}
+
+fn write_activity_flag(
+    args: &Args,
+    path: &Path,
+    stats: &Stats,
+    previous: &Path,
+) -> Result<(), Error> {
+    let previous = load_stats(args, previous)?;
+
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_csv_with_activity_flag(&mut output_file, &previous)?;
+    output_file.commit()?;
+
+    println!("Wrote activity since the previous snapshot into csv");
+
+    Ok(())
+}
+
+fn write_cdc_triggers(path: &Path, stats: &Stats, dialect: SqlDialect) -> Result<(), Error> {
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_sql_cdc_triggers(&mut output_file, dialect)?;
+    output_file.commit()?;
+
+    println!("Wrote change data capture triggers");
+
+    Ok(())
+}
+
+fn write_materialized_view(path: &Path, stats: &Stats, dialect: SqlDialect) -> Result<(), Error> {
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_sql_materialized_view(&mut output_file, dialect)?;
+    output_file.commit()?;
+
+    println!("Wrote the leaderboard summary");
+
+    Ok(())
+}
+
+fn write_row_level_security(
+    path: &Path,
+    stats: &Stats,
+    opts: &SqlOptions,
+    dialect: SqlDialect,
+) -> Result<(), Error> {
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_sql_with_row_level_security(&mut output_file, opts, dialect)?;
+    output_file.commit()?;
+
+    println!("Wrote the row level security policies");
+
+    Ok(())
+}
+
+fn write_cross_server_view(
+    path: &Path,
+    stats: &Stats,
+    regions: &[String],
+    dialect: SqlDialect,
+) -> Result<(), Error> {
+    let regions: Vec<&str> = regions.iter().map(String::as_str).collect();
+
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_sql_cross_server_view(&mut output_file, &regions, dialect)?;
+    output_file.commit()?;
+
+    println!("Wrote the cross server view of {} regions", regions.len());
+
+    Ok(())
+}
+
+fn write_histogram(path: &Path, stats: &Stats, bins: usize) -> Result<(), Error> {
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_histogram_csv(&mut output_file, bins)?;
+    output_file.commit()?;
+
+    println!("Wrote a histogram of {} bins", bins);
+
+    Ok(())
+}
+
+/// The name and source of --template, a builtin template or a file
+#[cfg(feature = "templates")]
+fn load_template(args: &Args) -> Result<Option<(String, String)>, Error> {
+    let Some(template) = &args.files.template else {
+        return Ok(None);
+    };
+
+    match poop_scoreboard::stats::builtin_template(template) {
+        Some(source) => Ok(Some((template.clone(), source.to_string()))),
+        None => {
+            let path = Path::new(template);
+            let name = path
+                .file_name()
+                .map_or_else(|| template.clone(), |x| x.to_string_lossy().into_owned());
+
+            Ok(Some((name, fs::read_to_string(path)?)))
+        }
+    }
+}
+
+#[cfg(not(feature = "templates"))]
+fn load_template(_: &Args) -> Result<Option<(String, String)>, Error> {
+    Ok(None)
+}
+
+/// `board.html.j2` is written as html, templates without an inner
+/// extension as txt
+fn template_extension(name: &str) -> &str {
+    let name = [".j2", ".jinja", ".jinja2", ".tera"]
+        .iter()
+        .find_map(|x| name.strip_suffix(x))
+        .unwrap_or(name);
+
+    Path::new(name)
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or("txt")
+}
+
+#[cfg(feature = "templates")]
+fn write_template(
+    path: &Path,
+    stats: &Stats,
+    name: &str,
+    source: &str,
+    timestamp: DateTime<Utc>,
+) -> Result<(), Error> {
+    let mut output_file = AtomicFile::create(path)?;
+    stats.write_template(&mut output_file, name, source, timestamp)?;
+    output_file.commit()?;
+
+    println!("Rendered {} to {}", name, path.display());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "templates"))]
+fn write_template(_: &Path, _: &Stats, _: &str, _: &str, _: DateTime<Utc>) -> Result<(), Error> {
+    unreachable!("there is no template without the templates feature")
+}
+
+fn modified_time(path: &Path) -> Result<DateTime<Utc>, Error> {
+    Ok(fs::metadata(path)?.modified()?.into())
+}
+
+fn write_sql(
+    stats: &Stats,
+    url: &str,
+    timestamp: DateTime<Utc>,
+    opts: &SqlOptions,
+    create_tables: bool,
+) -> Result<(), Error> {
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    if create_tables {
+        block_on(opts.create_tables(&mut conn))?;
+    }
+
+    let report = block_on(stats.write_to_connection_with_report(&mut conn, timestamp, opts))?;
+    if report.excluded_players > 0 {
+        println!("Skipped {} excluded players", report.excluded_players);
+    }
+
+    delete_expired(&mut conn, opts)
+}
+
+#[cfg(feature = "postgres")]
+fn write_postgres_jsonb(
+    stats: &Stats,
+    url: &str,
+    timestamp: DateTime<Utc>,
+    opts: &SqlOptions,
+) -> Result<(), Error> {
+    let mut conn = block_on(opts.connect(|| sqlx::PgConnection::connect(url)))?;
+
+    block_on(stats.write_to_postgres_jsonb(&mut conn, timestamp))?;
+    println!("Inserted {} player snapshots", stats.player_count());
+
+    Ok(())
+}
+
+fn delete_expired(conn: &mut MySqlConnection, opts: &SqlOptions) -> Result<(), Error> {
+    if let Some(ttl_days) = opts.ttl_days {
+        let deleted = block_on(opts.delete_expired(conn))?;
+        println!("Deleted {} stats older than {} days", deleted, ttl_days);
+    }
+
+    Ok(())
+}
+
+/// Runs the pending migrations, only on databases without any unless
+/// upgrading so an old database isn't changed by accident
+fn init_db(
+    url: &str,
+    upgrade: bool,
+    dry_run: bool,
+    objectives_from: Option<&Path>,
+    opts: &SqlOptions,
+) -> Result<(), Error> {
+    //a broken scoreboard fails before the database is touched
+    let objectives = match objectives_from {
+        Some(path) => Some(Stats::objectives_only(File::open(path)?)?),
+        None => None,
+    };
+
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    migrate(&mut conn, upgrade, dry_run, opts)?;
+
+    if let (false, Some(objectives)) = (dry_run, objectives) {
+        block_on(write_objectives_to_sql(&mut conn, &objectives, opts))?;
+        println!("Synced {} objectives", objectives.len());
+    }
+
+    Ok(())
+}
+
+fn migrate(
+    conn: &mut MySqlConnection,
+    upgrade: bool,
+    dry_run: bool,
+    opts: &SqlOptions,
+) -> Result<(), Error> {
+    let version = block_on(opts.schema_version(&mut *conn))?;
+    let pending = block_on(opts.pending_migrations(&mut *conn))?;
+
+    if pending.is_empty() {
+        println!("The schema is up to date at version {}", version);
+        return Ok(());
+    }
+
+    if dry_run {
+        for migration in pending.iter() {
+            println!("-- {}: {}", migration.version, migration.description);
+            for statement in migration.statements.iter() {
+                println!("{};", statement);
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    if version > 0 && !upgrade {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the schema is at version {} with {} pending migrations, apply them with --upgrade",
+                version,
+                pending.len()
+            ),
+        )
+        .into());
+    }
+
+    for migration in block_on(opts.migrate(&mut *conn))? {
+        println!(
+            "Applied migration {}: {}",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+fn set_player_excluded(
+    url: &str,
+    player: &str,
+    excluded: bool,
+    schema_prefix: Option<String>,
+) -> Result<(), Error> {
+    let opts = SqlOptions {
+        schema_prefix,
+        ..Default::default()
+    };
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    match excluded {
+        true => match block_on(opts.exclude_player(&mut conn, player))? {
+            true => println!("Excluded {} from the exports", player),
+            false => println!("{} is already excluded", player),
+        },
+        false => match block_on(opts.include_player(&mut conn, player))? {
+            true => println!("Included {} in the exports again", player),
+            false => eprintln!("Warning: {} is not excluded", player),
+        },
+    }
+
+    Ok(())
+}
+
+fn write_event_log(
+    stats: &Stats,
+    url: &str,
+    timestamp: DateTime<Utc>,
+    opts: &SqlOptions,
+    create_tables: bool,
+) -> Result<(), Error> {
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    if create_tables {
+        block_on(opts.create_event_log_table(&mut conn))?;
+    }
+
+    let event_id = Uuid::new_v4();
+    block_on(stats.write_to_sql_event_log(&mut conn, event_id, timestamp, opts))?;
+
+    println!("Recorded score event {}", event_id);
+
+    Ok(())
+}
+
+fn write_wide_table(
+    stats: &Stats,
+    url: &str,
+    timestamp: DateTime<Utc>,
+    opts: &SqlOptions,
+) -> Result<(), Error> {
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    block_on(stats.write_to_sql_wide(&mut conn, timestamp, opts))?;
+    println!(
+        "Inserted {} players into the wide table",
+        stats.player_count()
+    );
+
+    Ok(())
+}
+
+/// Fails with the destinations that failed, after every one was tried
+fn destinations_failure(results: Vec<(&'static str, Result<(), Error>)>) -> Result<(), Failure> {
+    let total = results.len();
+    let failed: Vec<(&'static str, Error)> = results
+        .into_iter()
+        .filter_map(|(destination, result)| result.err().map(|e| (destination, e)))
+        .collect();
+
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(Failure::Destinations(failed, total)),
+    }
+}
+
+/// Streams the scores into the long csv and the database, reading the
+/// file once for each
+fn stream(
+    args: &Args,
+    input_file: &Path,
+    output_path: Option<&Path>,
+    timestamp: DateTime<Utc>,
+    sql_opts: &SqlOptions,
+) -> Result<(), Failure> {
+    if output_path.is_some() && !args.long {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the csv with a column per objective can't be streamed, write the --long csv instead",
+        )
+        .into());
+    }
+
+    let export_options = export_options(args);
+    let mut results = Vec::new();
+
+    if let Some(path) = output_path {
+        results.push(("csv", stream_csv(input_file, path, &export_options)));
+    }
+
+    if let Some(sql) = &args.sql.sql_url {
+        results.push((
+            "sql",
+            File::open(input_file)
+                .map_err(Error::from)
+                .and_then(|file| {
+                    stream_sql(
+                        file,
+                        &export_options,
+                        sql,
+                        timestamp,
+                        sql_opts,
+                        args.sql.create_tables,
+                    )
+                }),
+        ));
+    }
+
+    destinations_failure(results)
+}
+
+fn stream_csv(input_file: &Path, path: &Path, export_options: &ExportOptions) -> Result<(), Error> {
+    let mut file = AtomicFile::create(path)?;
+
+    stream_csv_long(File::open(input_file)?, &mut file, |entry| {
+        export_options.keeps_objective(&entry.objective_name)
+            && export_options.keeps_player(&entry.player_name)
+            && export_options.keeps_score(&entry.objective_name, entry.score)
+    })?;
+
+    Ok(file.commit()?)
+}
+
+fn stream_sql(
+    input_file: File,
+    export_options: &ExportOptions,
+    url: &str,
+    timestamp: DateTime<Utc>,
+    opts: &SqlOptions,
+    create_tables: bool,
+) -> Result<(), Error> {
+    let mut conn = block_on(opts.connect(|| MySqlConnection::connect(url)))?;
+
+    if create_tables {
+        block_on(opts.create_tables(&mut conn))?;
+    }
+
+    block_on(opts.check_schema_version(&mut conn))?;
+    block_on(opts.prepare_partition(&mut conn, timestamp))?;
+
+    //a transaction per entry would commit three statements at a time
+    let mut transaction = block_on(conn.begin())?;
+
+    let mut objectives = stream_scores(input_file, |entry| {
+        match export_options.keeps_objective(&entry.objective_name)
+            && export_options.keeps_player(&entry.player_name)
+            && export_options.keeps_score(&entry.objective_name, entry.score)
+        {
+            true => block_on(entry.write_to_sql(&mut transaction, timestamp, opts)),
+            false => Ok(()),
+        }
+    })?;
+    objectives.retain(|name, _| export_options.keeps_objective(name));
+
+    block_on(write_objectives_to_sql(&mut transaction, &objectives, opts))?;
+    block_on(transaction.commit())?;
+
+    delete_expired(&mut conn, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(destinations: &[&'static str], total: usize) -> Failure {
+        Failure::Destinations(
+            destinations
+                .iter()
+                .map(|x| (*x, Error::IncorrectFlags))
+                .collect(),
+            total,
+        )
+    }
+
+    #[test]
+    fn every_failure_has_its_own_exit_code() {
+        assert_eq!(
+            exit_code(&Failure::Export(Error::IncorrectFlags)),
+            EXIT_EXPORT_FAILED
+        );
+        assert_eq!(exit_code(&failed(&["csv", "sql"], 2)), EXIT_EXPORT_FAILED);
+        assert_eq!(exit_code(&failed(&["post"], 2)), EXIT_POST_FAILED);
+        assert_eq!(exit_code(&failed(&["sql"], 2)), EXIT_PARTIAL_FAILURE);
+        assert_eq!(
+            exit_code(&Failure::TooSmall("players", 3, 50)),
+            EXIT_TOO_SMALL
+        );
+        assert_eq!(exit_code(&Failure::Empty), EXIT_EMPTY);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct_and_documented() {
+        let codes = [
+            EXIT_EXPORT_FAILED,
+            EXIT_POST_FAILED,
+            EXIT_PARTIAL_FAILURE,
+            EXIT_TOO_SMALL,
+            EXIT_EMPTY,
+        ];
+
+        for (i, code) in codes.iter().enumerate() {
+            assert!(!codes[i + 1..].contains(code), "{} is used twice", code);
+            assert!(
+                args::EXIT_CODES.contains(&format!("\n  {}  ", code)),
+                "{} is missing from the help",
+                code
+            );
+        }
+        assert_eq!(args::EXIT_CODES.matches("\n  ").count(), codes.len() + 1);
+    }
+
+    #[test]
+    fn located_errors_keep_the_details_of_the_error() {
+        let e = Error::NBTAt {
+            path: "data.PlayerScores",
+            index: 3,
+            error: Box::new(Error::NBTMissingField("Score")),
+        };
+
+        assert_eq!(
+            error_json(&e),
+            json!({
+                "kind": "nbt_missing_field",
+                "message": "NBT data file is missing the required field \"Score\" in data.PlayerScores[3]",
+                "details": { "field": "Score", "path": "data.PlayerScores", "index": 3 },
+            })
+        );
+
+        let e = Error::SQLStatement {
+            statement: "DELETE FROM stats".to_string(),
+            error: sqlx::Error::RowNotFound,
+        };
+        assert_eq!(error_json(&e)["kind"], "sql");
+        assert_eq!(
+            error_json(&e)["details"],
+            json!({ "statement": "DELETE FROM stats" })
+        );
+    }
+
+    #[test]
+    fn descriptors_are_named_after_the_csv() {
+        assert_eq!(
+            datapackage_path(Path::new("out/stats.csv")),
+            Path::new("out/stats.datapackage.json")
+        );
+        assert_eq!(
+            datapackage_path(Path::new("stats")),
+            Path::new("stats.datapackage.json")
+        );
+    }
+}
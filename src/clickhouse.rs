@@ -0,0 +1,178 @@
+use std::{io, time::Duration};
+
+use chrono::{DateTime, Utc};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use url::Url;
+
+use crate::{error::Error, stats::Stats};
+
+/// Options for inserting the stats into ClickHouse over its http interface
+#[derive(Debug, Clone)]
+pub struct ClickHouseOptions {
+    /// Like `http://host:8123?database=mc`, a user and password in the
+    /// url are used when `user` and `password` aren't set
+    pub url: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Created as a MergeTree ordered by `(objective, player, time)` when
+    /// it doesn't exist
+    pub table: String,
+    /// How many scores are sent in a single request
+    pub batch_size: usize,
+    /// Timeout for a single request
+    pub timeout: Duration,
+}
+
+impl Default for ClickHouseOptions {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            user: None,
+            password: None,
+            table: "stats".to_string(),
+            batch_size: 10_000,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ScoreRow<'a> {
+    time: &'a str,
+    objective: &'a str,
+    player: &'a str,
+    score: i64,
+}
+
+impl ClickHouseOptions {
+    /// The statement creating the table the scores are inserted into
+    pub fn table_sql(&self) -> Result<String, Error> {
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+    time DateTime64(3, 'UTC'),
+    objective String,
+    player String,
+    score Int64
+) ENGINE = MergeTree ORDER BY (objective, player, time)",
+            self.checked_table()?
+        ))
+    }
+
+    /// Creates the table when it is missing and inserts every score with
+    /// the time `timestamp` in batches of `batch_size`, as JSONEachRow.
+    ///
+    /// Batches are inserted one after another, when one fails the ones
+    /// before it stay inserted. Returns the number of inserted scores.
+    pub fn insert(&self, stats: &Stats, timestamp: DateTime<Utc>) -> Result<usize, Error> {
+        if self.batch_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the batch size must be larger than zero",
+            )
+            .into());
+        }
+
+        let (url, user, password) = self.credentials()?;
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+
+        let send = |query: &str, body: &[u8]| {
+            let mut request = agent.post(&url).query("query", query);
+            if let Some(user) = &user {
+                request = request.set("X-ClickHouse-User", user);
+            }
+            if let Some(password) = &password {
+                request = request.set("X-ClickHouse-Key", password);
+            }
+
+            match request.send_bytes(body) {
+                Ok(_) => Ok(()),
+                Err(ureq::Error::Status(status, response)) => Err(Error::HTTPStatus(
+                    status,
+                    response.into_string().unwrap_or_default(),
+                )),
+                Err(e) => Err(Error::from(e)),
+            }
+        };
+
+        send(&self.table_sql()?, &[])?;
+
+        let insert = format!("INSERT INTO {} FORMAT JSONEachRow", self.checked_table()?);
+        let time = timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+
+        let mut body = Vec::new();
+        let mut batched = 0;
+        let mut inserted = 0;
+
+        for (objective, scores) in stats.player_scores().iter() {
+            for score in scores {
+                serde_json::to_writer(
+                    &mut body,
+                    &ScoreRow {
+                        time: &time,
+                        objective,
+                        player: score.player_name(),
+                        score: score.score(),
+                    },
+                )?;
+                body.push(b'\n');
+                batched += 1;
+
+                if batched == self.batch_size {
+                    send(&insert, &body)?;
+                    inserted += batched;
+                    body.clear();
+                    batched = 0;
+                }
+            }
+        }
+
+        if batched > 0 {
+            send(&insert, &body)?;
+            inserted += batched;
+        }
+
+        Ok(inserted)
+    }
+
+    /// The table name ends up in the statements as is, so only allow plain identifiers
+    fn checked_table(&self) -> Result<&str, Error> {
+        match !self.table.is_empty()
+            && self
+                .table
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            true => Ok(&self.table),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid ClickHouse table name {}", self.table),
+            )
+            .into()),
+        }
+    }
+
+    /// The url without its user info, and the user and password to send
+    fn credentials(&self) -> Result<(String, Option<String>, Option<String>), Error> {
+        let mut url = Url::parse(&self.url).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid ClickHouse url {}: {}", self.url, e),
+            )
+        })?;
+
+        let decode = |x: &str| percent_decode_str(x).decode_utf8_lossy().into_owned();
+        let url_user = Some(url.username()).filter(|x| !x.is_empty()).map(decode);
+        let url_password = url.password().map(decode);
+
+        //only http urls have user info, so clearing it can't fail
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        Ok((
+            url.into(),
+            self.user.clone().or(url_user),
+            self.password.clone().or(url_password),
+        ))
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::Utc;
+use futures::executor::block_on;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, MySqlConnection};
+
+use crate::{
+    error::Error,
+    stats::{scoreboard_path, SqlOptions, Stats},
+};
+
+/// Options for [`run`]
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// A scoreboard file or a world directory containing `data/scoreboard.dat`
+    pub input: PathBuf,
+    pub sql_url: String,
+    /// Time between the starts of two imports
+    pub every: Duration,
+    /// Import right away instead of after the first interval
+    pub run_once_on_start: bool,
+    /// Create any missing tables before each import
+    pub create_tables: bool,
+    pub sql: SqlOptions,
+}
+
+/// The last successfully imported version of the input
+struct Snapshot {
+    modified: SystemTime,
+    sha256: [u8; 32],
+}
+
+enum Outcome {
+    Imported { scores: usize, excluded: usize },
+    Unchanged,
+}
+
+/// What happened in the daemon, written by [`fmt::Display`] as a line of
+/// `key=value` pairs
+#[derive(Debug)]
+pub enum DaemonEvent<'a> {
+    Start {
+        input: &'a Path,
+        every: Duration,
+    },
+    Imported {
+        cycle: u64,
+        scores: usize,
+        excluded_players: usize,
+        duration: Duration,
+    },
+    Unchanged {
+        cycle: u64,
+        duration: Duration,
+    },
+    Failed {
+        cycle: u64,
+        duration: Duration,
+        consecutive_failures: u64,
+        total_failures: u64,
+        error: &'a Error,
+    },
+    Shutdown {
+        cycles: u64,
+        total_failures: u64,
+    },
+}
+
+impl fmt::Display for DaemonEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Start { input, every } => write!(
+                f,
+                "event=start input={:?} every_secs={}",
+                input,
+                every.as_secs()
+            ),
+            Self::Imported {
+                cycle,
+                scores,
+                excluded_players,
+                duration,
+            } => write!(
+                f,
+                "event=cycle cycle={} outcome=imported scores={} excluded_players={} duration_ms={}",
+                cycle,
+                scores,
+                excluded_players,
+                duration.as_millis()
+            ),
+            Self::Unchanged { cycle, duration } => write!(
+                f,
+                "event=cycle cycle={} outcome=unchanged duration_ms={}",
+                cycle,
+                duration.as_millis()
+            ),
+            Self::Failed {
+                cycle,
+                duration,
+                consecutive_failures,
+                total_failures,
+                error,
+            } => write!(
+                f,
+                "event=cycle cycle={} outcome=failed duration_ms={} consecutive_failures={} total_failures={} error={:?}",
+                cycle,
+                duration.as_millis(),
+                consecutive_failures,
+                total_failures,
+                format!("{:?}", error)
+            ),
+            Self::Shutdown {
+                cycles,
+                total_failures,
+            } => write!(
+                f,
+                "event=shutdown cycles={} total_failures={}",
+                cycles, total_failures
+            ),
+        }
+    }
+}
+
+/// Imports the scoreboard into the database every `opts.every` until the
+/// process gets SIGINT or SIGTERM, see [`run_with_events`]
+pub fn run(opts: &DaemonOptions) -> Result<(), Error> {
+    run_with_events(opts, |_| ())
+}
+
+/// Imports the scoreboard into the database every `opts.every` until the
+/// process gets SIGINT or SIGTERM, handing `on_event` one event per cycle.
+///
+/// An import is skipped when the file is unchanged since the last one. A
+/// failed import is reported and tried again on the next cycle. Each import
+/// runs in a transaction: the first signal lets the import in flight
+/// finish before stopping, a second one exits right away and leaves the
+/// uncommitted import to be rolled back.
+pub fn run_with_events(
+    opts: &DaemonOptions,
+    mut on_event: impl FnMut(&DaemonEvent),
+) -> Result<(), Error> {
+    let (shutdown_tx, shutdown) = mpsc::channel();
+    let mut signals = 0;
+
+    ctrlc::set_handler(move || {
+        signals += 1;
+
+        match signals {
+            1 => {
+                let _ = shutdown_tx.send(());
+            }
+            _ => process::exit(130),
+        }
+    })
+    .map_err(io::Error::other)?;
+
+    let input = scoreboard_path(&opts.input);
+    let mut last = None;
+    let mut cycle: u64 = 0;
+    let mut consecutive_failures: u64 = 0;
+    let mut total_failures: u64 = 0;
+
+    let mut next_start = match opts.run_once_on_start {
+        true => Instant::now(),
+        false => Instant::now() + opts.every,
+    };
+
+    on_event(&DaemonEvent::Start {
+        input: &input,
+        every: opts.every,
+    });
+
+    while let Err(RecvTimeoutError::Timeout) =
+        shutdown.recv_timeout(next_start.saturating_duration_since(Instant::now()))
+    {
+        let started = Instant::now();
+        next_start = started + opts.every;
+        cycle += 1;
+
+        let outcome = import(opts, &input, &mut last);
+        let duration = started.elapsed();
+
+        match outcome {
+            Ok(Outcome::Imported { scores, excluded }) => {
+                consecutive_failures = 0;
+                on_event(&DaemonEvent::Imported {
+                    cycle,
+                    scores,
+                    excluded_players: excluded,
+                    duration,
+                });
+            }
+            Ok(Outcome::Unchanged) => on_event(&DaemonEvent::Unchanged { cycle, duration }),
+            Err(e) => {
+                consecutive_failures += 1;
+                total_failures += 1;
+                on_event(&DaemonEvent::Failed {
+                    cycle,
+                    duration,
+                    consecutive_failures,
+                    total_failures,
+                    error: &e,
+                });
+            }
+        }
+    }
+
+    on_event(&DaemonEvent::Shutdown {
+        cycles: cycle,
+        total_failures,
+    });
+
+    Ok(())
+}
+
+/// Imports the file unless its modification time or, failing that, its
+/// contents match the last import
+fn import(
+    opts: &DaemonOptions,
+    input: &Path,
+    last: &mut Option<Snapshot>,
+) -> Result<Outcome, Error> {
+    let modified = fs::metadata(input)?.modified()?;
+
+    if last.as_ref().is_some_and(|last| last.modified == modified) {
+        return Ok(Outcome::Unchanged);
+    }
+
+    let data = fs::read(input)?;
+    let sha256: [u8; 32] = Sha256::digest(&data).into();
+
+    if last.as_ref().is_some_and(|last| last.sha256 == sha256) {
+        *last = Some(Snapshot { modified, sha256 });
+        return Ok(Outcome::Unchanged);
+    }
+
+    let stats = Stats::from_gzip_reader(&data[..])?;
+    let timestamp = Utc::now();
+
+    let excluded = block_on(async {
+        let mut conn = opts
+            .sql
+            .connect(|| MySqlConnection::connect(&opts.sql_url))
+            .await?;
+
+        if opts.create_tables {
+            opts.sql.create_tables(&mut conn).await?;
+        }
+
+        opts.sql.check_schema_version(&mut conn).await?;
+        opts.sql.prepare_partition(&mut conn, timestamp).await?;
+
+        let mut transaction = conn.begin().await?;
+        let report = stats
+            .insert_into_connection_with_report(&mut transaction, timestamp, &opts.sql)
+            .await?;
+        transaction.commit().await?;
+
+        Ok::<usize, Error>(report.excluded_players)
+    })?;
+
+    *last = Some(Snapshot { modified, sha256 });
+
+    Ok(Outcome::Imported {
+        scores: stats.player_scores().values().map(Vec::len).sum(),
+        excluded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_written_as_key_value_pairs() {
+        let error = Error::NBTMissingField("data");
+
+        assert_eq!(
+            DaemonEvent::Imported {
+                cycle: 3,
+                scores: 120,
+                excluded_players: 2,
+                duration: Duration::from_millis(45),
+            }
+            .to_string(),
+            "event=cycle cycle=3 outcome=imported scores=120 excluded_players=2 duration_ms=45"
+        );
+        assert_eq!(
+            DaemonEvent::Failed {
+                cycle: 4,
+                duration: Duration::from_millis(5),
+                consecutive_failures: 1,
+                total_failures: 2,
+                error: &error,
+            }
+            .to_string(),
+            "event=cycle cycle=4 outcome=failed duration_ms=5 consecutive_failures=1 total_failures=2 error=\"NBTMissingField(\\\"data\\\")\""
+        );
+    }
+}
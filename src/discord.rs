@@ -0,0 +1,130 @@
+use serde_json::{json, Value};
+
+use crate::error::Error;
+
+/// Discord refuses messages with more content than this
+const MAX_CONTENT_CHARS: usize = 2000;
+/// Discord refuses embeds with more fields than this
+const MAX_EMBED_FIELDS: usize = 25;
+const MAX_TITLE_CHARS: usize = 256;
+
+/// Posts a leaderboard of `(player, score)` pairs to a Discord webhook,
+/// as an embed or as a plain code block when `plain` is set.
+///
+/// Entries that don't fit in Discord's limits are replaced with an
+/// "…and N more" line.
+pub fn post_leaderboard(
+    webhook_url: &str,
+    title: &str,
+    entries: &[(&str, i64)],
+    plain: bool,
+) -> Result<(), Error> {
+    let message = match plain {
+        true => plain_message(title, entries),
+        false => embed_message(title, entries),
+    };
+
+    match ureq::post(webhook_url)
+        .set("Content-Type", "application/json")
+        .send_string(&message.to_string())
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, response)) => Err(Error::HTTPStatus(
+            status,
+            response.into_string().unwrap_or_default(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn embed_message(title: &str, entries: &[(&str, i64)]) -> Value {
+    //the last field is needed for the "…and N more" line when there are too many entries
+    let shown = match entries.len() > MAX_EMBED_FIELDS {
+        true => MAX_EMBED_FIELDS - 1,
+        false => entries.len(),
+    };
+
+    let mut fields: Vec<Value> = entries[..shown]
+        .iter()
+        .enumerate()
+        .map(|(i, (player, score))| {
+            json!({
+                "name": format!("{}. {}", i + 1, player),
+                "value": score.to_string(),
+                "inline": false,
+            })
+        })
+        .collect();
+
+    if shown < entries.len() {
+        fields.push(json!({
+            "name": "\u{2026}",
+            "value": format!("and {} more", entries.len() - shown),
+            "inline": false,
+        }));
+    }
+
+    json!({
+        "embeds": [{
+            "title": title.chars().take(MAX_TITLE_CHARS).collect::<String>(),
+            "fields": fields,
+        }]
+    })
+}
+
+fn plain_message(title: &str, entries: &[(&str, i64)]) -> Value {
+    let name_width = entries
+        .iter()
+        .map(|x| x.0.chars().count())
+        .max()
+        .unwrap_or(0);
+    let rank_width = entries.len().to_string().len();
+
+    let lines: Vec<String> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, (player, score))| {
+            format!(
+                "{:>rank_width$}. {:<name_width$}  {}\n",
+                i + 1,
+                player,
+                score
+            )
+        })
+        .collect();
+
+    let header = format!(
+        "**{}**\n```\n",
+        title.chars().take(MAX_TITLE_CHARS).collect::<String>()
+    );
+    let footer = "```";
+
+    let mut content = header.clone();
+    let mut used = header.chars().count() + footer.chars().count();
+
+    for (i, line) in lines.iter().enumerate() {
+        let remaining = lines.len() - i - 1;
+
+        //room for the "…and N more" line has to be kept unless this is the last line
+        let reserved = match remaining {
+            0 => 0,
+            _ => more_line(remaining).chars().count(),
+        };
+
+        if used + line.chars().count() + reserved > MAX_CONTENT_CHARS {
+            content.push_str(&more_line(lines.len() - i));
+            break;
+        }
+
+        used += line.chars().count();
+        content.push_str(line);
+    }
+
+    content.push_str(footer);
+
+    json!({ "content": content })
+}
+
+fn more_line(count: usize) -> String {
+    format!("\u{2026}and {} more\n", count)
+}
@@ -0,0 +1,280 @@
+//! Changes to a scoreboard file written back as NBT
+//!
+//! The edits are made on the raw NBT tree instead of [`Stats`] so
+//! everything the exports don't read, like the data version and display
+//! slots, is written back unchanged.
+
+use std::io::{Read, Write};
+
+use flate2::{write::GzEncoder, Compression};
+use nbt::Value;
+
+use crate::{error::Error, inspect::read_nbt, stats::Stats};
+
+const DATA: &str = "data";
+const OBJECTIVES: &str = "Objectives";
+const PLAYER_SCORES: &str = "PlayerScores";
+const DISPLAY_SLOTS: &str = "DisplaySlots";
+
+/// The NBT of a scoreboard file
+#[derive(Debug, Clone)]
+pub struct Scoreboard {
+    root_name: String,
+    root: Value,
+}
+
+impl Scoreboard {
+    /// Reads a gzipped or uncompressed scoreboard
+    pub fn from_reader(src: impl Read) -> Result<Self, Error> {
+        let (root_name, root) = read_nbt(src)?;
+
+        Ok(Self { root_name, root })
+    }
+
+    /// Sets the score of a player in an existing objective, adding the
+    /// score when the player doesn't have one yet. Returns whether a score
+    /// was replaced.
+    pub fn set_score(&mut self, player: &str, objective: &str, score: i32) -> Result<bool, Error> {
+        let data = self.data_mut()?;
+
+        let exists = match data.get(OBJECTIVES) {
+            Some(Value::List(objectives)) => objectives.iter().any(|x| name_is(x, objective)),
+            _ => return Err(Error::NBTMissingField(OBJECTIVES)),
+        };
+        if !exists {
+            return Err(Error::UnknownObjective(objective.to_string()));
+        }
+
+        //some tools leave the list out when there are no scores
+        let scores = match data
+            .entry(PLAYER_SCORES.to_string())
+            .or_insert_with(|| Value::List(Vec::new()))
+        {
+            Value::List(scores) => scores,
+            _ => return Err(Error::NBTMissingField(PLAYER_SCORES)),
+        };
+
+        let existing = scores.iter_mut().find_map(|x| match x {
+            Value::Compound(x) if is_score_of(x, player, objective) => Some(x),
+            _ => None,
+        });
+
+        match existing {
+            Some(existing) => {
+                existing.insert("Score".to_string(), Value::Int(score));
+                Ok(true)
+            }
+            None => {
+                let mut new = nbt::Map::new();
+                new.insert("Name".to_string(), Value::String(player.to_string()));
+                new.insert(
+                    "Objective".to_string(),
+                    Value::String(objective.to_string()),
+                );
+                new.insert("Score".to_string(), Value::Int(score));
+                new.insert("Locked".to_string(), Value::Byte(0));
+                scores.push(Value::Compound(new));
+                Ok(false)
+            }
+        }
+    }
+
+    /// Removes every score of a player, returning how many there were
+    pub fn remove_player(&mut self, player: &str) -> Result<usize, Error> {
+        let removed = self.remove_scores(|x| string_is(x, "Name", player))?;
+
+        match removed {
+            0 => Err(Error::UnknownPlayer(player.to_string())),
+            n => Ok(n),
+        }
+    }
+
+    /// Removes an objective with its scores and any display slot showing
+    /// it, returning how many scores it had
+    pub fn remove_objective(&mut self, objective: &str) -> Result<usize, Error> {
+        let data = self.data_mut()?;
+
+        match data.get_mut(OBJECTIVES) {
+            Some(Value::List(objectives)) => {
+                let before = objectives.len();
+                objectives.retain(|x| !name_is(x, objective));
+
+                if objectives.len() == before {
+                    return Err(Error::UnknownObjective(objective.to_string()));
+                }
+            }
+            _ => return Err(Error::NBTMissingField(OBJECTIVES)),
+        }
+
+        if let Some(Value::Compound(slots)) = data.get_mut(DISPLAY_SLOTS) {
+            slots.retain(|_, x| !matches!(x, Value::String(x) if x == objective));
+        }
+
+        self.remove_scores(|x| string_is(x, "Objective", objective))
+    }
+
+    /// Writes the scoreboard gzipped like Minecraft does
+    pub fn write_gzip(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = GzEncoder::new(w, Compression::default());
+
+        w.write_all(&[self.root.id()])?;
+        w.write_all(&(self.root_name.len() as u16).to_be_bytes())?;
+        w.write_all(self.root_name.as_bytes())?;
+        self.root.to_writer(&mut w)?;
+
+        w.finish()?.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the scoreboard and reads it back as [`Stats`], to check the
+    /// written file loads before it replaces anything
+    pub fn to_gzip_bytes(&self) -> Result<(Vec<u8>, Stats), Error> {
+        let mut bytes = Vec::new();
+        self.write_gzip(&mut bytes)?;
+
+        let stats = Stats::from_gzip_reader(&bytes[..])?;
+
+        Ok((bytes, stats))
+    }
+
+    fn data_mut(&mut self) -> Result<&mut nbt::Map<String, Value>, Error> {
+        match &mut self.root {
+            Value::Compound(root) => match root.get_mut(DATA) {
+                Some(Value::Compound(data)) => Ok(data),
+                _ => Err(Error::NBTMissingField(DATA)),
+            },
+            _ => Err(Error::NBTMissingField(DATA)),
+        }
+    }
+
+    fn remove_scores(
+        &mut self,
+        matches: impl Fn(&nbt::Map<String, Value>) -> bool,
+    ) -> Result<usize, Error> {
+        match self.data_mut()?.get_mut(PLAYER_SCORES) {
+            Some(Value::List(scores)) => {
+                let before = scores.len();
+                scores.retain(|x| !matches!(x, Value::Compound(x) if matches(x)));
+
+                Ok(before - scores.len())
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+fn name_is(objective: &Value, name: &str) -> bool {
+    matches!(objective, Value::Compound(x) if string_is(x, "Name", name))
+}
+
+fn is_score_of(score: &nbt::Map<String, Value>, player: &str, objective: &str) -> bool {
+    string_is(score, "Name", player) && string_is(score, "Objective", objective)
+}
+
+fn string_is(compound: &nbt::Map<String, Value>, key: &str, expected: &str) -> bool {
+    matches!(compound.get(key), Some(Value::String(x)) if x == expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn scoreboard() -> Scoreboard {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 3),
+                ("ville", "kills", 7),
+                ("anna", "deaths", 1),
+            ]
+            .into_iter(),
+        );
+
+        Scoreboard::from_reader(&file[..]).unwrap()
+    }
+
+    /// Writes `scoreboard` and parses it back both as NBT and as [`Stats`]
+    fn round_trip(scoreboard: &Scoreboard) -> (Scoreboard, Stats) {
+        let (bytes, stats) = scoreboard.to_gzip_bytes().unwrap();
+
+        (Scoreboard::from_reader(&bytes[..]).unwrap(), stats)
+    }
+
+    #[test]
+    fn unchanged_scoreboard_round_trips() {
+        let scoreboard = scoreboard();
+
+        let (written, stats) = round_trip(&scoreboard);
+
+        assert_eq!(written.root_name, scoreboard.root_name);
+        assert_eq!(written.root, scoreboard.root);
+        assert_eq!(stats.player_scores()["deaths"].len(), 2);
+    }
+
+    #[test]
+    fn set_score_round_trips() {
+        let mut scoreboard = scoreboard();
+
+        assert!(scoreboard.set_score("ville", "deaths", 0).unwrap());
+        assert!(!scoreboard.set_score("bob", "kills", 5).unwrap());
+        let (written, stats) = round_trip(&scoreboard);
+
+        assert_eq!(written.root, scoreboard.root);
+        assert_eq!(
+            stats.scores_of("ville"),
+            Some(nbt::Map::from_iter([("deaths", 0), ("kills", 7)]))
+        );
+        assert_eq!(
+            stats.scores_of("bob"),
+            Some(nbt::Map::from_iter([("kills", 5)]))
+        );
+    }
+
+    #[test]
+    fn remove_player_round_trips() {
+        let mut scoreboard = scoreboard();
+
+        assert_eq!(scoreboard.remove_player("ville").unwrap(), 2);
+        let (written, stats) = round_trip(&scoreboard);
+
+        assert_eq!(written.root, scoreboard.root);
+        assert_eq!(stats.scores_of("ville"), None);
+        assert_eq!(
+            stats.scores_of("anna"),
+            Some(nbt::Map::from_iter([("deaths", 1)]))
+        );
+    }
+
+    #[test]
+    fn remove_objective_drops_its_scores() {
+        let mut scoreboard = scoreboard();
+
+        assert_eq!(scoreboard.remove_objective("deaths").unwrap(), 2);
+        let (written, stats) = round_trip(&scoreboard);
+
+        assert_eq!(written.root, scoreboard.root);
+        assert!(!stats.objectives().contains_key("deaths"));
+        assert!(!stats.player_scores().contains_key("deaths"));
+        assert_eq!(stats.scores_of("anna"), None);
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let mut scoreboard = scoreboard();
+
+        assert!(matches!(
+            scoreboard.set_score("ville", "walk", 1),
+            Err(Error::UnknownObjective(_))
+        ));
+        assert!(matches!(
+            scoreboard.remove_player("bob"),
+            Err(Error::UnknownPlayer(_))
+        ));
+        assert!(matches!(
+            scoreboard.remove_objective("walk"),
+            Err(Error::UnknownObjective(_))
+        ));
+    }
+}
@@ -1,85 +1,206 @@
-use std::{fmt::Display, io};
+use std::io;
 
-#[derive(Debug)]
-#[allow(dead_code)]
+/// The wrapped errors are transparent, they print and chain exactly like
+/// the error they wrap
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code, deprecated)]
 pub enum Error {
-    NBTError(nbt::Error),
-    IOError(io::Error),
-    SerdeJsonError(serde_json::Error),
-    CSVError(csv::Error),
-    SQLXError(sqlx::Error),
+    #[error(transparent)]
+    NBTError(#[from] nbt::Error),
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    CSVError(#[from] csv::Error),
+    #[error(transparent)]
+    SQLXError(#[from] sqlx::Error),
+    #[error(transparent)]
+    BincodeError(#[from] bincode::Error),
+    #[error(transparent)]
+    TOMLError(#[from] toml::de::Error),
+    #[cfg(feature = "http")]
+    #[error(transparent)]
+    HTTPError(Box<ureq::Error>),
+    #[error("Server responded with status {0}: {1}")]
+    HTTPStatus(u16, String),
+    #[error("No objective named {0}")]
+    UnknownObjective(String),
+    #[error("No player named {0}")]
+    UnknownPlayer(String),
+    #[error("Invalid expression {0}")]
+    InvalidExpression(String),
+    #[error("Request body is larger than {0} bytes")]
+    PostBodyTooLarge(usize),
+    #[error("NBT data file is missing the required field \"{0}\"")]
     NBTMissingField(&'static str),
+    #[error("NBT field \"{field}\" is not a {expected}")]
+    NBTWrongType {
+        field: &'static str,
+        expected: &'static str,
+    },
+    /// `error` happened reading the element `index` of the list at `path`
+    #[error("{error} in {path}[{index}]")]
+    NBTAt {
+        path: &'static str,
+        index: usize,
+        error: Box<Error>,
+    },
+    #[error("No NBT value at {0}")]
+    UnknownNBTPath(String),
+    #[error("The database schema is at version {0}, newer than the supported version {1}")]
+    SchemaTooNew(u32, u32),
+    /// `error` came from running the sql `statement`
+    #[error("{error}")]
+    SQLStatement {
+        statement: String,
+        error: sqlx::Error,
+    },
+    #[error("Timed out waiting for the sql {0} to finish")]
+    SQLTimeout(&'static str),
+    #[error("Schema prefix \"{0}\" may only contain alphanumeric characters and underscores")]
+    InvalidSchemaPrefix(String),
+    #[error("KEY_BLOCK_SIZE {0} is not one of 1, 2, 4, 8 or 16")]
+    InvalidKeyBlockSize(u8),
+    #[error("The encryption key must be 64 hex digits")]
+    InvalidEncryptionKey,
+    #[error("Can't decrypt the player name {0} with this key")]
+    UndecryptablePlayerName(String),
+    #[error("More than one column has the header \"{0}\"")]
+    DuplicateHeader(String),
+    #[error("Template {name}{}: {message}", location(*line, *column))]
+    Template {
+        name: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    #[error("The given flags can't be used together")]
+    IncorrectFlags,
+    /// The misspelled name [`Error::IncorrectFlags`] had, never returned
+    #[deprecated(note = "renamed to Error::IncorrectFlags, which is what is returned")]
+    #[error("The given flags can't be used together")]
     IncorrecFlags,
+    /// Was returned for malformed objectives, never returned
+    #[deprecated(note = "objectives return Error::NBTMissingField or Error::NBTWrongType")]
+    #[error("NBT data file has a malformed objective")]
     LOLError,
+    /// Was returned for malformed player scores, never returned
+    #[deprecated(note = "player scores return Error::NBTMissingField or Error::NBTWrongType")]
+    #[error("NBT data file has a malformed player score")]
     NOTLOLError,
 }
 
-impl std::error::Error for Error {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match &self {
-            Self::NBTMissingField(_) => None,
-            Self::IncorrecFlags => None,
-            Self::LOLError => None,
-            Self::NOTLOLError => None,
-            &error => error.source(),
+impl Error {
+    /// Locates an error reading the element `index` of the NBT list at `path`
+    pub(crate) fn in_nbt_list(self, path: &'static str, index: usize) -> Self {
+        Self::NBTAt {
+            path,
+            index,
+            error: Box::new(self),
         }
     }
+}
 
-    fn description(&self) -> &str {
-        "description() is deprecated; use Display"
+fn location(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(" line {}, column {}", line, column),
+        (Some(line), None) => format!(" line {}", line),
+        _ => String::new(),
     }
+}
 
-    fn cause(&self) -> Option<&dyn std::error::Error> {
-        self.source()
+#[cfg(feature = "http")]
+impl From<ureq::Error> for Error {
+    fn from(value: ureq::Error) -> Self {
+        Self::HTTPError(Box::new(value))
     }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self {
-            Self::NBTMissingField(missing_field) => {
-                write!(
-                    f,
-                    "NBT data file is missing the required field \"{}\"",
-                    missing_field
-                )
-            }
-            Self::IncorrecFlags => write!(f, "IncorrecFlags"),
-            Self::LOLError => write!(f, "LOLError"),
-            Self::NOTLOLError => write!(f, "NOTLOLError"),
-            &error => {
-                write!(f, "{}", error.to_string())
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use std::{error::Error as StdError, iter};
+
+    use super::*;
+
+    /// The messages of `e` and every error it was caused by
+    fn chain(e: &(dyn StdError + 'static)) -> Vec<String> {
+        iter::successors(Some(e), |&e| e.source())
+            .map(ToString::to_string)
+            .collect()
     }
-}
 
-impl From<nbt::Error> for Error {
-    fn from(value: nbt::Error) -> Self {
-        Self::NBTError(value)
+    /// Wrapping the error of `make` keeps its chain as is, so no message
+    /// shows up twice
+    fn assert_transparent<E: StdError + Into<Error> + 'static>(make: impl Fn() -> E) {
+        let expected = chain(&make());
+        let wrapped = chain(&make().into());
+
+        assert_eq!(wrapped, expected);
+        assert!(
+            wrapped.windows(2).all(|x| x[0] != x[1]),
+            "{:?} repeats a message",
+            wrapped
+        );
     }
-}
 
-impl From<io::Error> for Error {
-    fn from(value: io::Error) -> Self {
-        Self::IOError(value)
+    fn io_error() -> io::Error {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "the file ended early")
     }
-}
 
-impl From<serde_json::Error> for Error {
-    fn from(value: serde_json::Error) -> Self {
-        Self::SerdeJsonError(value)
+    #[test]
+    fn io_error_is_transparent() {
+        assert_transparent(io_error);
     }
-}
 
-impl From<csv::Error> for Error {
-    fn from(value: csv::Error) -> Self {
-        Self::CSVError(value)
+    #[test]
+    fn nbt_error_is_transparent() {
+        assert_transparent(|| nbt::Error::IoError(io_error()));
+    }
+
+    #[test]
+    fn serde_json_error_is_transparent() {
+        assert_transparent(|| serde_json::from_str::<u8>("[").unwrap_err());
+    }
+
+    #[test]
+    fn csv_error_is_transparent() {
+        assert_transparent(|| csv::Error::from(io_error()));
+    }
+
+    #[test]
+    fn sqlx_error_keeps_the_io_error_as_its_source() {
+        assert_transparent(|| sqlx::Error::Io(io_error()));
+
+        let e = Error::from(sqlx::Error::Io(io_error()));
+        assert_eq!(e.source().unwrap().to_string(), "the file ended early");
+    }
+
+    #[test]
+    fn bincode_error_is_transparent() {
+        assert_transparent(|| bincode::Error::from(bincode::ErrorKind::Io(io_error())));
+    }
+
+    #[test]
+    fn toml_error_is_transparent() {
+        assert_transparent(|| toml::from_str::<toml::Table>("a =").unwrap_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_error_is_transparent() {
+        let make = || ureq::Error::from(io_error());
+
+        assert_eq!(chain(&Error::from(make())), chain(&make()));
     }
-}
 
-impl From<sqlx::Error> for Error {
-    fn from(value: sqlx::Error) -> Self {
-        Self::SQLXError(value)
+    #[test]
+    #[allow(deprecated)]
+    fn misspelled_variant_reads_like_the_renamed_one() {
+        assert_eq!(
+            Error::IncorrecFlags.to_string(),
+            Error::IncorrectFlags.to_string()
+        );
+        assert!(matches!(Error::IncorrecFlags, Error::IncorrecFlags));
     }
 }
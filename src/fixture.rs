@@ -0,0 +1,186 @@
+//! Gzipped scoreboard files for tests and benchmarks, written tag by tag
+//! so even a million scores never have to be built as an nbt tree first
+
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use flate2::{write::GzEncoder, Compression};
+
+const TAG_END: u8 = 0x00;
+const TAG_BYTE: u8 = 0x01;
+const TAG_INT: u8 = 0x03;
+const TAG_STRING: u8 = 0x08;
+const TAG_LIST: u8 = 0x09;
+const TAG_COMPOUND: u8 = 0x0a;
+
+/// A scoreboard with the `(name, criteria)` objectives and the
+/// `(player, objective, score)` scores, the display name of an objective is
+/// its name
+pub fn scoreboard<P, O>(
+    objectives: &[(&str, &str)],
+    scores: impl ExactSizeIterator<Item = (P, O, i32)>,
+) -> Vec<u8>
+where
+    P: AsRef<str>,
+    O: AsRef<str>,
+{
+    write(objectives, Some(scores))
+}
+
+/// A scoreboard like some tools write it, with objectives but without a
+/// PlayerScores list at all
+pub fn scoreboard_without_scores(objectives: &[(&str, &str)]) -> Vec<u8> {
+    write(objectives, None::<std::iter::Empty<(&str, &str, i32)>>)
+}
+
+/// A scoreboard like [`scoreboard`] with the `(name, members)` teams, the
+/// display name of a team is its name in upper case
+pub fn scoreboard_with_teams<P, O>(
+    objectives: &[(&str, &str)],
+    scores: impl ExactSizeIterator<Item = (P, O, i32)>,
+    teams: &[(&str, &[&str])],
+) -> Vec<u8>
+where
+    P: AsRef<str>,
+    O: AsRef<str>,
+{
+    write_with_teams(objectives, Some(scores), teams)
+}
+
+/// A scoreboard where every one of `players` players has a score in every
+/// one of `objectives` dummy objectives, named like `player12` and
+/// `objective3`
+pub fn generated(players: usize, objectives: usize) -> Vec<u8> {
+    let names: Vec<String> = (0..objectives).map(|i| format!("objective{}", i)).collect();
+    let list: Vec<(&str, &str)> = names.iter().map(|x| (x.as_str(), "dummy")).collect();
+
+    scoreboard(
+        &list,
+        (0..players * objectives).map(|i| {
+            (
+                format!("player{}", i / objectives),
+                names[i % objectives].as_str(),
+                (i % 100_000) as i32,
+            )
+        }),
+    )
+}
+
+fn write<P, O>(
+    objectives: &[(&str, &str)],
+    scores: Option<impl ExactSizeIterator<Item = (P, O, i32)>>,
+) -> Vec<u8>
+where
+    P: AsRef<str>,
+    O: AsRef<str>,
+{
+    write_with_teams(objectives, scores, &[])
+}
+
+fn write_with_teams<P, O>(
+    objectives: &[(&str, &str)],
+    scores: Option<impl ExactSizeIterator<Item = (P, O, i32)>>,
+    teams: &[(&str, &[&str])],
+) -> Vec<u8>
+where
+    P: AsRef<str>,
+    O: AsRef<str>,
+{
+    //the encoder is slow with many small writes, so it gets the whole file at once
+    let mut w = Vec::new();
+
+    header(&mut w, TAG_COMPOUND, "");
+    header(&mut w, TAG_COMPOUND, "data");
+
+    header(&mut w, TAG_LIST, "Objectives");
+    list_header(&mut w, objectives.len());
+    for (name, criteria) in objectives {
+        string(&mut w, "Name", name);
+        string(&mut w, "CriteriaName", criteria);
+        string(&mut w, "DisplayName", &format!("\"{}\"", name));
+        string(&mut w, "RenderType", "integer");
+        byte(&mut w, "display_auto_update", 0);
+        w.write_all(&[TAG_END]).unwrap();
+    }
+
+    if let Some(scores) = scores {
+        header(&mut w, TAG_LIST, "PlayerScores");
+        list_header(&mut w, scores.len());
+        for (player, objective, score) in scores {
+            string(&mut w, "Name", player.as_ref());
+            string(&mut w, "Objective", objective.as_ref());
+            header(&mut w, TAG_INT, "Score");
+            w.write_all(&score.to_be_bytes()).unwrap();
+            byte(&mut w, "Locked", 0);
+            w.write_all(&[TAG_END]).unwrap();
+        }
+    }
+
+    if !teams.is_empty() {
+        header(&mut w, TAG_LIST, "Teams");
+        list_header(&mut w, teams.len());
+        for (name, players) in teams {
+            string(&mut w, "Name", name);
+            string(
+                &mut w,
+                "DisplayName",
+                &format!("\"{}\"", name.to_uppercase()),
+            );
+            header(&mut w, TAG_LIST, "Players");
+            w.write_all(&[TAG_STRING]).unwrap();
+            w.write_all(&(players.len() as i32).to_be_bytes()).unwrap();
+            for player in players.iter() {
+                raw_string(&mut w, player);
+            }
+            w.write_all(&[TAG_END]).unwrap();
+        }
+    }
+
+    //ends data and the root
+    w.write_all(&[TAG_END, TAG_END]).unwrap();
+
+    let mut gz = GzEncoder::new(Vec::new(), Compression::fast());
+    gz.write_all(&w).unwrap();
+    gz.finish().unwrap()
+}
+
+fn header(w: &mut impl Write, id: u8, name: &str) {
+    w.write_all(&[id]).unwrap();
+    raw_string(w, name);
+}
+
+/// A list of compounds, empty lists have no element type like Minecraft
+/// writes them
+fn list_header(w: &mut impl Write, len: usize) {
+    let id = match len {
+        0 => TAG_END,
+        _ => TAG_COMPOUND,
+    };
+    w.write_all(&[id]).unwrap();
+    w.write_all(&(len as i32).to_be_bytes()).unwrap();
+}
+
+fn string(w: &mut impl Write, name: &str, value: &str) {
+    header(w, TAG_STRING, name);
+    raw_string(w, value);
+}
+
+fn byte(w: &mut impl Write, name: &str, value: i8) {
+    header(w, TAG_BYTE, name);
+    w.write_all(&value.to_be_bytes()).unwrap();
+}
+
+fn raw_string(w: &mut impl Write, value: &str) {
+    w.write_all(&(value.len() as u16).to_be_bytes()).unwrap();
+    w.write_all(value.as_bytes()).unwrap();
+}
+
+/// An empty directory for the files of a single test, left behind for
+/// inspecting failures
+pub fn temp_dir(test: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("poop-data-{}-{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
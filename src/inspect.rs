@@ -0,0 +1,388 @@
+//! Dumps the raw NBT tree of a file without parsing it as a scoreboard,
+//! for finding out why a file can't be read
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+};
+
+use flate2::bufread::GzDecoder;
+use nbt::Value;
+use serde_json::{json, Map};
+
+use crate::error::Error;
+
+/// How many elements of a list or array are shown without `full`
+const LIST_LIMIT: usize = 10;
+
+/// Options for [`write_tree`] and [`to_json`]
+#[derive(Debug, Clone, Default)]
+pub struct InspectOptions {
+    /// Levels below the inspected value that are expanded, deeper lists
+    /// and compounds only show their type
+    pub depth: Option<usize>,
+    /// Show every element of long lists and arrays
+    pub full: bool,
+}
+
+/// Reads the root tag of a gzipped or uncompressed NBT file as its name
+/// and value
+pub fn read_nbt(src: impl Read) -> Result<(String, Value), Error> {
+    let mut src = BufReader::new(src);
+
+    match src.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        true => read_root(GzDecoder::new(src)),
+        false => read_root(src),
+    }
+}
+
+fn read_root(mut src: impl Read) -> Result<(String, Value), Error> {
+    let mut tag = [0; 1];
+    src.read_exact(&mut tag)?;
+
+    let mut len = [0; 2];
+    src.read_exact(&mut len)?;
+
+    let mut name = vec![0; u16::from_be_bytes(len) as usize];
+    src.read_exact(&mut name)?;
+
+    let value = Value::from_reader(tag[0], &mut src)?;
+
+    Ok((String::from_utf8_lossy(&name).into_owned(), value))
+}
+
+/// The value at a path of compound keys and list indices separated by
+/// dots, like `data.Objectives.0`
+pub fn find_path<'a>(value: &'a Value, path: &str) -> Result<&'a Value, Error> {
+    path.split('.')
+        .filter(|x| !x.is_empty())
+        .try_fold(value, |value, segment| {
+            let child = match value {
+                Value::Compound(map) => map.get(segment),
+                Value::List(list) => segment.parse::<usize>().ok().and_then(|i| list.get(i)),
+                _ => None,
+            };
+
+            child.ok_or_else(|| Error::UnknownNBTPath(path.to_string()))
+        })
+}
+
+/// The type of a value as shown in the tree, like `List[Compound;34]`
+pub fn type_name(value: &Value) -> String {
+    match value {
+        Value::List(list) => format!(
+            "List[{};{}]",
+            list.first().map(tag_name).unwrap_or("End"),
+            list.len()
+        ),
+        Value::ByteArray(x) => format!("ByteArray[{}]", x.len()),
+        Value::IntArray(x) => format!("IntArray[{}]", x.len()),
+        Value::LongArray(x) => format!("LongArray[{}]", x.len()),
+        _ => tag_name(value).to_string(),
+    }
+}
+
+fn tag_name(value: &Value) -> &'static str {
+    match value {
+        Value::Byte(_) => "Byte",
+        Value::Short(_) => "Short",
+        Value::Int(_) => "Int",
+        Value::Long(_) => "Long",
+        Value::Float(_) => "Float",
+        Value::Double(_) => "Double",
+        Value::ByteArray(_) => "ByteArray",
+        Value::String(_) => "String",
+        Value::List(_) => "List",
+        Value::Compound(_) => "Compound",
+        Value::IntArray(_) => "IntArray",
+        Value::LongArray(_) => "LongArray",
+    }
+}
+
+/// Writes the value as an indented tree with the type of every value and
+/// the value of every scalar
+pub fn write_tree(
+    mut w: impl Write,
+    name: &str,
+    value: &Value,
+    opts: &InspectOptions,
+) -> io::Result<()> {
+    let mut out = String::new();
+    tree_lines(&mut out, name, value, 0, opts);
+    w.write_all(out.as_bytes())?;
+    w.flush()
+}
+
+fn tree_lines(out: &mut String, name: &str, value: &Value, level: usize, opts: &InspectOptions) {
+    let indent = "  ".repeat(level);
+    let expand = opts.depth.is_none_or(|depth| level < depth);
+
+    match value {
+        Value::Compound(map) => {
+            writeln!(out, "{}{}: {}", indent, name, type_name(value)).unwrap();
+
+            if expand {
+                for (key, child) in sorted(map) {
+                    tree_lines(out, key, child, level + 1, opts);
+                }
+            }
+        }
+        Value::List(list) => {
+            writeln!(out, "{}{}: {}", indent, name, type_name(value)).unwrap();
+
+            if expand {
+                for (i, child) in list.iter().take(shown(list.len(), opts)).enumerate() {
+                    tree_lines(out, &i.to_string(), child, level + 1, opts);
+                }
+                more_line(out, level + 1, list.len(), opts);
+            }
+        }
+        Value::ByteArray(x) => array_line(out, &indent, name, value, x, opts),
+        Value::IntArray(x) => array_line(out, &indent, name, value, x, opts),
+        Value::LongArray(x) => array_line(out, &indent, name, value, x, opts),
+        Value::String(x) => writeln!(out, "{}{}: String = {:?}", indent, name, x).unwrap(),
+        _ => writeln!(
+            out,
+            "{}{}: {} = {}",
+            indent,
+            name,
+            type_name(value),
+            scalar(value)
+        )
+        .unwrap(),
+    }
+}
+
+fn array_line<T: ToString>(
+    out: &mut String,
+    indent: &str,
+    name: &str,
+    value: &Value,
+    elements: &[T],
+    opts: &InspectOptions,
+) {
+    let shown: Vec<String> = elements
+        .iter()
+        .take(shown(elements.len(), opts))
+        .map(T::to_string)
+        .collect();
+    let rest = match elements.len() - shown.len() {
+        0 => String::new(),
+        n => format!(", … {} more", n),
+    };
+
+    writeln!(
+        out,
+        "{}{}: {} = [{}{}]",
+        indent,
+        name,
+        type_name(value),
+        shown.join(", "),
+        rest
+    )
+    .unwrap();
+}
+
+fn more_line(out: &mut String, level: usize, len: usize, opts: &InspectOptions) {
+    if len > shown(len, opts) {
+        writeln!(
+            out,
+            "{}… {} more",
+            "  ".repeat(level),
+            len - shown(len, opts)
+        )
+        .unwrap();
+    }
+}
+
+/// The value as json, every value being an object with its `type` and
+/// its `value`. Truncated lists and arrays get the number of elements
+/// left out as `more`.
+pub fn to_json(value: &Value, opts: &InspectOptions) -> serde_json::Value {
+    json_node(value, 0, opts)
+}
+
+fn json_node(value: &Value, level: usize, opts: &InspectOptions) -> serde_json::Value {
+    let expand = opts.depth.is_none_or(|depth| level < depth);
+
+    let (inner, len) = match value {
+        Value::Compound(_) | Value::List(_) if !expand => {
+            return json!({ "type": type_name(value) })
+        }
+        Value::Compound(map) => (
+            serde_json::Value::Object(
+                sorted(map)
+                    .into_iter()
+                    .map(|(key, child)| (key.clone(), json_node(child, level + 1, opts)))
+                    .collect::<Map<_, _>>(),
+            ),
+            None,
+        ),
+        Value::List(list) => (
+            list.iter()
+                .take(shown(list.len(), opts))
+                .map(|child| json_node(child, level + 1, opts))
+                .collect(),
+            Some(list.len()),
+        ),
+        Value::ByteArray(x) => (json!(x[..shown(x.len(), opts)]), Some(x.len())),
+        Value::IntArray(x) => (json!(x[..shown(x.len(), opts)]), Some(x.len())),
+        Value::LongArray(x) => (json!(x[..shown(x.len(), opts)]), Some(x.len())),
+        Value::String(x) => (json!(x), None),
+        Value::Byte(x) => (json!(x), None),
+        Value::Short(x) => (json!(x), None),
+        Value::Int(x) => (json!(x), None),
+        Value::Long(x) => (json!(x), None),
+        Value::Float(x) => (json!(x), None),
+        Value::Double(x) => (json!(x), None),
+    };
+
+    let mut node = json!({ "type": type_name(value), "value": inner });
+
+    if let Some(len) = len.filter(|len| *len > shown(*len, opts)) {
+        node["more"] = json!(len - shown(len, opts));
+    }
+
+    node
+}
+
+fn shown(len: usize, opts: &InspectOptions) -> usize {
+    match opts.full {
+        true => len,
+        false => len.min(LIST_LIMIT),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Byte(x) => x.to_string(),
+        Value::Short(x) => x.to_string(),
+        Value::Int(x) => x.to_string(),
+        Value::Long(x) => x.to_string(),
+        Value::Float(x) => x.to_string(),
+        Value::Double(x) => x.to_string(),
+        _ => String::new(),
+    }
+}
+
+//compounds are hash maps, sorting keeps the dump the same between runs
+fn sorted(map: &nbt::Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    /// A scoreboard with `players` scores in a single objective
+    fn root(players: usize) -> Value {
+        let names: Vec<String> = (0..players).map(|i| format!("p{}", i)).collect();
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount")],
+            names.iter().map(|x| (x.as_str(), "deaths", 1)),
+        );
+
+        read_nbt(&nbt[..]).unwrap().1
+    }
+
+    fn tree(value: &Value, opts: &InspectOptions) -> String {
+        let mut out = Vec::new();
+        write_tree(&mut out, "score", value, opts).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn gzipped_and_uncompressed_files_are_read() {
+        let nbt = fixture::scoreboard_without_scores(&[("deaths", "deathCount")]);
+        let mut raw = Vec::new();
+        flate2::read::GzDecoder::new(&nbt[..])
+            .read_to_end(&mut raw)
+            .unwrap();
+
+        assert_eq!(read_nbt(&nbt[..]).unwrap(), read_nbt(&raw[..]).unwrap());
+    }
+
+    #[test]
+    fn paths_find_keys_and_indices() {
+        let root = root(2);
+
+        assert_eq!(
+            find_path(&root, "data.Objectives.0.Name").unwrap(),
+            &Value::String("deaths".to_string())
+        );
+        assert_eq!(
+            find_path(&root, "data.PlayerScores.1.Name").unwrap(),
+            &Value::String("p1".to_string())
+        );
+        assert_eq!(find_path(&root, "").unwrap(), &root);
+    }
+
+    #[test]
+    fn unknown_paths_are_errors() {
+        let root = root(2);
+
+        for path in [
+            "data.Nope",
+            "data.PlayerScores.2",
+            "data.PlayerScores.first",
+            "data.Objectives.0.Name.0",
+        ] {
+            assert!(
+                matches!(find_path(&root, path), Err(Error::UnknownNBTPath(x)) if x == path),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn tree_shows_types_and_values() {
+        let root = root(1);
+        let score = find_path(&root, "data.PlayerScores.0").unwrap();
+
+        assert_eq!(
+            tree(score, &InspectOptions::default()),
+            "score: Compound\n  \
+            Locked: Byte = 0\n  \
+            Name: String = \"p0\"\n  \
+            Objective: String = \"deaths\"\n  \
+            Score: Int = 1\n"
+        );
+        assert_eq!(
+            tree(
+                score,
+                &InspectOptions {
+                    depth: Some(0),
+                    ..Default::default()
+                }
+            ),
+            "score: Compound\n"
+        );
+    }
+
+    #[test]
+    fn long_lists_are_truncated_unless_full() {
+        let root = root(LIST_LIMIT + 2);
+        let scores = find_path(&root, "data.PlayerScores").unwrap();
+        let shallow = InspectOptions {
+            depth: Some(1),
+            ..Default::default()
+        };
+
+        let truncated = tree(scores, &shallow);
+        assert!(truncated.starts_with("score: List[Compound;12]\n  0: Compound\n"));
+        assert!(truncated.ends_with("  9: Compound\n  … 2 more\n"));
+        assert_eq!(to_json(scores, &shallow)["more"], json!(2));
+
+        let full = InspectOptions {
+            full: true,
+            ..shallow
+        };
+        assert!(tree(scores, &full).ends_with("  11: Compound\n"));
+        assert_eq!(to_json(scores, &full).get("more"), None);
+    }
+}
@@ -1,2 +1,19 @@
+pub mod atomic_file;
+#[cfg(feature = "http")]
+pub mod clickhouse;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "http")]
+pub mod discord;
+pub mod edit;
 pub mod error;
+#[cfg(test)]
+mod fixture;
+pub mod inspect;
+#[cfg(feature = "http")]
+pub mod mojang;
+#[cfg(feature = "http")]
+pub mod post;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod stats;
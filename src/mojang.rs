@@ -0,0 +1,216 @@
+//! Player UUIDs looked up by name with the Mojang profiles API, for
+//! servers without a usercache.json
+//!
+//! This sends player names to Mojang, so it is only done when asked for.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{atomic_file::AtomicFile, error::Error, stats::UuidMap};
+
+pub const PROFILES_URL: &str = "https://api.mojang.com/profiles/minecraft";
+
+/// The most names the profiles API takes in one request
+const BATCH_SIZE: usize = 10;
+
+const CACHE_FILE: &str = "mojang_uuids.json";
+
+/// Names without a profile are asked about again after this long, in
+/// case the name has been taken since
+const NOT_FOUND_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Options for [`resolve_uuids`]
+#[derive(Debug, Clone)]
+pub struct MojangOptions {
+    pub url: String,
+    /// Where looked up names are remembered between runs, so names are
+    /// only sent once and the rate limit lasts longer
+    pub cache_dir: Option<PathBuf>,
+    pub timeout: Duration,
+}
+
+impl Default for MojangOptions {
+    fn default() -> Self {
+        Self {
+            url: PROFILES_URL.to_string(),
+            cache_dir: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What [`resolve_uuids`] found out
+#[derive(Debug, Default)]
+pub struct UuidLookup {
+    /// The UUIDs of the names that have a profile, in the dashed form
+    /// usercache.json uses
+    pub uuids: UuidMap,
+    /// Names without a Mojang profile, like renamed players
+    pub not_found: Vec<String>,
+    /// Names that couldn't be looked up
+    pub failed: Vec<String>,
+    /// Why lookups or the cache failed. The lookup carries on past them.
+    pub errors: Vec<Error>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    uuid: Option<String>,
+    /// Seconds since the Unix epoch
+    looked_up: u64,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    id: String,
+    name: String,
+}
+
+/// Looks up the UUIDs of `names`, first in the cache and then in batches
+/// of 10 with the profiles API.
+///
+/// Nothing here fails the export: names whose lookup fails are listed in
+/// [`UuidLookup::failed`] with the reasons in [`UuidLookup::errors`].
+/// After a failed request the remaining names aren't sent either, as
+/// failures are usually the rate limit or a missing connection.
+pub fn resolve_uuids(names: &[&str], opts: &MojangOptions) -> UuidLookup {
+    let mut lookup = UuidLookup::default();
+    let now = unix_time(SystemTime::now());
+
+    let cache_path = opts.cache_dir.as_ref().map(|x| x.join(CACHE_FILE));
+    let mut cache: HashMap<String, CacheEntry> = match &cache_path {
+        Some(path) if path.exists() => match read_cache(path) {
+            Ok(cache) => cache,
+            Err(e) => {
+                lookup.errors.push(e);
+                HashMap::new()
+            }
+        },
+        _ => HashMap::new(),
+    };
+
+    //names are case insensitive
+    let mut to_send = Vec::new();
+    for name in names {
+        match cache.get(&name.to_lowercase()) {
+            Some(CacheEntry {
+                uuid: Some(uuid), ..
+            }) => {
+                lookup.uuids.insert(name.to_string(), uuid.clone());
+            }
+            Some(entry) if now.saturating_sub(entry.looked_up) < NOT_FOUND_TTL.as_secs() => {
+                lookup.not_found.push(name.to_string());
+            }
+            _ => to_send.push(*name),
+        }
+    }
+
+    let agent = ureq::AgentBuilder::new().timeout(opts.timeout).build();
+    let mut batches = to_send.chunks(BATCH_SIZE);
+
+    for batch in batches.by_ref() {
+        let profiles = match request_profiles(&agent, &opts.url, batch) {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                lookup.errors.push(e);
+                lookup.failed.extend(batch.iter().map(|x| x.to_string()));
+                break;
+            }
+        };
+
+        let found: HashMap<String, String> = profiles
+            .into_iter()
+            .map(|x| (x.name.to_lowercase(), dashed(&x.id)))
+            .collect();
+
+        for name in batch {
+            let uuid = found.get(&name.to_lowercase()).cloned();
+
+            match &uuid {
+                Some(uuid) => {
+                    lookup.uuids.insert(name.to_string(), uuid.clone());
+                }
+                None => lookup.not_found.push(name.to_string()),
+            }
+
+            cache.insert(
+                name.to_lowercase(),
+                CacheEntry {
+                    uuid,
+                    looked_up: now,
+                },
+            );
+        }
+    }
+
+    lookup
+        .failed
+        .extend(batches.flatten().map(|x| x.to_string()));
+
+    if let Some(path) = &cache_path {
+        if let Err(e) = write_cache(path, &cache) {
+            lookup.errors.push(e);
+        }
+    }
+
+    lookup
+}
+
+fn request_profiles(agent: &ureq::Agent, url: &str, names: &[&str]) -> Result<Vec<Profile>, Error> {
+    let body = serde_json::to_string(names)?;
+
+    match agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+    {
+        Ok(response) => Ok(serde_json::from_str(&response.into_string()?)?),
+        Err(ureq::Error::Status(status, response)) => Err(Error::HTTPStatus(
+            status,
+            response.into_string().unwrap_or_default(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The API leaves the dashes out of UUIDs
+fn dashed(id: &str) -> String {
+    match id.len() == 32 && id.is_ascii() {
+        true => format!(
+            "{}-{}-{}-{}-{}",
+            &id[..8],
+            &id[8..12],
+            &id[12..16],
+            &id[16..20],
+            &id[20..]
+        ),
+        false => id.to_string(),
+    }
+}
+
+fn read_cache(path: &Path) -> Result<HashMap<String, CacheEntry>, Error> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+fn write_cache(path: &Path, cache: &HashMap<String, CacheEntry>) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = AtomicFile::create(path)?;
+    serde_json::to_writer(&mut file, cache)?;
+    file.commit()?;
+
+    Ok(())
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
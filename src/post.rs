@@ -0,0 +1,150 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{error::Error, stats::Stats};
+
+/// How the stats are serialized into the request body
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PostFormat {
+    /// The whole scoreboard as a single json document
+    #[default]
+    Json,
+    /// One `{"objective_name","player_name","score"}` object per line
+    Ndjson,
+}
+
+/// Options for sending the stats to an http endpoint
+#[derive(Debug, Clone)]
+pub struct PostOptions {
+    pub url: String,
+    pub format: PostFormat,
+    /// Extra request headers as `(name, value)` pairs
+    pub headers: Vec<(String, String)>,
+    /// How many times a request answered with a 5xx status is repeated
+    pub retries: u32,
+    /// Timeout for a single request
+    pub timeout: Duration,
+    /// Serializing stops with an error once the body grows past this many bytes
+    pub max_body_len: usize,
+}
+
+impl Default for PostOptions {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            format: PostFormat::default(),
+            headers: Vec::new(),
+            retries: 0,
+            timeout: Duration::from_secs(30),
+            max_body_len: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ScoreLine<'a> {
+    objective_name: &'a str,
+    player_name: &'a str,
+    score: i64,
+}
+
+impl PostOptions {
+    /// Serializes the stats once and POSTs them to `url`, retrying server errors
+    pub fn post(&self, stats: &Stats) -> Result<(), Error> {
+        let body = self.body(stats)?;
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+        let content_type = match self.format {
+            PostFormat::Json => "application/json",
+            PostFormat::Ndjson => "application/x-ndjson",
+        };
+
+        let mut attempt = 0;
+
+        loop {
+            let mut request = agent.post(&self.url).set("Content-Type", content_type);
+            for (name, value) in self.headers.iter() {
+                request = request.set(name, value);
+            }
+
+            match request.send_bytes(&body) {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(status, _)) if status >= 500 && attempt < self.retries => {
+                    attempt += 1;
+                    thread::sleep(Duration::from_secs(attempt.into()));
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    return Err(Error::HTTPStatus(
+                        status,
+                        response.into_string().unwrap_or_default(),
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn body(&self, stats: &Stats) -> Result<Vec<u8>, Error> {
+        let mut body = CappedWriter {
+            buf: Vec::new(),
+            max_len: self.max_body_len,
+            exceeded: false,
+        };
+
+        let result = match self.format {
+            PostFormat::Json => serde_json::to_writer(&mut body, stats),
+            PostFormat::Ndjson => write_ndjson(&mut body, stats),
+        };
+
+        match result {
+            Ok(()) => Ok(body.buf),
+            Err(_) if body.exceeded => Err(Error::PostBodyTooLarge(body.max_len)),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn write_ndjson(mut w: impl Write, stats: &Stats) -> Result<(), serde_json::Error> {
+    for (objective_name, scores) in stats.player_scores().iter() {
+        for score in scores {
+            serde_json::to_writer(
+                &mut w,
+                &ScoreLine {
+                    objective_name,
+                    player_name: score.player_name(),
+                    score: score.score(),
+                },
+            )?;
+            w.write_all(b"\n").map_err(serde_json::Error::io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps a serializer from growing the body without bound
+struct CappedWriter {
+    buf: Vec<u8>,
+    max_len: usize,
+    exceeded: bool,
+}
+
+impl Write for CappedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + data.len() > self.max_len {
+            self.exceeded = true;
+            return Err(io::Error::other("request body too large"));
+        }
+
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    fs::{self, File},
+    io::{self, Cursor},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    error::Error,
+    stats::{ExportOptions, Stats},
+};
+
+/// How long a parsed file is used without checking whether it changed
+const CACHE_TTL: Duration = Duration::from_secs(2);
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Serves the scores in the scoreboard file at `input` on the `listen`
+/// address until the process is stopped, see [`ScoreServer`]
+pub fn serve(input: &Path, listen: &str) -> Result<(), Error> {
+    ScoreServer::bind(input, listen)?.run()
+}
+
+/// A server listening for requests for the scores in a scoreboard file.
+///
+/// The file is parsed on demand and reparsed only once its modification
+/// time changes. A file that fails to parse gives 503 responses with the
+/// error instead of stopping the server.
+pub struct ScoreServer {
+    server: Server,
+    source: ScoreboardSource,
+}
+
+impl ScoreServer {
+    /// Binds the `listen` address, requests wait until [`ScoreServer::run`]
+    pub fn bind(input: &Path, listen: &str) -> Result<Self, Error> {
+        Ok(Self {
+            server: Server::http(listen).map_err(io::Error::other)?,
+            source: ScoreboardSource::new(input),
+        })
+    }
+
+    /// The address requests are answered on, with the port the system
+    /// picked when `listen` asked for port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Answers requests until the process is stopped
+    pub fn run(mut self) -> Result<(), Error> {
+        for request in self.server.incoming_requests() {
+            let response = match request.method() {
+                Method::Get => respond(&mut self.source, request.url()),
+                _ => text(405, "only GET is supported"),
+            };
+
+            //the client may have gone away already, which is no reason to stop serving
+            let _ = request.respond(response);
+        }
+
+        Ok(())
+    }
+}
+
+struct CachedStats {
+    modified: SystemTime,
+    checked: Instant,
+    stats: Arc<Stats>,
+}
+
+struct ScoreboardSource {
+    path: PathBuf,
+    cached: Option<CachedStats>,
+}
+
+impl ScoreboardSource {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            cached: None,
+        }
+    }
+
+    fn stats(&mut self) -> Result<Arc<Stats>, Error> {
+        if let Some(cached) = &mut self.cached {
+            if cached.checked.elapsed() < CACHE_TTL {
+                return Ok(cached.stats.clone());
+            }
+
+            if fs::metadata(&self.path)?.modified()? == cached.modified {
+                cached.checked = Instant::now();
+                return Ok(cached.stats.clone());
+            }
+        }
+
+        let modified = fs::metadata(&self.path)?.modified()?;
+        let stats = Arc::new(Stats::from_gzip_reader(File::open(&self.path)?)?);
+
+        self.cached = Some(CachedStats {
+            modified,
+            checked: Instant::now(),
+            stats: stats.clone(),
+        });
+
+        Ok(stats)
+    }
+}
+
+fn respond(source: &mut ScoreboardSource, url: &str) -> HttpResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    if path == "/healthz" {
+        return text(200, "ok");
+    }
+
+    let stats = match source.stats() {
+        Ok(stats) => stats.filtered(&parse_filter(query)),
+        Err(e) => return text(503, &format!("{:?}", e)),
+    };
+
+    match path {
+        "/scores.json" => json(&stats),
+        "/scores.csv" => {
+            let mut body = Vec::new();
+
+            match stats.write_csv(&mut body) {
+                Ok(()) => with_content_type(Response::from_data(body), "text/csv; charset=utf-8"),
+                Err(e) => text(500, &format!("{:?}", e)),
+            }
+        }
+        "/objectives" => json(stats.objectives()),
+        _ => match path.strip_prefix("/players/") {
+            Some(name) => match stats.scores_of(&decode(name)) {
+                Some(scores) => json(&scores),
+                None => text(404, "no scores for this player"),
+            },
+            None => text(404, "not found"),
+        },
+    }
+}
+
+/// Reads the comma separated `objectives` and `players` query parameters
+fn parse_filter(query: &str) -> ExportOptions {
+    let mut filter = ExportOptions::default();
+
+    for (key, value) in query.split('&').filter_map(|x| x.split_once('=')) {
+        let values = Some(decode(value).split(',').map(String::from).collect());
+
+        match key {
+            "objectives" => filter.objectives = values,
+            "players" => filter.players = values,
+            _ => (),
+        }
+    }
+
+    filter
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+fn json(value: &impl Serialize) -> HttpResponse {
+    match serde_json::to_vec(value) {
+        Ok(body) => with_content_type(Response::from_data(body), "application/json"),
+        Err(e) => text(500, &e.to_string()),
+    }
+}
+
+fn text(status: u16, body: &str) -> HttpResponse {
+    with_content_type(
+        Response::from_data(body.as_bytes().to_vec()).with_status_code(status),
+        "text/plain; charset=utf-8",
+    )
+}
+
+fn with_content_type(response: HttpResponse, content_type: &str) -> HttpResponse {
+    response.with_header(Header::from_bytes("Content-Type", content_type).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    use super::*;
+    use crate::fixture;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn bound_address_is_returned_and_served() {
+        let input = fixture::temp_dir("server_bound").join("scoreboard.dat");
+        fs::write(
+            &input,
+            fixture::scoreboard(
+                &[("deaths", "deathCount")],
+                [("ville", "deaths", 3)].into_iter(),
+            ),
+        )
+        .unwrap();
+
+        let server = ScoreServer::bind(&input, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        thread::spawn(move || server.run());
+
+        assert!(get(addr, "/healthz").ends_with("ok"));
+        assert!(get(addr, "/scores.csv").ends_with("Players,deaths\nville,3\n"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::HashMap, io::Write};
+
+use csv::ByteRecord;
+
+use super::{CsvOptions, Stats};
+use crate::error::Error;
+
+type ScoreLookup<'a> = HashMap<(&'a str, &'a str), i64>;
+
+impl Stats {
+    /// Writes a csv like [`Stats::write_csv`] with an `Active` column
+    /// appended, `Y` when any score of the player changed since `previous`
+    /// and `N` when they are all the same.
+    ///
+    /// A score that was added or removed since `previous` counts as a
+    /// change, so players new since then are active.
+    pub fn write_csv_with_activity_flag(
+        &self,
+        w: impl Write,
+        previous: &Stats,
+    ) -> Result<(), Error> {
+        let titles = self.titles();
+        let current_scores = score_lookup(self);
+        let previous_scores = score_lookup(previous);
+
+        //objectives only in the previous snapshot still count for changes
+        let mut objectives: Vec<&str> = self
+            .player_scores
+            .keys()
+            .chain(previous.player_scores.keys())
+            .map(String::as_str)
+            .collect();
+        objectives.sort_unstable();
+        objectives.dedup();
+
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+
+        for column in self.columns(&titles, &CsvOptions::default(), false)? {
+            record.push_field(column.name.as_bytes());
+        }
+        record.push_field(b"Active");
+        w.write_byte_record(&record)?;
+
+        for player in self.player_names() {
+            record.clear();
+            record.push_field(player.as_bytes());
+
+            for title in &titles {
+                let score = current_scores.get(&(title.as_str(), player));
+                record.push_field(score_buffer.format(*score.unwrap_or(&0)).as_bytes());
+            }
+
+            let active = objectives.iter().any(|objective| {
+                current_scores.get(&(objective, player))
+                    != previous_scores.get(&(objective, player))
+            });
+            record.push_field(match active {
+                true => b"Y",
+                false => b"N",
+            });
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+fn score_lookup(stats: &Stats) -> ScoreLookup<'_> {
+    stats
+        .player_scores
+        .iter()
+        .flat_map(|(objective, scores)| {
+            scores
+                .iter()
+                .map(move |x| ((objective.as_str(), x.player_name()), x.score))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats(scores: &[(&str, &str, i32)]) -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.iter().copied(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    #[test]
+    fn added_and_removed_scores_are_changes() {
+        let previous = stats(&[
+            ("ville", "deaths", 3),
+            ("ville", "kills", 1),
+            ("anna", "kills", 5),
+            ("otto", "deaths", 2),
+            ("otto", "kills", 1),
+            ("mia", "kills", 4),
+            ("gone", "kills", 9),
+        ]);
+        let current = stats(&[
+            ("ville", "deaths", 3),
+            ("ville", "kills", 1),
+            ("anna", "kills", 7),
+            ("otto", "kills", 1),
+            ("mia", "deaths", 0),
+            ("mia", "kills", 4),
+            ("neo", "kills", 1),
+        ]);
+
+        let mut out = Vec::new();
+        current
+            .write_csv_with_activity_flag(&mut out, &previous)
+            .unwrap();
+
+        //otto lost a score and mia gained one, even if it is 0
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Players,deaths,kills,Active\n\
+            anna,0,7,Y\n\
+            mia,0,4,Y\n\
+            neo,0,1,Y\n\
+            otto,0,1,Y\n\
+            ville,3,1,N\n"
+        );
+    }
+
+    #[test]
+    fn objectives_removed_since_the_previous_snapshot_are_changes() {
+        let previous = stats(&[("anna", "deaths", 1), ("anna", "kills", 1)]);
+        let nbt = fixture::scoreboard(
+            &[("kills", "playerKillCount")],
+            [("anna", "kills", 1)].into_iter(),
+        );
+        let current = Stats::from_gzip_reader(&nbt[..]).unwrap();
+
+        let mut out = Vec::new();
+        current
+            .write_csv_with_activity_flag(&mut out, &previous)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Players,kills,Active\nanna,1,Y\n"
+        );
+    }
+}
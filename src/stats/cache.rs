@@ -0,0 +1,299 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{NameMerge, NameNormalization, Stats};
+use crate::{atomic_file::AtomicFile, error::Error};
+
+/// Bumped whenever the layout of [`Stats`] changes so old caches get ignored
+const CACHE_VERSION: u32 = 5;
+
+/// Identifies the exact source file a cache was made from
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CacheHeader {
+    version: u32,
+    source_len: u64,
+    source_modified: SystemTime,
+    source_sha256: [u8; 32],
+    /// The player names are cached normalized
+    normalization: NameNormalization,
+}
+
+impl CacheHeader {
+    fn new(
+        source: &Path,
+        source_bytes: &[u8],
+        normalization: &NameNormalization,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            version: CACHE_VERSION,
+            source_len: source_bytes.len() as u64,
+            source_modified: fs::metadata(source)?.modified()?,
+            source_sha256: Sha256::digest(source_bytes).into(),
+            normalization: *normalization,
+        })
+    }
+}
+
+impl Stats {
+    /// Writes the parsed stats into a binary cache at `path`, tagged with
+    /// the size, modification time and hash of the `source` they were parsed from
+    pub fn write_cache(&self, path: &Path, source: &Path) -> Result<(), Error> {
+        let header = CacheHeader::new(source, &fs::read(source)?, &NameNormalization::default())?;
+
+        self.write_cache_with_header(path, &header, &[])
+    }
+
+    /// Reads stats from the cache at `path`.
+    ///
+    /// Returns `None` when the cache is missing, unreadable, made by a
+    /// different version or made from a different `source`, so a cache is
+    /// never trusted over the source file.
+    pub fn read_cache(path: &Path, source: &Path) -> Option<Self> {
+        let header = CacheHeader::new(
+            source,
+            &fs::read(source).ok()?,
+            &NameNormalization::default(),
+        )
+        .ok()?;
+
+        Some(Self::read_cache_with_header(path, &header)?.0)
+    }
+
+    /// Parses the gzipped nbt at `source`, reusing the cache in `cache_dir`
+    /// when the source is unchanged and regenerating it otherwise.
+    ///
+    /// The cache is only a shortcut, failing to write it doesn't fail the
+    /// parse. See [`Stats::from_path_cached_with_warning`] to find out why
+    /// the next call has to parse again.
+    pub fn from_path_cached(source: &Path, cache_dir: &Path) -> Result<Self, Error> {
+        Ok(Self::from_path_cached_with_warning(source, cache_dir)?.0)
+    }
+
+    /// Like [`Stats::from_path_cached`], also returning the error that kept
+    /// the cache from being written, if any
+    pub fn from_path_cached_with_warning(
+        source: &Path,
+        cache_dir: &Path,
+    ) -> Result<(Self, Option<Error>), Error> {
+        let (stats, _, warning) =
+            Self::from_path_cached_normalized(source, cache_dir, &NameNormalization::default())?;
+
+        Ok((stats, warning))
+    }
+
+    /// Like [`Stats::from_path_cached_with_warning`], normalizing the player
+    /// names while parsing like [`Stats::from_gzip_reader_normalized`] and
+    /// also returning the names that merged. The cache holds the normalized
+    /// names, so it is only reused with the same `normalization`.
+    pub fn from_path_cached_normalized(
+        source: &Path,
+        cache_dir: &Path,
+        normalization: &NameNormalization,
+    ) -> Result<(Self, Vec<NameMerge>, Option<Error>), Error> {
+        let source_bytes = fs::read(source)?;
+        let header = CacheHeader::new(source, &source_bytes, normalization)?;
+        let path = cache_path(source, cache_dir)?;
+
+        if let Some((stats, merges)) = Self::read_cache_with_header(&path, &header) {
+            return Ok((stats, merges, None));
+        }
+
+        let (stats, merges) =
+            Self::from_gzip_reader_normalized(source_bytes.as_slice(), normalization)?;
+        let written = fs::create_dir_all(cache_dir)
+            .map_err(Error::from)
+            .and_then(|_| stats.write_cache_with_header(&path, &header, &merges));
+
+        Ok((stats, merges, written.err()))
+    }
+
+    fn write_cache_with_header(
+        &self,
+        path: &Path,
+        header: &CacheHeader,
+        merges: &[NameMerge],
+    ) -> Result<(), Error> {
+        let options = bincode::DefaultOptions::new();
+        let payload = options.serialize(&(self, &self.player_order, merges))?;
+        let payload_sha256: [u8; 32] = Sha256::digest(&payload).into();
+
+        let mut w = BufWriter::new(AtomicFile::create(path)?);
+
+        options.serialize_into(&mut w, header)?;
+        options.serialize_into(&mut w, &payload_sha256)?;
+        w.write_all(&payload)?;
+        w.into_inner().map_err(|e| e.into_error())?.commit()?;
+
+        Ok(())
+    }
+
+    fn read_cache_with_header(path: &Path, header: &CacheHeader) -> Option<(Self, Vec<NameMerge>)> {
+        let file = File::open(path).ok()?;
+        let len = file.metadata().ok()?.len();
+
+        //the limit keeps a corrupted length prefix from allocating more than the file holds
+        let options = bincode::DefaultOptions::new().with_limit(len);
+        let mut r = BufReader::new(file);
+
+        let cached_header: CacheHeader = options.deserialize_from(&mut r).ok()?;
+
+        if cached_header != *header {
+            return None;
+        }
+
+        //the payload is hashed so a damaged cache is never mistaken for data
+        let payload_sha256: [u8; 32] = options.deserialize_from(&mut r).ok()?;
+        let mut payload = Vec::new();
+        r.read_to_end(&mut payload).ok()?;
+
+        if <[u8; 32]>::from(Sha256::digest(&payload)) != payload_sha256 {
+            return None;
+        }
+
+        let (mut stats, player_order, merges): (Self, _, _) = options.deserialize(&payload).ok()?;
+        stats.player_order = player_order;
+        stats.intern_player_names();
+
+        Some((stats, merges))
+    }
+}
+
+/// Each source file gets its own cache, named after a hash of its absolute path
+fn cache_path(source: &Path, cache_dir: &Path) -> Result<PathBuf, Error> {
+    let source = fs::canonicalize(source)?;
+    let hash = Sha256::digest(source.as_os_str().as_encoded_bytes());
+
+    Ok(cache_dir.join(format!("{:x}.cache", hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn csv(stats: &Stats) -> String {
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+        String::from_utf8(csv).unwrap()
+    }
+
+    /// A scoreboard file in a fresh directory of the test
+    fn source(test: &str) -> (PathBuf, PathBuf) {
+        let dir = fixture::temp_dir(test);
+        let source = dir.join("scoreboard.dat");
+        fs::write(
+            &source,
+            fixture::scoreboard(
+                &[("deaths", "deathCount")],
+                [("ville", "deaths", 3), ("anna", "deaths", 5)].into_iter(),
+            ),
+        )
+        .unwrap();
+
+        (dir, source)
+    }
+
+    #[test]
+    fn missing_cache_dir_is_created() {
+        let (dir, source) = source("cache_dir_created");
+        let cache_dir = dir.join("nested").join("cache");
+
+        let (_, warning) = Stats::from_path_cached_with_warning(&source, &cache_dir).unwrap();
+
+        assert!(warning.is_none(), "{:?}", warning);
+        assert!(cache_path(&source, &cache_dir).unwrap().exists());
+    }
+
+    #[test]
+    fn corrupt_cache_falls_back_to_the_source() {
+        let (dir, source) = source("corrupt_cache");
+        let cache_dir = dir.join("cache");
+        let expected = csv(&Stats::from_path_cached(&source, &cache_dir).unwrap());
+
+        let path = cache_path(&source, &cache_dir).unwrap();
+        let mut cache = fs::read(&path).unwrap();
+        let last = cache.len() - 1;
+        cache[last] ^= 0xff;
+        fs::write(&path, &cache).unwrap();
+
+        assert!(Stats::read_cache(&path, &source).is_none());
+        assert_eq!(
+            csv(&Stats::from_path_cached(&source, &cache_dir).unwrap()),
+            expected
+        );
+
+        //and the damaged cache got replaced
+        assert!(Stats::read_cache(&path, &source).is_some());
+    }
+
+    #[test]
+    fn truncated_cache_falls_back_to_the_source() {
+        let (dir, source) = source("truncated_cache");
+        let cache_dir = dir.join("cache");
+        let expected = csv(&Stats::from_path_cached(&source, &cache_dir).unwrap());
+
+        let path = cache_path(&source, &cache_dir).unwrap();
+        let cache = fs::read(&path).unwrap();
+        fs::write(&path, &cache[..cache.len() / 2]).unwrap();
+
+        assert_eq!(
+            csv(&Stats::from_path_cached(&source, &cache_dir).unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn unwritable_cache_is_only_a_warning() {
+        let (dir, source) = source("unwritable_cache");
+        //a file where the directory should be
+        let cache_dir = dir.join("cache");
+        fs::write(&cache_dir, b"").unwrap();
+
+        let (stats, warning) = Stats::from_path_cached_with_warning(&source, &cache_dir).unwrap();
+
+        assert!(warning.is_some());
+        assert_eq!(csv(&stats), "Players,deaths\nanna,5\nville,3\n");
+    }
+
+    #[test]
+    fn cache_is_only_reused_with_the_same_normalization() {
+        let dir = fixture::temp_dir("cache_normalization");
+        let source = dir.join("scoreboard.dat");
+        fs::write(
+            &source,
+            fixture::scoreboard(
+                &[("deaths", "deathCount")],
+                [("ville", "deaths", 3), ("vi\u{200B}lle", "deaths", 5)].into_iter(),
+            ),
+        )
+        .unwrap();
+        let cache_dir = dir.join("cache");
+        let normalization = NameNormalization {
+            form: None,
+            strip_invisible: true,
+        };
+
+        let (_, merges, _) =
+            Stats::from_path_cached_normalized(&source, &cache_dir, &normalization).unwrap();
+        let (cached, cached_merges, _) =
+            Stats::from_path_cached_normalized(&source, &cache_dir, &normalization).unwrap();
+
+        assert_eq!(merges.len(), 1);
+        assert_eq!(cached_merges, merges);
+        assert_eq!(cached.get_player_list(), ["ville"]);
+        assert_eq!(
+            Stats::from_path_cached(&source, &cache_dir)
+                .unwrap()
+                .player_count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::Write;
+
+use super::{SqlDialect, Stats};
+use crate::error::Error;
+
+/// Columns of the audit table, `old_score` is the previous score of the
+/// player in the objective for inserts
+const CHANGE_COLUMNS: &str = "change_type, old_score, new_score, player_name, objective_name";
+
+/// The latest score of the inserted row's player and objective before it
+const PREVIOUS_SCORE: &str = "(SELECT score FROM stats \
+WHERE player_name = NEW.player_name AND objective_name = NEW.objective_name AND time < NEW.time \
+ORDER BY time DESC LIMIT 1)";
+
+impl Stats {
+    /// Writes the statements creating a `stats_changes` audit table and
+    /// triggers on the `stats` table that record every inserted, updated and
+    /// deleted score in it, for change data capture pipelines.
+    ///
+    /// Inserts are recorded with the previous score of the player in the
+    /// objective as `old_score`, so a new snapshot reads as a change. The
+    /// statements are separated by semicolons and can be run again.
+    pub fn write_sql_cdc_triggers(
+        &self,
+        mut w: impl Write,
+        dialect: SqlDialect,
+    ) -> Result<(), Error> {
+        match dialect {
+            SqlDialect::MySql => {
+                write!(
+                    w,
+                    "CREATE TABLE IF NOT EXISTS stats_changes (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    changed_at TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+    change_type VARCHAR(6) NOT NULL,
+    old_score BIGINT,
+    new_score BIGINT,
+    player_name VARCHAR(255),
+    objective_name VARCHAR(255)
+);
+"
+                )?;
+
+                //single statement triggers need no DELIMITER
+                let triggers = [
+                    (
+                        "INSERT",
+                        format!(
+                            "{}, NEW.score, NEW.player_name, NEW.objective_name",
+                            PREVIOUS_SCORE
+                        ),
+                    ),
+                    (
+                        "UPDATE",
+                        "OLD.score, NEW.score, NEW.player_name, NEW.objective_name".to_string(),
+                    ),
+                    (
+                        "DELETE",
+                        "OLD.score, NULL, OLD.player_name, OLD.objective_name".to_string(),
+                    ),
+                ];
+
+                for (event, values) in triggers {
+                    write!(
+                        w,
+                        "
+CREATE TRIGGER IF NOT EXISTS stats_cdc_{} AFTER {} ON stats FOR EACH ROW
+    INSERT INTO stats_changes ({}) VALUES ('{}', {});
+",
+                        event.to_lowercase(),
+                        event,
+                        CHANGE_COLUMNS,
+                        event,
+                        values
+                    )?;
+                }
+            }
+            SqlDialect::Postgres => write!(
+                w,
+                "CREATE TABLE IF NOT EXISTS stats_changes (
+    id BIGSERIAL PRIMARY KEY,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    change_type VARCHAR(6) NOT NULL,
+    old_score BIGINT,
+    new_score BIGINT,
+    player_name VARCHAR(255),
+    objective_name VARCHAR(255)
+);
+
+CREATE OR REPLACE FUNCTION stats_cdc() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO stats_changes ({columns})
+        VALUES ('INSERT', {previous}, NEW.score, NEW.player_name, NEW.objective_name);
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO stats_changes ({columns})
+        VALUES ('UPDATE', OLD.score, NEW.score, NEW.player_name, NEW.objective_name);
+    ELSE
+        INSERT INTO stats_changes ({columns})
+        VALUES ('DELETE', OLD.score, NULL, OLD.player_name, OLD.objective_name);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER stats_cdc AFTER INSERT OR UPDATE OR DELETE ON stats
+    FOR EACH ROW EXECUTE FUNCTION stats_cdc();
+",
+                columns = CHANGE_COLUMNS,
+                previous = PREVIOUS_SCORE
+            )?,
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
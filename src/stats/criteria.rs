@@ -0,0 +1,255 @@
+use std::{convert::Infallible, fmt, str::FromStr};
+
+/// English labels for the `minecraft.custom` statistics, keyed by the
+/// statistic name without its namespace
+const CUSTOM_LABELS: &[(&str, &str)] = &[
+    ("animals_bred", "Animals Bred"),
+    ("aviate_one_cm", "Distance by Elytra"),
+    ("bell_ring", "Bells Rung"),
+    ("boat_one_cm", "Distance by Boat"),
+    ("clean_armor", "Armor Pieces Cleaned"),
+    ("clean_banner", "Banners Cleaned"),
+    ("clean_shulker_box", "Shulker Boxes Cleaned"),
+    ("climb_one_cm", "Distance Climbed"),
+    ("crouch_one_cm", "Distance Crouched"),
+    ("damage_absorbed", "Damage Absorbed"),
+    ("damage_blocked_by_shield", "Damage Blocked by Shield"),
+    ("damage_dealt", "Damage Dealt"),
+    ("damage_dealt_absorbed", "Damage Dealt (Absorbed)"),
+    ("damage_dealt_resisted", "Damage Dealt (Resisted)"),
+    ("damage_resisted", "Damage Resisted"),
+    ("damage_taken", "Damage Taken"),
+    ("deaths", "Number of Deaths"),
+    ("drop", "Items Dropped"),
+    ("eat_cake_slice", "Cake Slices Eaten"),
+    ("enchant_item", "Items Enchanted"),
+    ("fall_one_cm", "Distance Fallen"),
+    ("fill_cauldron", "Cauldrons Filled"),
+    ("fish_caught", "Fish Caught"),
+    ("fly_one_cm", "Distance Flown"),
+    ("happy_ghast_one_cm", "Distance by Happy Ghast"),
+    ("horse_one_cm", "Distance by Horse"),
+    ("inspect_dispenser", "Dispensers Searched"),
+    ("inspect_dropper", "Droppers Searched"),
+    ("inspect_hopper", "Hoppers Searched"),
+    ("interact_with_anvil", "Interactions with Anvil"),
+    ("interact_with_beacon", "Interactions with Beacon"),
+    (
+        "interact_with_blast_furnace",
+        "Interactions with Blast Furnace",
+    ),
+    (
+        "interact_with_brewingstand",
+        "Interactions with Brewing Stand",
+    ),
+    ("interact_with_campfire", "Interactions with Campfire"),
+    (
+        "interact_with_cartography_table",
+        "Interactions with Cartography Table",
+    ),
+    (
+        "interact_with_crafting_table",
+        "Interactions with Crafting Table",
+    ),
+    ("interact_with_furnace", "Interactions with Furnace"),
+    ("interact_with_grindstone", "Interactions with Grindstone"),
+    ("interact_with_lectern", "Interactions with Lectern"),
+    ("interact_with_loom", "Interactions with Loom"),
+    (
+        "interact_with_smithing_table",
+        "Interactions with Smithing Table",
+    ),
+    ("interact_with_smoker", "Interactions with Smoker"),
+    ("interact_with_stonecutter", "Interactions with Stonecutter"),
+    ("jump", "Times Jumped"),
+    ("leave_game", "Games Quit"),
+    ("minecart_one_cm", "Distance by Minecart"),
+    ("mob_kills", "Mob Kills"),
+    ("open_barrel", "Barrels Opened"),
+    ("open_chest", "Chests Opened"),
+    ("open_enderchest", "Ender Chests Opened"),
+    ("open_shulker_box", "Shulker Boxes Opened"),
+    ("pig_one_cm", "Distance by Pig"),
+    ("play_noteblock", "Note Blocks Played"),
+    ("play_record", "Music Discs Played"),
+    ("play_time", "Play Time"),
+    ("player_kills", "Player Kills"),
+    ("pot_flower", "Plants Potted"),
+    ("raid_trigger", "Raids Triggered"),
+    ("raid_win", "Raids Won"),
+    ("sleep_in_bed", "Times Slept in a Bed"),
+    ("sneak_time", "Sneak Time"),
+    ("sprint_one_cm", "Distance Sprinted"),
+    ("strider_one_cm", "Distance by Strider"),
+    ("swim_one_cm", "Distance Swum"),
+    ("talked_to_villager", "Talked to Villagers"),
+    ("target_hit", "Targets Hit"),
+    ("time_since_death", "Time Since Last Death"),
+    ("time_since_rest", "Time Since Last Rest"),
+    ("total_world_time", "Time with World Open"),
+    ("traded_with_villager", "Traded with Villagers"),
+    ("trigger_trapped_chest", "Trapped Chests Triggered"),
+    ("tune_noteblock", "Note Blocks Tuned"),
+    ("use_cauldron", "Water Taken from Cauldron"),
+    ("walk_on_water_one_cm", "Distance Walked on Water"),
+    ("walk_one_cm", "Distance Walked"),
+    ("walk_under_water_one_cm", "Distance Walked under Water"),
+];
+
+/// English labels for the single word criteria
+const SIMPLE_LABELS: &[(&str, &str)] = &[
+    ("air", "Air"),
+    ("armor", "Armor"),
+    ("deathCount", "Deaths"),
+    ("food", "Food"),
+    ("health", "Health"),
+    ("level", "Level"),
+    ("playerKillCount", "Player Kills"),
+    ("totalKillCount", "Total Kills"),
+    ("xp", "Experience"),
+];
+
+const MINECRAFT_NAMESPACE: &str = "minecraft.";
+const CUSTOM_CATEGORY: &str = "minecraft.custom";
+
+/// The criteria of an objective
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Criteria {
+    /// A statistic like `minecraft.custom:minecraft.jump`, split at the
+    /// colon into the category and the statistic name
+    Statistic { category: String, name: String },
+    /// Any criteria without a category like `dummy` or `deathCount`
+    Simple(String),
+}
+
+/// Selects every statistic criteria in [`Criteria::matches`]
+const STATISTIC_SELECTOR: &str = "statistic";
+
+impl Criteria {
+    /// What the criteria is grouped by: the category of statistics like
+    /// `minecraft.mined`, otherwise the whole criteria like `dummy`
+    pub fn category(&self) -> &str {
+        match self {
+            Criteria::Statistic { category, .. } => category,
+            Criteria::Simple(name) => name,
+        }
+    }
+
+    /// Whether `selector` picks this criteria: `statistic` for every
+    /// statistic, a [`Criteria::category`] or the whole criteria string
+    pub fn matches(&self, selector: &str) -> bool {
+        match self {
+            Criteria::Statistic { category, name } => {
+                selector == STATISTIC_SELECTOR
+                    || selector == category
+                    || selector
+                        .strip_prefix(category.as_str())
+                        .and_then(|x| x.strip_prefix(':'))
+                        == Some(name)
+            }
+            Criteria::Simple(name) => selector == name,
+        }
+    }
+
+    /// An English label for well-known vanilla criteria
+    pub fn label(&self) -> Option<&'static str> {
+        let (table, key) = match self {
+            Criteria::Statistic { category, name } if category == CUSTOM_CATEGORY => {
+                (CUSTOM_LABELS, name.strip_prefix(MINECRAFT_NAMESPACE)?)
+            }
+            Criteria::Statistic { .. } => return None,
+            Criteria::Simple(name) => (SIMPLE_LABELS, name.as_str()),
+        };
+
+        table
+            .iter()
+            .find(|(criteria, _)| *criteria == key)
+            .map(|(_, label)| *label)
+    }
+}
+
+impl From<&str> for Criteria {
+    fn from(value: &str) -> Self {
+        match value.split_once(':') {
+            Some((category, name)) => Criteria::Statistic {
+                category: category.to_string(),
+                name: name.to_string(),
+            },
+            None => Criteria::Simple(value.to_string()),
+        }
+    }
+}
+
+impl FromStr for Criteria {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
+impl fmt::Display for Criteria {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Criteria::Statistic { category, name } => write!(f, "{}:{}", category, name),
+            Criteria::Simple(name) => f.write_str(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every criteria string with a label, as it appears in a scoreboard
+    fn labelled() -> impl Iterator<Item = (String, &'static str)> {
+        CUSTOM_LABELS
+            .iter()
+            .map(|(name, label)| {
+                (
+                    format!("{}:{}{}", CUSTOM_CATEGORY, MINECRAFT_NAMESPACE, name),
+                    *label,
+                )
+            })
+            .chain(
+                SIMPLE_LABELS
+                    .iter()
+                    .map(|(name, label)| (name.to_string(), *label)),
+            )
+    }
+
+    #[test]
+    fn every_label_round_trips() {
+        for (criteria, label) in labelled() {
+            let parsed: Criteria = criteria.parse().unwrap();
+
+            assert_eq!(parsed.label(), Some(label), "{}", criteria);
+            assert_eq!(parsed.to_string(), criteria);
+        }
+    }
+
+    #[test]
+    fn labels_are_unique() {
+        for table in [CUSTOM_LABELS, SIMPLE_LABELS] {
+            for (i, (name, _)) in table.iter().enumerate() {
+                assert!(
+                    table[i + 1..].iter().all(|(other, _)| other != name),
+                    "{} is in the table twice",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_criteria_have_no_label() {
+        for criteria in [
+            "dummy",
+            "minecraft.custom:minecraft.not_a_stat",
+            "minecraft.custom:jump",
+            "minecraft.mined:minecraft.stone",
+        ] {
+            assert_eq!(Criteria::from(criteria).label(), None, "{}", criteria);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fmt::Write as _, io, io::Write};
+
+use super::{SqlDialect, Stats};
+use crate::error::Error;
+
+/// How many of the top players of every region are compared per objective
+const TOP_PLAYERS: usize = 10;
+
+/// The latest score of every player in every objective of every region,
+/// numbered from the highest score with ties in player name order
+const RANKED_SELECT: &str = "SELECT
+        s.region,
+        s.objective_name,
+        s.player_name,
+        s.score,
+        ROW_NUMBER() OVER (
+            PARTITION BY s.region, s.objective_name
+            ORDER BY s.score DESC, s.player_name
+        ) AS score_rank
+    FROM stats s
+    JOIN (
+        SELECT region, player_name, objective_name, MAX(time) AS time
+        FROM stats
+        GROUP BY region, player_name, objective_name
+    ) latest ON latest.region = s.region
+        AND latest.player_name = s.player_name
+        AND latest.objective_name = s.objective_name
+        AND latest.time = s.time";
+
+impl Stats {
+    /// Writes the statements creating a `cross_server_leaderboard` view
+    /// with a row per objective and rank, and a `{region}_player` and
+    /// `{region}_score` column per region holding that region's player at
+    /// that rank. The top 10 players of every region are compared, using
+    /// the latest score of every player in the `stats` table.
+    ///
+    /// PostgreSQL joins the regions with `FULL OUTER JOIN`. MySQL has no
+    /// full joins, so it joins every region to the union of their ranks.
+    /// The view is dropped first since its columns depend on the regions.
+    pub fn write_sql_cross_server_view(
+        &self,
+        mut w: impl Write,
+        regions: &[&str],
+        dialect: SqlDialect,
+    ) -> Result<(), Error> {
+        if regions.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a cross server view needs at least one region",
+            )
+            .into());
+        }
+
+        if let Some(region) = regions
+            .iter()
+            .enumerate()
+            .find_map(|(i, region)| regions[..i].contains(region).then_some(region))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the region {} is compared more than once", region),
+            )
+            .into());
+        }
+
+        let literals: Vec<String> = regions
+            .iter()
+            .map(|region| string_literal(region, dialect))
+            .collect();
+
+        let mut sql = format!(
+            "DROP VIEW IF EXISTS cross_server_leaderboard;
+
+CREATE VIEW cross_server_leaderboard AS
+WITH ranked AS (
+    {}
+    WHERE s.region IN ({})
+),
+top_players AS (
+    SELECT * FROM ranked WHERE score_rank <= {}
+)
+SELECT
+",
+            RANKED_SELECT,
+            literals.join(", "),
+            TOP_PLAYERS
+        );
+
+        //the objective and rank of whichever region has the row
+        let keys = match dialect {
+            SqlDialect::MySql => ["k.objective_name".to_string(), "k.score_rank".to_string()],
+            SqlDialect::Postgres => {
+                ["objective_name", "score_rank"].map(|column| match regions.len() {
+                    1 => format!("r0.{}", column),
+                    n => format!(
+                        "COALESCE({})",
+                        (0..n)
+                            .map(|i| format!("r{}.{}", i, column))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                })
+            }
+        };
+
+        writeln!(sql, "    {} AS objective_name,", keys[0]).unwrap();
+        write!(sql, "    {} AS score_rank", keys[1]).unwrap();
+        for (i, region) in regions.iter().enumerate() {
+            write!(
+                sql,
+                ",\n    r{i}.player_name AS {},\n    r{i}.score AS {}",
+                identifier(&format!("{}_player", region), dialect),
+                identifier(&format!("{}_score", region), dialect),
+            )
+            .unwrap();
+        }
+
+        match dialect {
+            SqlDialect::MySql => {
+                let union = literals
+                    .iter()
+                    .map(|region| {
+                        format!(
+                            "    SELECT objective_name, score_rank FROM top_players WHERE region = {}",
+                            region
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n    UNION\n");
+                write!(sql, "\nFROM (\n{}\n) k", union).unwrap();
+
+                for (i, region) in literals.iter().enumerate() {
+                    write!(
+                        sql,
+                        "\nLEFT JOIN top_players r{i} ON r{i}.region = {}
+    AND r{i}.objective_name = k.objective_name
+    AND r{i}.score_rank = k.score_rank",
+                        region
+                    )
+                    .unwrap();
+                }
+            }
+            SqlDialect::Postgres => {
+                for (i, region) in literals.iter().enumerate() {
+                    let region_rows = format!(
+                        "(SELECT * FROM top_players WHERE region = {}) r{}",
+                        region, i
+                    );
+
+                    match i {
+                        0 => write!(sql, "\nFROM {}", region_rows).unwrap(),
+                        1 => write!(
+                            sql,
+                            "\nFULL OUTER JOIN {region_rows}
+    ON r1.objective_name = r0.objective_name
+    AND r1.score_rank = r0.score_rank"
+                        )
+                        .unwrap(),
+                        _ => {
+                            let previous = |column: &str| {
+                                (0..i)
+                                    .map(|j| format!("r{}.{}", j, column))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            };
+
+                            write!(
+                                sql,
+                                "\nFULL OUTER JOIN {region_rows}
+    ON r{i}.objective_name = COALESCE({})
+    AND r{i}.score_rank = COALESCE({})",
+                                previous("objective_name"),
+                                previous("score_rank"),
+                            )
+                            .unwrap()
+                        }
+                    }
+                }
+            }
+        }
+
+        sql.push_str(";\n");
+
+        w.write_all(sql.as_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+fn string_literal(value: &str, dialect: SqlDialect) -> String {
+    let escaped = value.replace('\'', "''");
+
+    match dialect {
+        SqlDialect::MySql => format!("'{}'", escaped.replace('\\', "\\\\")),
+        SqlDialect::Postgres => format!("'{}'", escaped),
+    }
+}
+
+fn identifier(name: &str, dialect: SqlDialect) -> String {
+    match dialect {
+        SqlDialect::MySql => format!("`{}`", name.replace('`', "``")),
+        SqlDialect::Postgres => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
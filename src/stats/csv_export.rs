@@ -0,0 +1,1550 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    fmt::{self, Write as _},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use base64::prelude::*;
+use csv::ByteRecord;
+use nbt::Map;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{
+    sanitize::strip_formatting,
+    uuids::{read_usercache, UuidMap},
+    PlayerOrder, Stats,
+};
+use crate::{atomic_file::AtomicFile, error::Error};
+
+/// Written to the csv for players that have no score in an objective
+const MISSING_SCORE: &str = "0";
+
+/// Header of the csv with a row per score
+pub(super) const LONG_CSV_HEADER: [&str; 3] = ["player", "objective", "score"];
+
+/// Lets Excel on Windows know the csv is UTF-8
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Group label for players missing from the group map or with an empty label
+const UNGROUPED: &str = "Ungrouped";
+
+/// Header of the [`CsvOptions::row_hash`] column
+const ROW_HASH: &str = "_sha256";
+
+/// Game ticks in a second, read by [`ObjectiveFormatter::TicksToTime`]
+const TICKS_PER_SECOND: u64 = 20;
+
+/// A column after the objectives as `(header, value per player)`
+pub type ComputedColumn = (String, Map<String, f64>);
+
+/// Options controlling the layout of the written csv
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Maps player names to group labels. When set a leading `Group` column
+    /// is written and rows are sorted by group, then by player name.
+    pub player_groups: Option<HashMap<String, String>>,
+    /// Formatters for the scores of specific objectives keyed by objective
+    /// name, other objectives are written as plain integers
+    pub formatters: HashMap<String, ObjectiveFormatter>,
+    /// Write a leading `UUID` column identifying each row, requires
+    /// `uuid_map_path`. Players missing from the map get an empty UUID and
+    /// are identified by their name only.
+    pub use_uuid_as_key: bool,
+    /// The usercache.json to read player UUIDs from
+    pub uuid_map_path: Option<PathBuf>,
+    /// Player UUIDs looked up some other way, like with the Mojang API.
+    /// The usercache wins for players in both.
+    pub uuids: Option<UuidMap>,
+    /// Replace every score with a bar of up to 10 block characters scaled
+    /// to the highest score of its objective. The numbers are lost and
+    /// `formatters` are ignored.
+    pub unicode_bars: bool,
+    /// Appends `(outlier)` to scores more than 1.5 times the interquartile
+    /// range below the first or above the third quartile of their objective
+    pub annotate_outliers: bool,
+    /// Score bins of objectives as `(upper bound, label)` pairs sorted by
+    /// the bound, keyed by objective name. A `{objective}_class` column
+    /// after the scores of the objective holds the label of the first bin
+    /// whose bound is above the score.
+    pub classify: HashMap<String, Vec<(i64, String)>>,
+    /// Add an `{objective}_pct` column right after the scores of every
+    /// objective with the percentile rank of the score from 0 to 100, the
+    /// number of lower scores divided by the number of other players
+    pub percentile_columns: bool,
+    /// Add an `{objective}_rank` column right after the scores of every
+    /// objective with the dense rank of the score, where the highest score
+    /// is 1, tied scores share a rank and no rank is skipped after a tie
+    pub dense_rank_columns: bool,
+    /// Write missing scores as empty cells instead of `0`, so scores
+    /// dropped by [`ExportOptions::min_score`](super::ExportOptions::min_score)
+    /// can be told apart from zeros
+    pub empty_missing_scores: bool,
+    /// Write player names and display names as they are, without removing
+    /// `§` formatting codes and non-printable characters with
+    /// [`strip_formatting`](super::strip_formatting)
+    pub preserve_raw_names: bool,
+    /// Appends `*` to the scores it returns `true` for, for example to
+    /// flag suspicious scores
+    pub flag_condition: Option<FlagCondition>,
+    /// Appends `(↑)`, `(↓)` or `(=)` to every score comparing it with the
+    /// same score in this baseline, where missing scores count as 0
+    pub change_arrows: Option<Arc<Stats>>,
+    /// Extra columns after the objectives as `(header, value per player)`,
+    /// usually from [`Stats::computed_column`]. Players without a value get
+    /// the missing score placeholder.
+    pub computed_columns: Vec<ComputedColumn>,
+    /// Extra columns after the objectives combining the scores of the
+    /// listed objectives, keyed by header and written sorted by it. Players
+    /// without a score in any of the objectives get the missing score
+    /// placeholder.
+    pub combine_objectives: HashMap<String, (Vec<String>, CombineMode)>,
+    /// Leave out the columns of the objectives in `combine_objectives`
+    pub hide_sources: bool,
+    /// The order of the objective columns
+    pub column_order: ColumnOrder,
+    /// Write the `Players` column base64 encoded, read back with
+    /// [`Stats::decode_player_names_csv`]
+    pub base64_player_names: bool,
+    /// Start the csv with a UTF-8 byte order mark
+    pub bom: bool,
+    /// The order of the rows, players in the same group keep this order
+    pub player_order: PlayerOrder,
+    /// Which name of each objective is used as its column header
+    pub header_names: HeaderNames,
+    /// Exact headers replacing the ones from `header_names`
+    pub header_map: HeaderMap,
+    /// Writes an extra header row above the objectives grouping them by the
+    /// part of their name before the first `.`, like `pvp` for `pvp.kills`
+    ///
+    /// The group name is only written above the first column of each group
+    /// so spreadsheets can merge the cells
+    pub group_objectives_by_prefix: bool,
+    /// What to do when two columns would get the same header
+    pub on_header_collision: HeaderCollision,
+    /// Decimal places of computed columns and averaged combined columns
+    pub float_precision: u8,
+    /// Head the first column `Team`, for stats from
+    /// [`Stats::aggregate_by_team`]
+    pub team_rows: bool,
+    /// Add a `Members` column after the first with the number of members
+    /// of the [`Stats::teams`] named like the row, usually with `team_rows`
+    pub team_size_column: bool,
+    /// Add a last `_sha256` column with the hex SHA-256 of the player name
+    /// and the raw scores of the row, so rows repeated across exports can
+    /// be found without a database. Formatting options don't change it.
+    pub row_hash: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            player_groups: None,
+            formatters: HashMap::new(),
+            use_uuid_as_key: false,
+            uuid_map_path: None,
+            uuids: None,
+            unicode_bars: false,
+            annotate_outliers: false,
+            classify: HashMap::new(),
+            percentile_columns: false,
+            dense_rank_columns: false,
+            empty_missing_scores: false,
+            preserve_raw_names: false,
+            flag_condition: None,
+            change_arrows: None,
+            computed_columns: Vec::new(),
+            combine_objectives: HashMap::new(),
+            hide_sources: false,
+            column_order: ColumnOrder::default(),
+            base64_player_names: false,
+            bom: false,
+            player_order: PlayerOrder::default(),
+            header_names: HeaderNames::default(),
+            header_map: HeaderMap::default(),
+            group_objectives_by_prefix: false,
+            on_header_collision: HeaderCollision::default(),
+            float_precision: 4,
+            row_hash: false,
+            team_rows: false,
+            team_size_column: false,
+        }
+    }
+}
+
+/// Ways to deal with columns that would get the same header, usually two
+/// objectives with the same display name
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderCollision {
+    /// Append the objective name in parentheses, like `Kills (mobKills)`
+    #[default]
+    Disambiguate,
+    /// Fail with [`Error::DuplicateHeader`]
+    Error,
+}
+
+/// The objective names usable as column headers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderNames {
+    /// The display name
+    #[default]
+    Display,
+    /// The objective name used in commands
+    Internal,
+    /// The label of well-known vanilla criteria, then the display name
+    /// and then the objective name
+    Friendly,
+}
+
+/// Headers written in place of the usual ones, read from a toml file like
+///
+/// ```toml
+/// players = "Pelaaja"
+///
+/// [objectives]
+/// kills = "Tapot"
+/// deaths = "Kuolemat"
+/// ```
+///
+/// The headers are written as they are, without removing formatting
+/// codes. Columns that aren't mapped keep their usual header.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderMap {
+    /// The header of the first column, `Players` or `Team`
+    pub players: Option<String>,
+    /// Headers keyed by objective name
+    #[serde(default)]
+    pub objectives: HashMap<String, String>,
+}
+
+impl HeaderMap {
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// The mapped objectives that aren't in `stats`, sorted by name
+    pub fn unknown_objectives<'a>(&'a self, stats: &Stats) -> Vec<&'a str> {
+        let mut unknown: Vec<&str> = self
+            .objectives
+            .keys()
+            .filter(|x| !stats.objectives.contains_key(*x))
+            .map(String::as_str)
+            .collect();
+        unknown.sort_unstable();
+
+        unknown
+    }
+}
+
+/// How the objective columns are ordered, ties are broken by objective
+/// name
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnOrder {
+    /// By objective name
+    #[default]
+    Name,
+    /// By display name
+    Display,
+    /// Grouped by [`Criteria::category`](super::Criteria::category), like
+    /// every `minecraft.mined` statistic together
+    Criteria,
+    /// By the sum of every score in the objective, highest first
+    TotalDesc,
+}
+
+/// How [`CsvOptions::combine_objectives`] combines scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombineMode {
+    Sum,
+    /// The mean of the scores the player has, missing scores aren't
+    /// counted as 0
+    Average,
+}
+
+/// A column of the csv written with some [`CsvOptions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvColumn {
+    /// The header of the column
+    pub name: String,
+    pub column_type: ColumnType,
+    /// The objective the scores come from, `None` for the player columns and
+    /// computed columns
+    pub objective: Option<String>,
+    /// The criteria of `objective`
+    pub criteria: Option<String>,
+}
+
+/// What the values of a [`CsvColumn`] are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Integer,
+    Number,
+}
+
+/// Called with the player, the objective name and the score of every
+/// score written
+#[derive(Clone)]
+pub struct FlagCondition(Arc<ConditionFn>);
+
+type ConditionFn = dyn Fn(&str, &str, i64) -> bool + Send + Sync;
+
+impl FlagCondition {
+    pub fn new(condition: impl Fn(&str, &str, i64) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(condition))
+    }
+}
+
+impl fmt::Debug for FlagCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FlagCondition")
+    }
+}
+
+/// Converts the scores of an objective into some other unit
+#[derive(Debug, Clone)]
+pub enum ObjectiveFormatter {
+    /// Multiplies every score, for example ticks into seconds with
+    /// `scale: 1.0 / 20.0, suffix: "s", decimal_places: 1`
+    Scale {
+        scale: f64,
+        /// Appended after the scaled score
+        suffix: String,
+        decimal_places: u8,
+    },
+    /// Reads the score as game ticks, 20 a second, and writes it as
+    /// `HH:MM:SS` so 72000 becomes `01:00:00`. Partial seconds are dropped.
+    TicksToTime,
+}
+
+impl ObjectiveFormatter {
+    /// Whether the formatted scores are still plain numbers
+    fn is_numeric(&self) -> bool {
+        match self {
+            ObjectiveFormatter::Scale { suffix, .. } => suffix.is_empty(),
+            ObjectiveFormatter::TicksToTime => false,
+        }
+    }
+
+    fn format(&self, score: i64, buffer: &mut String) {
+        buffer.clear();
+
+        match self {
+            ObjectiveFormatter::Scale {
+                scale,
+                suffix,
+                decimal_places,
+            } => write!(
+                buffer,
+                "{:.*}{}",
+                *decimal_places as usize,
+                score as f64 * scale,
+                suffix
+            )
+            .unwrap(),
+            ObjectiveFormatter::TicksToTime => {
+                if score < 0 {
+                    buffer.push('-');
+                }
+
+                let seconds = score.unsigned_abs() / TICKS_PER_SECOND;
+                write!(
+                    buffer,
+                    "{:02}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+impl CsvOptions {
+    /// The combined columns sorted by header
+    fn combined_columns(&self) -> Vec<(&String, &(Vec<String>, CombineMode))> {
+        let mut combined: Vec<_> = self.combine_objectives.iter().collect();
+        combined.sort_unstable_by_key(|(name, _)| *name);
+
+        combined
+    }
+
+    fn read_uuids(&self) -> Result<Option<UuidMap>, Error> {
+        if !self.use_uuid_as_key {
+            return Ok(None);
+        }
+
+        match (&self.uuid_map_path, &self.uuids) {
+            (Some(path), uuids) => {
+                let mut map = read_usercache(path)?;
+                for (name, uuid) in uuids.iter().flatten() {
+                    map.entry(name.clone()).or_insert_with(|| uuid.clone());
+                }
+                Ok(Some(map))
+            }
+            (None, Some(uuids)) => Ok(Some(uuids.clone())),
+            (None, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "use_uuid_as_key requires uuid_map_path or uuids",
+            )
+            .into()),
+        }
+    }
+
+    fn group_of(&self, player: &str) -> Option<&str> {
+        let groups = self.player_groups.as_ref()?;
+
+        match groups.get(player).map(String::as_str) {
+            Some("") | None => Some(UNGROUPED),
+            Some(group) => Some(group),
+        }
+    }
+}
+
+impl Stats {
+    pub fn write_csv(&self, w: impl Write) -> Result<(), Error> {
+        self.write_csv_with_options(w, &CsvOptions::default())
+    }
+
+    pub fn write_csv_with_options(&self, w: impl Write, opts: &CsvOptions) -> Result<(), Error> {
+        let mut players = self.players_in_order(opts.player_order);
+
+        if opts.player_groups.is_some() {
+            players.sort_by_key(|player| opts.group_of(player));
+        }
+
+        self.write_csv_players(w, &players, opts, opts.read_uuids()?.as_ref())
+    }
+
+    /// Writes a csv with the scores of `objectives` read as game ticks and
+    /// written as `HH:MM:SS`, replacing any other formatter of them
+    pub fn write_csv_with_score_as_time(
+        &self,
+        w: impl Write,
+        objectives: &[&str],
+        opts: &CsvOptions,
+    ) -> Result<(), Error> {
+        let mut opts = opts.clone();
+        for objective in objectives {
+            opts.formatters
+                .insert(objective.to_string(), ObjectiveFormatter::TicksToTime);
+        }
+
+        self.write_csv_with_options(w, &opts)
+    }
+
+    /// Writes a `player,objective,score` row for every score, ordered by
+    /// player and then by objective. The same rows as [`stream_csv_long`]
+    /// writes, without its limit to the order of the file.
+    ///
+    /// [`stream_csv_long`]: super::stream_csv_long
+    pub fn write_csv_long(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+
+        let scores = self.score_lookup();
+        let mut objectives: Vec<&str> = self.player_scores.keys().map(String::as_str).collect();
+        objectives.sort_unstable();
+
+        w.write_record(LONG_CSV_HEADER)?;
+
+        for player in self.players_in_order(PlayerOrder::default()) {
+            for objective in objectives.iter() {
+                let Some(score) = scores.get(&(*objective, player)) else {
+                    continue;
+                };
+
+                record.clear();
+                record.push_field(player.as_bytes());
+                record.push_field(objective.as_bytes());
+                record.push_field(score_buffer.format(*score).as_bytes());
+                w.write_byte_record(&record)?;
+            }
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Splits the alphabetically sorted players into chunks of `chunk_size`
+    /// and writes each chunk into its own csv file in `dir`, every file
+    /// having the full header.
+    ///
+    /// Returns the paths of the written files in player order. Nothing is
+    /// written when any of the chunk files already exists.
+    pub fn write_csv_chunked(&self, dir: &Path, chunk_size: usize) -> Result<Vec<PathBuf>, Error> {
+        if chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk_size must be larger than zero",
+            )
+            .into());
+        }
+
+        let players = self.player_names();
+        let chunks: Vec<(PathBuf, &[&str])> = players
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, players)| (dir.join(format!("chunk_{:04}.csv", i)), players))
+            .collect();
+
+        //existing chunks are never replaced, checked up front so nothing is
+        //written when any of them exists
+        if let Some((path, _)) = chunks.iter().find(|(path, _)| path.exists()) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )
+            .into());
+        }
+
+        let mut paths = Vec::new();
+
+        for (path, players) in chunks {
+            let mut file = AtomicFile::create(&path)?;
+            self.write_csv_players(&mut file, players, &CsvOptions::default(), None)?;
+            file.commit()?;
+
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    /// The columns [`Stats::write_csv_with_options`] writes with `opts`, in
+    /// order
+    pub fn csv_columns(&self, opts: &CsvOptions) -> Result<Vec<CsvColumn>, Error> {
+        self.columns(&self.exported_titles(opts), opts, opts.use_uuid_as_key)
+    }
+
+    /// The [`Stats::titles`] in the column order of `opts`, without the
+    /// sources it hides
+    fn exported_titles(&self, opts: &CsvOptions) -> Vec<&String> {
+        let mut titles = self.titles();
+
+        //the titles are sorted by name, so stable sorts keep that for ties
+        match opts.column_order {
+            ColumnOrder::Name => {}
+            ColumnOrder::Display => {
+                titles.sort_by_key(|title| self.objectives[*title].display_name())
+            }
+            ColumnOrder::Criteria => titles.sort_by_cached_key(|title| {
+                self.objectives[*title].criteria().category().to_string()
+            }),
+            ColumnOrder::TotalDesc => titles.sort_by_cached_key(|title| {
+                let total: i128 = self
+                    .player_scores
+                    .get(*title)
+                    .into_iter()
+                    .flatten()
+                    .map(|x| x.score as i128)
+                    .sum();
+
+                Reverse(total)
+            }),
+        }
+
+        if opts.hide_sources {
+            titles.retain(|title| {
+                !opts
+                    .combine_objectives
+                    .values()
+                    .any(|(sources, _)| sources.contains(title))
+            });
+        }
+
+        titles
+    }
+
+    /// The objective names in column order
+    pub(super) fn titles(&self) -> Vec<&String> {
+        let mut titles: Vec<&String> = self.objectives.keys().collect();
+        titles.sort_unstable();
+
+        titles
+    }
+
+    pub(super) fn columns(
+        &self,
+        titles: &[&String],
+        opts: &CsvOptions,
+        with_uuid: bool,
+    ) -> Result<Vec<CsvColumn>, Error> {
+        let text = |name: &str| CsvColumn {
+            name: name.to_string(),
+            column_type: ColumnType::String,
+            objective: None,
+            criteria: None,
+        };
+
+        let mut columns = Vec::new();
+
+        if with_uuid {
+            columns.push(text("UUID"));
+        }
+
+        if opts.player_groups.is_some() {
+            columns.push(text("Group"));
+        }
+
+        match (&opts.header_map.players, opts.team_rows) {
+            (Some(header), _) => columns.push(text(header)),
+            (None, true) => columns.push(text("Team")),
+            (None, false) => columns.push(text("Players")),
+        }
+
+        if opts.team_size_column {
+            columns.push(CsvColumn {
+                column_type: ColumnType::Integer,
+                ..text("Members")
+            });
+        }
+
+        //anything added to a score makes it text
+        let decorated = opts.unicode_bars
+            || opts.change_arrows.is_some()
+            || opts.annotate_outliers
+            || opts.flag_condition.is_some();
+
+        for title in titles {
+            let objective = &self.objectives[*title];
+
+            let header = match opts.header_names {
+                HeaderNames::Display => objective.display_name.as_str(),
+                HeaderNames::Internal => title.as_str(),
+                HeaderNames::Friendly => match objective.criteria().label() {
+                    Some(label) => label,
+                    None if !objective.display_name.is_empty() => &objective.display_name,
+                    None => title.as_str(),
+                },
+            };
+
+            columns.push(CsvColumn {
+                name: match (
+                    opts.header_map.objectives.get(*title),
+                    opts.preserve_raw_names,
+                ) {
+                    (Some(mapped), _) => mapped.clone(),
+                    (None, true) => header.to_string(),
+                    (None, false) => strip_formatting(header).into_owned(),
+                },
+                column_type: match (decorated, opts.formatters.get(*title)) {
+                    (true, _) => ColumnType::String,
+                    (false, Some(formatter)) if !formatter.is_numeric() => ColumnType::String,
+                    (false, Some(_)) => ColumnType::Number,
+                    (false, None) => ColumnType::Integer,
+                },
+                objective: Some(title.to_string()),
+                criteria: Some(objective.criteria_name.clone()),
+            });
+
+            if opts.dense_rank_columns {
+                columns.push(CsvColumn {
+                    name: format!("{}_rank", title),
+                    column_type: ColumnType::Integer,
+                    objective: Some(title.to_string()),
+                    criteria: Some(objective.criteria_name.clone()),
+                });
+            }
+
+            if opts.percentile_columns {
+                columns.push(CsvColumn {
+                    name: format!("{}_pct", title),
+                    column_type: ColumnType::Integer,
+                    objective: Some(title.to_string()),
+                    criteria: Some(objective.criteria_name.clone()),
+                });
+            }
+
+            if opts.classify.contains_key(*title) {
+                columns.push(CsvColumn {
+                    name: format!("{}_class", title),
+                    column_type: ColumnType::String,
+                    objective: Some(title.to_string()),
+                    criteria: Some(objective.criteria_name.clone()),
+                });
+            }
+        }
+
+        for (name, (sources, mode)) in opts.combined_columns() {
+            if let Some(source) = sources.iter().find(|x| !self.objectives.contains_key(*x)) {
+                return Err(Error::UnknownObjective(source.clone()));
+            }
+
+            columns.push(CsvColumn {
+                column_type: match mode {
+                    CombineMode::Sum => ColumnType::Integer,
+                    CombineMode::Average => ColumnType::Number,
+                },
+                ..text(name)
+            });
+        }
+
+        for (name, _) in opts.computed_columns.iter() {
+            columns.push(CsvColumn {
+                column_type: ColumnType::Number,
+                ..text(name)
+            });
+        }
+
+        if opts.row_hash {
+            columns.push(text(ROW_HASH));
+        }
+
+        resolve_header_collisions(&mut columns, opts.on_header_collision)?;
+
+        Ok(columns)
+    }
+
+    fn write_csv_players(
+        &self,
+        mut w: impl Write,
+        players: &[&str],
+        opts: &CsvOptions,
+        uuids: Option<&UuidMap>,
+    ) -> Result<(), Error> {
+        let titles = self.exported_titles(opts);
+        let columns = self.columns(&titles, opts, uuids.is_some())?;
+
+        let formatters: Vec<Option<&ObjectiveFormatter>> = titles
+            .iter()
+            .map(|title| opts.formatters.get(*title))
+            .collect();
+
+        let scores = self.score_lookup();
+        let baseline_scores = opts.change_arrows.as_ref().map(|x| x.score_lookup());
+
+        //bars are scaled to the highest score of all players, not just the
+        //ones written here, so chunks share the same scale
+        let max_scores: Vec<i64> = match opts.unicode_bars {
+            true => titles
+                .iter()
+                .map(|title| {
+                    self.player_scores
+                        .get(*title)
+                        .and_then(|x| x.iter().map(|x| x.score).max())
+                        .unwrap_or(0)
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        //like the bars the fences come from every player of the objective
+        let outlier_fences: Vec<Option<(f64, f64)>> = match opts.annotate_outliers {
+            true => titles
+                .iter()
+                .map(|title| {
+                    let mut scores: Vec<i64> = self
+                        .player_scores
+                        .get(*title)
+                        .into_iter()
+                        .flatten()
+                        .map(|x| x.score)
+                        .collect();
+                    scores.sort_unstable();
+
+                    outlier_fences(&scores)
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        let sorted_scores: Vec<Vec<i64>> = match opts.percentile_columns {
+            true => titles
+                .iter()
+                .map(|title| {
+                    let mut scores: Vec<i64> = self
+                        .player_scores
+                        .get(*title)
+                        .into_iter()
+                        .flatten()
+                        .map(|x| x.score)
+                        .collect();
+                    scores.sort_unstable();
+
+                    scores
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        //every distinct score from the highest down
+        let distinct_scores: Vec<Vec<i64>> = match opts.dense_rank_columns {
+            true => titles
+                .iter()
+                .map(|title| {
+                    let mut scores: Vec<i64> = self
+                        .player_scores
+                        .get(*title)
+                        .into_iter()
+                        .flatten()
+                        .map(|x| x.score)
+                        .collect();
+                    scores.sort_unstable_by(|a, b| b.cmp(a));
+                    scores.dedup();
+
+                    scores
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        if opts.bom {
+            w.write_all(UTF8_BOM)?;
+        }
+
+        let mut w = csv::Writer::from_writer(w);
+
+        //a single record and score buffers are reused for every row
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+        let mut formatted_buffer = String::new();
+
+        if opts.group_objectives_by_prefix {
+            let mut previous = None;
+            for column in &columns {
+                let prefix = column
+                    .objective
+                    .as_deref()
+                    .and_then(|x| x.split_once('.'))
+                    .map(|(prefix, _)| prefix);
+
+                match prefix.is_some() && prefix != previous {
+                    true => record.push_field(prefix.unwrap_or_default().as_bytes()),
+                    false => record.push_field(b""),
+                }
+
+                previous = prefix;
+            }
+
+            w.write_byte_record(&record)?;
+            record.clear();
+        }
+
+        for column in &columns {
+            record.push_field(column.name.as_bytes());
+        }
+
+        w.write_byte_record(&record)?;
+
+        //loops over every player gathering all the stats
+        for player in players {
+            record.clear();
+
+            if let Some(uuids) = uuids {
+                record.push_field(uuids.get(*player).map_or("", String::as_str).as_bytes());
+            }
+
+            if let Some(group) = opts.group_of(player) {
+                record.push_field(group.as_bytes());
+            }
+
+            match opts.base64_player_names {
+                true => record.push_field(BASE64_STANDARD.encode(player).as_bytes()),
+                false => match opts.preserve_raw_names {
+                    true => record.push_field(player.as_bytes()),
+                    false => record.push_field(strip_formatting(player).as_bytes()),
+                },
+            }
+
+            if opts.team_size_column {
+                let members = self
+                    .teams
+                    .iter()
+                    .find(|team| team.name() == *player)
+                    .map_or(0, |team| team.players().len());
+                record.push_field(score_buffer.format(members).as_bytes());
+            }
+
+            //gathers all the stats for a specific player
+            for (i, (title, formatter)) in titles.iter().zip(&formatters).enumerate() {
+                let score = scores.get(&(title.as_str(), *player)).copied();
+
+                let bins = opts.classify.get(*title);
+
+                let Some(score) = score else {
+                    match opts.unicode_bars || opts.empty_missing_scores {
+                        true => record.push_field(b""),
+                        false => record.push_field(MISSING_SCORE.as_bytes()),
+                    }
+                    if opts.dense_rank_columns {
+                        record.push_field(b"");
+                    }
+                    if opts.percentile_columns {
+                        record.push_field(b"");
+                    }
+                    if bins.is_some() {
+                        record.push_field(b"");
+                    }
+                    continue;
+                };
+
+                match (opts.unicode_bars, formatter) {
+                    (true, _) => {
+                        formatted_buffer.clear();
+                        push_bar(score, max_scores[i], &mut formatted_buffer);
+                    }
+                    (false, Some(formatter)) => formatter.format(score, &mut formatted_buffer),
+                    (false, None) => {
+                        formatted_buffer.clear();
+                        formatted_buffer.push_str(score_buffer.format(score));
+                    }
+                }
+
+                if let Some(baseline) = &baseline_scores {
+                    let previous = baseline
+                        .get(&(title.as_str(), *player))
+                        .copied()
+                        .unwrap_or(0);
+
+                    formatted_buffer.push_str(match score.cmp(&previous) {
+                        Ordering::Greater => "(↑)",
+                        Ordering::Less => "(↓)",
+                        Ordering::Equal => "(=)",
+                    });
+                }
+
+                if let Some(Some((low, high))) = outlier_fences.get(i) {
+                    if (score as f64) < *low || (score as f64) > *high {
+                        formatted_buffer.push_str("(outlier)");
+                    }
+                }
+
+                if let Some(condition) = &opts.flag_condition {
+                    if (condition.0)(player, title, score) {
+                        formatted_buffer.push('*');
+                    }
+                }
+
+                record.push_field(formatted_buffer.as_bytes());
+
+                if let Some(distinct) = distinct_scores.get(i) {
+                    let rank = distinct.partition_point(|x| *x > score) + 1;
+                    record.push_field(score_buffer.format(rank).as_bytes());
+                }
+
+                if let Some(sorted) = sorted_scores.get(i) {
+                    record.push_field(score_buffer.format(percentile(sorted, score)).as_bytes());
+                }
+
+                if let Some(bins) = bins {
+                    let class = bins.iter().find(|(bound, _)| score < *bound);
+                    record.push_field(class.map_or("", |(_, label)| label.as_str()).as_bytes());
+                }
+            }
+
+            for (_, (sources, mode)) in opts.combined_columns() {
+                let source_scores = sources.iter().filter_map(|source| {
+                    scores.get(&(source.as_str(), *player)).map(|x| *x as i128)
+                });
+
+                let (count, total) =
+                    source_scores.fold((0, 0), |(count, total), x| (count + 1, total + x));
+
+                formatted_buffer.clear();
+                match (count, mode) {
+                    (0, _) if opts.empty_missing_scores => {}
+                    (0, _) => formatted_buffer.push_str(MISSING_SCORE),
+                    (_, CombineMode::Sum) => write!(formatted_buffer, "{}", total).unwrap(),
+                    (_, CombineMode::Average) => write!(
+                        formatted_buffer,
+                        "{:.prec$}",
+                        total as f64 / count as f64,
+                        prec = opts.float_precision as usize
+                    )
+                    .unwrap(),
+                }
+                record.push_field(formatted_buffer.as_bytes());
+            }
+
+            for (_, values) in opts.computed_columns.iter() {
+                match values.get(*player) {
+                    Some(value) => {
+                        formatted_buffer.clear();
+                        write!(
+                            formatted_buffer,
+                            "{:.prec$}",
+                            value,
+                            prec = opts.float_precision as usize
+                        )
+                        .unwrap();
+                        record.push_field(formatted_buffer.as_bytes());
+                    }
+                    None => record.push_field(MISSING_SCORE.as_bytes()),
+                }
+            }
+
+            if opts.row_hash {
+                //missing scores hash as empty so they differ from a 0
+                let mut hasher = Sha256::new().chain_update(player.as_bytes());
+                for title in titles.iter() {
+                    hasher.update(b",");
+                    if let Some(score) = scores.get(&(title.as_str(), *player)) {
+                        hasher.update(score_buffer.format(*score).as_bytes());
+                    }
+                }
+
+                formatted_buffer.clear();
+                for byte in hasher.finalize() {
+                    write!(formatted_buffer, "{:02x}", byte).unwrap();
+                }
+                record.push_field(formatted_buffer.as_bytes());
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+/// The percentile rank of `score` in the sorted scores that include it,
+/// rounded to an integer. Tied scores share the rank of the first of them
+/// and a lone score is at 100.
+fn percentile(sorted_scores: &[i64], score: i64) -> usize {
+    let rank = sorted_scores.partition_point(|x| *x < score);
+
+    match sorted_scores.len() - 1 {
+        0 => 100,
+        others => (rank * 100 + others / 2) / others,
+    }
+}
+
+/// The lowest and highest score that isn't an outlier, `Q1 - 1.5 IQR` and
+/// `Q3 + 1.5 IQR`, or `None` without any scores
+fn outlier_fences(sorted_scores: &[i64]) -> Option<(f64, f64)> {
+    let q1 = quantile(sorted_scores, 0.25)?;
+    let q3 = quantile(sorted_scores, 0.75)?;
+    let iqr = q3 - q1;
+
+    Some((q1 - 1.5 * iqr, q3 + 1.5 * iqr))
+}
+
+/// The `q` quantile of sorted scores, interpolating linearly between the
+/// two closest ranks
+fn quantile(sorted_scores: &[i64], q: f64) -> Option<f64> {
+    let last = sorted_scores.len().checked_sub(1)?;
+
+    let rank = q * last as f64;
+    let below = rank.floor() as usize;
+    let above = rank.ceil() as usize;
+
+    let low = sorted_scores[below] as f64;
+    let high = sorted_scores[above] as f64;
+
+    Some(low + (high - low) * (rank - below as f64))
+}
+
+/// Makes the headers of `columns` unique according to `on_collision`, only
+/// objective columns are renamed
+pub(super) fn resolve_header_collisions(
+    columns: &mut [CsvColumn],
+    on_collision: HeaderCollision,
+) -> Result<(), Error> {
+    let duplicates = |columns: &[CsvColumn]| {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for column in columns {
+            *counts.entry(&column.name).or_default() += 1;
+        }
+
+        //in column order so errors name the leftmost duplicate
+        columns
+            .iter()
+            .filter(|x| counts[x.name.as_str()] > 1)
+            .map(|x| x.name.clone())
+            .collect::<Vec<String>>()
+    };
+
+    let duplicated = duplicates(columns);
+
+    match (duplicated.first(), on_collision) {
+        (None, _) => return Ok(()),
+        (Some(name), HeaderCollision::Error) => return Err(Error::DuplicateHeader(name.clone())),
+        (Some(_), HeaderCollision::Disambiguate) => (),
+    }
+
+    for column in columns.iter_mut() {
+        if let (true, Some(objective)) = (duplicated.contains(&column.name), &column.objective) {
+            column.name = format!("{} ({})", column.name, objective);
+        }
+    }
+
+    //columns that aren't objectives keep their name, so they can still clash
+    match duplicates(columns).into_iter().next() {
+        Some(name) => Err(Error::DuplicateHeader(name)),
+        None => Ok(()),
+    }
+}
+
+/// Partial blocks from one to seven eighths wide
+const PARTIAL_BLOCKS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+const FULL_BLOCK: char = '█';
+const MAX_BAR_CHARS: i128 = 10;
+
+/// Appends a bar `score / max` of `MAX_BAR_CHARS` long in eighths of a
+/// character, zero and negative scores get no bar
+fn push_bar(score: i64, max: i64, buffer: &mut String) {
+    if score <= 0 || max <= 0 {
+        return;
+    }
+
+    let eighths = (score as i128 * MAX_BAR_CHARS * 8 + max as i128 / 2) / max as i128;
+    let eighths = eighths.clamp(1, MAX_BAR_CHARS * 8) as usize;
+
+    buffer.extend(std::iter::repeat_n(FULL_BLOCK, eighths / 8));
+    if let Some(partial) = (eighths % 8).checked_sub(1) {
+        buffer.push(PARTIAL_BLOCKS[partial]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats() -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 3),
+                ("anna", "kills", 7),
+                ("ville", "kills", 1),
+            ]
+            .into_iter(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    fn csv(stats: &Stats, opts: &CsvOptions) -> String {
+        let mut out = Vec::new();
+        stats.write_csv_with_options(&mut out, opts).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn missing_scores_are_written_as_the_placeholder() {
+        assert_eq!(
+            csv(&stats(), &CsvOptions::default()),
+            "Players,deaths,kills\nanna,0,7\nville,3,1\n"
+        );
+    }
+
+    #[test]
+    fn missing_scores_can_be_left_empty() {
+        let opts = CsvOptions {
+            empty_missing_scores: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&stats(), &opts),
+            "Players,deaths,kills\nanna,,7\nville,3,1\n"
+        );
+    }
+
+    #[test]
+    fn names_are_quoted_when_needed() {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount")],
+            [("a,b", "deaths", 1), ("say \"hi\"", "deaths", 2)].into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+
+        assert_eq!(
+            csv(&stats, &CsvOptions::default()),
+            "Players,deaths\n\"a,b\",1\n\"say \"\"hi\"\"\",2\n"
+        );
+    }
+
+    #[test]
+    fn combined_columns_read_the_same_scores() {
+        let opts = CsvOptions {
+            combine_objectives: HashMap::from([(
+                "both".to_string(),
+                (
+                    vec!["deaths".to_string(), "kills".to_string()],
+                    CombineMode::Sum,
+                ),
+            )]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&stats(), &opts),
+            "Players,deaths,kills,both\nanna,0,7,7\nville,3,1,4\n"
+        );
+    }
+
+    #[test]
+    fn averages_skip_missing_scores() {
+        let nbt = fixture::scoreboard(
+            &[
+                ("deaths", "deathCount"),
+                ("kills", "playerKillCount"),
+                ("jumps", "minecraft.custom:minecraft.jump"),
+            ],
+            [
+                ("ville", "deaths", 3),
+                ("anna", "kills", 7),
+                ("ville", "kills", 2),
+                ("otto", "jumps", 5),
+            ]
+            .into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+        let opts = CsvOptions {
+            combine_objectives: HashMap::from([(
+                "fights".to_string(),
+                (
+                    vec!["deaths".to_string(), "kills".to_string()],
+                    CombineMode::Average,
+                ),
+            )]),
+            hide_sources: true,
+            float_precision: 2,
+            ..Default::default()
+        };
+
+        //otto has neither score and gets the placeholder, not an average of 0
+        assert_eq!(
+            csv(&stats, &opts),
+            "Players,jumps,fights\nanna,0,7.00\notto,5,0\nville,0,2.50\n"
+        );
+    }
+
+    #[test]
+    fn ticks_are_formatted_as_time() {
+        let mut buffer = String::new();
+
+        for (ticks, expected) in [
+            (72_000, "01:00:00"),
+            (0, "00:00:00"),
+            (19, "00:00:00"),
+            (1_234, "00:01:01"),
+            (-72_000, "-01:00:00"),
+            (-1_234, "-00:01:01"),
+            (7_200_000, "100:00:00"),
+            (i32::MAX as i64, "29826:09:42"),
+        ] {
+            ObjectiveFormatter::TicksToTime.format(ticks, &mut buffer);
+            assert_eq!(buffer, expected, "{}", ticks);
+        }
+    }
+
+    #[test]
+    fn ticks_to_time_formats_its_objective_only() {
+        let nbt = fixture::scoreboard(
+            &[("play", "dummy"), ("deaths", "deathCount")],
+            [("ville", "play", 72_000), ("ville", "deaths", 72_000)].into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+        let opts = CsvOptions {
+            formatters: HashMap::from([("play".to_string(), ObjectiveFormatter::TicksToTime)]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&stats, &opts),
+            "Players,deaths,play\nville,72000,01:00:00\n"
+        );
+    }
+
+    /// Tied deaths, and players missing from either objective
+    fn ties() -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("anna", "deaths", 10),
+                ("bob", "deaths", 20),
+                ("carl", "deaths", 20),
+                ("dave", "deaths", 40),
+                ("anna", "kills", 1),
+                ("eve", "kills", 5),
+            ]
+            .into_iter(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    #[test]
+    fn percentiles_count_the_scores_of_the_objective() {
+        let opts = CsvOptions {
+            percentile_columns: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&ties(), &opts),
+            "Players,deaths,deaths_pct,kills,kills_pct\n\
+            anna,10,0,1,0\n\
+            bob,20,33,0,\n\
+            carl,20,33,0,\n\
+            dave,40,100,0,\n\
+            eve,0,,5,100\n"
+        );
+    }
+
+    #[test]
+    fn percentile_ranks() {
+        for (sorted, score, expected) in [
+            (&[7][..], 7, 100),
+            (&[1, 2, 3, 4, 5], 1, 0),
+            (&[1, 2, 3, 4, 5], 3, 50),
+            (&[1, 2, 3, 4, 5], 5, 100),
+            (&[5, 5, 5], 5, 0),
+            (&[1, 5, 5, 9], 5, 33),
+        ] {
+            assert_eq!(
+                percentile(sorted, score),
+                expected,
+                "{} in {:?}",
+                score,
+                sorted
+            );
+        }
+    }
+
+    #[test]
+    fn tied_scores_share_a_dense_rank() {
+        let opts = CsvOptions {
+            dense_rank_columns: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&ties(), &opts),
+            "Players,deaths,deaths_rank,kills,kills_rank\n\
+            anna,10,3,1,2\n\
+            bob,20,2,0,\n\
+            carl,20,2,0,\n\
+            dave,40,1,0,\n\
+            eve,0,,5,1\n"
+        );
+    }
+
+    #[test]
+    fn quantiles_interpolate_between_ranks() {
+        for (sorted, q, expected) in [
+            (&[1, 2, 3, 4][..], 0.25, 1.75),
+            (&[1, 2, 3, 4], 0.75, 3.25),
+            (&[1, 2, 3, 4, 5], 0.5, 3.0),
+            (&[10, 10, 10, 10, 100], 0.75, 10.0),
+            (&[7], 0.25, 7.0),
+        ] {
+            assert_eq!(quantile(sorted, q), Some(expected), "{} of {:?}", q, sorted);
+        }
+
+        assert_eq!(quantile(&[], 0.5), None);
+        assert_eq!(outlier_fences(&[1, 2, 3, 4]), Some((-0.5, 5.5)));
+        assert_eq!(outlier_fences(&[]), None);
+    }
+
+    #[test]
+    fn outliers_are_annotated() {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("walk", "dummy")],
+            [
+                ("anna", "walk", 10),
+                ("bob", "walk", 10),
+                ("carl", "walk", 10),
+                ("dave", "walk", 10),
+                ("eve", "walk", 100),
+                ("fred", "deaths", 1),
+            ]
+            .into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+        let opts = CsvOptions {
+            annotate_outliers: true,
+            ..Default::default()
+        };
+
+        //the tied scores leave no spread, and fred's missing walk isn't a score
+        assert_eq!(
+            csv(&stats, &opts),
+            "Players,deaths,walk\n\
+            anna,0,10\n\
+            bob,0,10\n\
+            carl,0,10\n\
+            dave,0,10\n\
+            eve,0,100(outlier)\n\
+            fred,1,0\n"
+        );
+    }
+
+    /// The `_sha256` column of every row by player
+    fn row_hashes(stats: &Stats, opts: &CsvOptions) -> Vec<(String, String)> {
+        csv(stats, opts)
+            .lines()
+            .skip(1)
+            .map(|row| {
+                let (player, _) = row.split_once(',').unwrap();
+                let (_, hash) = row.rsplit_once(',').unwrap();
+                (player.to_string(), hash.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn row_hash_ignores_formatting() {
+        let plain = CsvOptions {
+            row_hash: true,
+            ..Default::default()
+        };
+        let formatted = CsvOptions {
+            row_hash: true,
+            formatters: HashMap::from([(
+                "kills".to_string(),
+                ObjectiveFormatter::Scale {
+                    scale: 0.5,
+                    suffix: "k".to_string(),
+                    decimal_places: 2,
+                },
+            )]),
+            percentile_columns: true,
+            dense_rank_columns: true,
+            empty_missing_scores: true,
+            ..Default::default()
+        };
+
+        let hashes = row_hashes(&stats(), &plain);
+
+        assert_eq!(hashes, row_hashes(&stats(), &formatted));
+        assert_eq!(
+            hashes[1],
+            (
+                "ville".to_string(),
+                format!("{:x}", Sha256::digest(b"ville,3,1"))
+            )
+        );
+    }
+
+    #[test]
+    fn missing_scores_hash_unlike_zeros() {
+        let opts = CsvOptions {
+            row_hash: true,
+            ..Default::default()
+        };
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 3),
+                ("anna", "deaths", 0),
+                ("anna", "kills", 7),
+                ("ville", "kills", 1),
+            ]
+            .into_iter(),
+        );
+        let zero = Stats::from_gzip_reader(&nbt[..]).unwrap();
+
+        //both rows are written as anna,0,7
+        let missing = row_hashes(&stats(), &opts);
+        let zero = row_hashes(&zero, &opts);
+
+        assert_ne!(missing[0], zero[0]);
+        assert_eq!(missing[0].1, format!("{:x}", Sha256::digest(b"anna,,7")));
+        assert_eq!(zero[0].1, format!("{:x}", Sha256::digest(b"anna,0,7")));
+        assert_eq!(missing[1], zero[1]);
+    }
+
+    #[test]
+    fn bars_are_scaled_to_the_highest_score() {
+        let opts = CsvOptions {
+            unicode_bars: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv(&stats(), &opts),
+            "Players,deaths,kills\nanna,,██████████\nville,██████████,█▍\n"
+        );
+    }
+
+    #[test]
+    fn bars_are_rounded_to_eighths() {
+        let bar = |score, max| {
+            let mut buffer = String::new();
+            push_bar(score, max, &mut buffer);
+            buffer
+        };
+
+        assert_eq!(bar(50, 100), "█████");
+        assert_eq!(bar(51, 100), "█████▏");
+        assert_eq!(bar(1, 1000), "▏");
+        assert_eq!(bar(0, 100), "");
+        assert_eq!(bar(-5, 100), "");
+        assert_eq!(bar(5, -100), "");
+        assert_eq!(bar(i64::MAX, i64::MAX), "██████████");
+    }
+
+    #[test]
+    fn flagged_scores_are_marked() {
+        let opts = CsvOptions {
+            flag_condition: Some(FlagCondition::new(|player, objective, score| {
+                (player == "ville" && objective == "kills") || score > 5
+            })),
+            ..Default::default()
+        };
+
+        //missing scores are never passed to the condition
+        assert_eq!(
+            csv(&stats(), &opts),
+            "Players,deaths,kills\nanna,0,7*\nville,3,1*\n"
+        );
+    }
+
+    #[test]
+    fn arrows_compare_with_the_baseline() {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 3),
+                ("ville", "kills", 2),
+                ("anna", "deaths", 4),
+            ]
+            .into_iter(),
+        );
+        let opts = CsvOptions {
+            change_arrows: Some(Arc::new(Stats::from_gzip_reader(&nbt[..]).unwrap())),
+            ..Default::default()
+        };
+
+        //anna's kills are missing from the baseline and count as 0, her
+        //deaths are missing now and get no arrow
+        assert_eq!(
+            csv(&stats(), &opts),
+            "Players,deaths,kills\nanna,0,7(↑)\nville,3(=),1(↓)\n"
+        );
+    }
+
+    #[test]
+    fn the_bom_comes_before_the_header_only() {
+        let opts = CsvOptions {
+            bom: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        stats().write_csv_with_options(&mut out, &opts).unwrap();
+
+        assert_eq!(
+            out,
+            b"\xEF\xBB\xBFPlayers,deaths,kills\nanna,0,7\nville,3,1\n"
+        );
+        //csv readers skip it instead of reading it into the first header
+        let read = Stats::from_csv_reader(&out[..]).unwrap();
+        assert_eq!(read.scores_of("ville"), stats().scores_of("ville"));
+    }
+}
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, io, io::Read};
+
+use base64::prelude::*;
+use nbt::Map;
+
+use super::{NameInterner, Objective, PlayerScore, Stats};
+use crate::error::Error;
+
+/// Columns that come before `Players` in a written csv
+const KEY_COLUMNS: [&str; 2] = ["UUID", "Group"];
+
+/// Options for reading back a wide csv written by this crate
+#[derive(Debug, Default, Clone)]
+pub struct CsvImportOptions {
+    /// Cells holding exactly this are players without a score. Empty cells
+    /// by default, set it to `0` to drop the placeholder the csv writer
+    /// uses for missing scores along with real zeros.
+    pub missing_score: String,
+    /// Maps headers to objective names, headers missing from the map are
+    /// used as the objective name
+    pub header_map: HashMap<String, String>,
+    /// The header of the players column when it isn't `Players`, like
+    /// after [`HeaderMap::players`](super::HeaderMap::players)
+    pub players_header: Option<String>,
+    /// The `Players` column was written with
+    /// [`CsvOptions::base64_player_names`](super::CsvOptions::base64_player_names)
+    pub base64_player_names: bool,
+}
+
+impl Stats {
+    /// Reads back a wide csv with a `Players` column followed by a column of
+    /// scores per objective, like [`Stats::write_csv`] writes.
+    ///
+    /// The csv only holds display names, so objectives get the header as
+    /// display name and, unless mapped, as name. The criteria is `dummy`.
+    pub fn from_csv_reader(r: impl Read) -> Result<Self, Error> {
+        Self::from_csv_reader_with(r, &CsvImportOptions::default())
+    }
+
+    /// Reads back a csv written with [`CsvOptions::base64_player_names`],
+    /// decoding the player names.
+    ///
+    /// The csv only holds display names, so they are used as the objective
+    /// names too and the other objective fields get placeholder values.
+    /// Every cell becomes a score, including the `0` written for missing
+    /// scores.
+    ///
+    /// [`CsvOptions::base64_player_names`]: super::CsvOptions::base64_player_names
+    pub fn decode_player_names_csv(r: impl Read) -> Result<Self, Error> {
+        Self::from_csv_reader_with(
+            r,
+            &CsvImportOptions {
+                base64_player_names: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// [`Stats::from_csv_reader`] with `opts`
+    pub fn from_csv_reader_with(r: impl Read, opts: &CsvImportOptions) -> Result<Self, Error> {
+        let mut reader = csv::Reader::from_reader(r);
+        let headers = reader.headers()?.clone();
+
+        let players_header = opts.players_header.as_deref().unwrap_or("Players");
+        let players_column = headers
+            .iter()
+            .position(|header| header == players_header)
+            .ok_or_else(|| invalid_data(format!("missing the {} column", players_header)))?;
+
+        if !headers
+            .iter()
+            .take(players_column)
+            .all(|header| KEY_COLUMNS.contains(&header))
+        {
+            return Err(invalid_data(format!(
+                "unexpected columns before {}",
+                players_header
+            )));
+        }
+
+        let titles: Vec<&str> = headers.iter().skip(players_column + 1).collect();
+        let names: Vec<&str> = titles
+            .iter()
+            .map(|title| opts.header_map.get(*title).map_or(*title, String::as_str))
+            .collect();
+
+        let mut objectives: Map<String, Objective> = Map::new();
+
+        for (title, name) in titles.iter().zip(&names) {
+            let objective = Objective {
+                name: name.to_string(),
+                criteria_name: "dummy".to_string(),
+                display_auto_update: 0,
+                display_name: title.to_string(),
+                render_type: "integer".to_string(),
+            };
+
+            if objectives.insert(name.to_string(), objective).is_some() {
+                return Err(invalid_data(format!(
+                    "more than one column is objective {}",
+                    name
+                )));
+            }
+        }
+
+        let mut player_scores: Map<String, Vec<PlayerScore>> = Map::new();
+        let mut interner = NameInterner::default();
+
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |x| x.line());
+
+            let name = record.get(players_column).unwrap_or_default();
+            let player_name = match opts.base64_player_names {
+                true => interner.intern(
+                    &BASE64_STANDARD
+                        .decode(name)
+                        .ok()
+                        .and_then(|x| String::from_utf8(x).ok())
+                        .ok_or_else(|| {
+                            invalid_data(format!(
+                                "line {}: {} is not a base64 player name",
+                                line, name
+                            ))
+                        })?,
+                ),
+                false => interner.intern(name),
+            };
+
+            let cells = record.iter().skip(players_column + 1);
+
+            for ((title, name), cell) in titles.iter().zip(&names).zip(cells) {
+                if cell == opts.missing_score {
+                    continue;
+                }
+
+                let score = cell.parse().map_err(|_| {
+                    invalid_data(format!(
+                        "line {}, column {}: {} is not an integer score",
+                        line, title, cell
+                    ))
+                })?;
+
+                player_scores
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(PlayerScore {
+                        locked: 0,
+                        player_name: player_name.clone(),
+                        score,
+                    });
+            }
+        }
+
+        Ok(Self {
+            objectives,
+            player_scores,
+            player_order: interner.into_order(),
+            teams: Vec::new(),
+        })
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture, stats::CsvOptions};
+
+    fn import(csv: &str, opts: &CsvImportOptions) -> Result<Stats, Error> {
+        Stats::from_csv_reader_with(csv.as_bytes(), opts)
+    }
+
+    fn message(e: Error) -> String {
+        match e {
+            Error::IOError(e) if e.kind() == io::ErrorKind::InvalidData => e.to_string(),
+            e => panic!("not invalid data: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn empty_cells_are_missing_scores() {
+        let stats = import(
+            "Players,deaths,kills\nville,3,\nanna,0,7\n",
+            &CsvImportOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            stats.scores_of("ville"),
+            Some(Map::from_iter([("deaths", 3)]))
+        );
+        assert_eq!(
+            stats.scores_of("anna"),
+            Some(Map::from_iter([("deaths", 0), ("kills", 7)]))
+        );
+    }
+
+    #[test]
+    fn placeholder_cells_are_missing_scores() {
+        let opts = CsvImportOptions {
+            missing_score: "0".to_string(),
+            ..Default::default()
+        };
+
+        let stats = import("Players,deaths,kills\nville,3,0\nanna,0,0\n", &opts).unwrap();
+
+        assert_eq!(
+            stats.scores_of("ville"),
+            Some(Map::from_iter([("deaths", 3)]))
+        );
+        assert_eq!(stats.scores_of("anna"), None);
+        //with the placeholder set an empty cell is no longer a missing score
+        assert!(import("Players,deaths\nville,\n", &opts).is_err());
+    }
+
+    #[test]
+    fn key_columns_and_mapped_headers_are_read() {
+        let opts = CsvImportOptions {
+            header_map: HashMap::from([("Deaths".to_string(), "deaths".to_string())]),
+            ..Default::default()
+        };
+
+        let stats = import("UUID,Group,Players,Deaths\n,red,ville,3\n", &opts).unwrap();
+
+        assert_eq!(stats.objectives()["deaths"].display_name, "Deaths");
+        assert_eq!(
+            stats.scores_of("ville"),
+            Some(Map::from_iter([("deaths", 3)]))
+        );
+    }
+
+    #[test]
+    fn bad_scores_are_located_by_line_and_column() {
+        let e = import(
+            "Players,deaths,kills\nville,3,1\nanna,2,lots\n",
+            &CsvImportOptions::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            message(e),
+            "line 3, column kills: lots is not an integer score"
+        );
+    }
+
+    #[test]
+    fn bad_base64_names_are_located_by_line() {
+        let opts = CsvImportOptions {
+            base64_player_names: true,
+            ..Default::default()
+        };
+
+        let stats = import("Players,deaths\ndmlsbGU=,3\n", &opts).unwrap();
+        assert_eq!(
+            stats.scores_of("ville"),
+            Some(Map::from_iter([("deaths", 3)]))
+        );
+
+        let e = import("Players,deaths\ndmlsbGU=,3\nnot base64!,1\n", &opts).unwrap_err();
+        assert_eq!(
+            message(e),
+            "line 3: not base64! is not a base64 player name"
+        );
+    }
+
+    #[test]
+    fn malformed_headers_are_errors() {
+        for (csv, expected) in [
+            ("Name,deaths\nville,3\n", "missing the Players column"),
+            (
+                "Rank,Players,deaths\n1,ville,3\n",
+                "unexpected columns before Players",
+            ),
+            (
+                "Players,deaths,deaths\nville,3,3\n",
+                "more than one column is objective deaths",
+            ),
+        ] {
+            let e = import(csv, &CsvImportOptions::default()).unwrap_err();
+            assert_eq!(message(e), expected);
+        }
+    }
+
+    #[test]
+    fn base64_names_survive_a_round_trip() {
+        let names = ["a,b", "say \"hi\"", "§cred", "line\nbreak", "ünï 名"];
+        let mut scores: Vec<(&str, &str, i32)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, "deaths", i as i32))
+            .collect();
+        scores.push(("ünï 名", "kills", 7));
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+
+        let mut csv = Vec::new();
+        let opts = CsvOptions {
+            base64_player_names: true,
+            empty_missing_scores: true,
+            ..Default::default()
+        };
+        stats.write_csv_with_options(&mut csv, &opts).unwrap();
+        //encoded names never need quoting
+        assert!(!csv.contains(&b'"'));
+
+        let decoded = Stats::decode_player_names_csv(&csv[..]).unwrap();
+
+        assert_eq!(decoded.player_names(), stats.player_names());
+        for name in names {
+            assert_eq!(decoded.scores_of(name), stats.scores_of(name));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{io::Write, path::Path};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use super::{ColumnType, CsvOptions, Stats};
+use crate::error::Error;
+
+impl Stats {
+    /// Writes a Frictionless Data `datapackage.json` describing the csv
+    /// [`Stats::write_csv_with_options`] writes to `csv_path` with `opts`.
+    ///
+    /// The fields come from [`Stats::csv_columns`], so they match the csv
+    /// column for column. Score fields also name their objective and its
+    /// criteria.
+    pub fn write_datapackage(
+        &self,
+        w: impl Write,
+        csv_path: &Path,
+        opts: &CsvOptions,
+        created: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let file_name = csv_path
+            .file_name()
+            .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+
+        let fields: Vec<Value> = self
+            .csv_columns(opts)?
+            .into_iter()
+            .map(|column| {
+                let mut field = Map::new();
+                field.insert("name".into(), column.name.into());
+                field.insert(
+                    "type".into(),
+                    match column.column_type {
+                        ColumnType::String => "string",
+                        ColumnType::Integer => "integer",
+                        ColumnType::Number => "number",
+                    }
+                    .into(),
+                );
+
+                if let Some(objective) = column.objective {
+                    field.insert("objective".into(), objective.into());
+                }
+                if let Some(criteria) = column.criteria {
+                    field.insert("criteria".into(), criteria.into());
+                }
+
+                Value::Object(field)
+            })
+            .collect();
+
+        let mut resource = json!({
+            "name": package_name(&file_name),
+            "path": file_name,
+            "profile": "tabular-data-resource",
+            "format": "csv",
+            "mediatype": "text/csv",
+            "encoding": "utf-8",
+            "schema": { "fields": fields },
+        });
+
+        //the names are in the second row below the prefix groups
+        if opts.group_objectives_by_prefix {
+            resource["dialect"] = json!({ "headerRows": [2] });
+        }
+
+        let package = json!({
+            "profile": "tabular-data-package",
+            "name": package_name(&file_name),
+            "created": created.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "resources": [resource],
+        });
+
+        serde_json::to_writer_pretty(w, &package)?;
+
+        Ok(())
+    }
+}
+
+/// Package and resource names may only have lowercase alphanumerics, `-`,
+/// `_` and `.`
+fn package_name(file_name: &str) -> String {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+
+    stem.chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                true => c.to_ascii_lowercase(),
+                false => '_',
+            },
+        )
+        .collect()
+}
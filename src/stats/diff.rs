@@ -0,0 +1,129 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use super::Stats;
+
+/// A score that isn't the same in two [`Stats`], `None` where the player
+/// has no score in the objective
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScoreDiff {
+    pub objective: String,
+    pub player: String,
+    pub old: Option<i64>,
+    pub new: Option<i64>,
+}
+
+impl Stats {
+    /// Every score that differs between `self` and `new`, sorted by
+    /// objective and then player. Display names and other objective fields
+    /// aren't compared.
+    pub fn diff(&self, new: &Stats) -> Vec<ScoreDiff> {
+        let objectives: BTreeSet<&String> = self
+            .player_scores
+            .keys()
+            .chain(new.player_scores.keys())
+            .collect();
+
+        let mut diffs = Vec::new();
+
+        for objective in objectives {
+            let old_scores = self.scores_in(objective);
+            let new_scores = new.scores_in(objective);
+
+            let players: BTreeSet<&str> = old_scores
+                .keys()
+                .chain(new_scores.keys())
+                .copied()
+                .collect();
+
+            for player in players {
+                let old = old_scores.get(player).copied();
+                let new = new_scores.get(player).copied();
+
+                if old != new {
+                    diffs.push(ScoreDiff {
+                        objective: objective.clone(),
+                        player: player.to_string(),
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+
+        diffs
+    }
+
+    fn scores_in(&self, objective: &str) -> HashMap<&str, i64> {
+        self.player_scores
+            .get(objective)
+            .into_iter()
+            .flatten()
+            .map(|x| (x.player_name(), x.score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats(scores: &[(&str, &str, i32)]) -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.iter().copied(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    fn diff(objective: &str, player: &str, old: Option<i64>, new: Option<i64>) -> ScoreDiff {
+        ScoreDiff {
+            objective: objective.to_string(),
+            player: player.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn same_scores_have_no_diff() {
+        let old = stats(&[("ville", "deaths", 3), ("anna", "kills", 1)]);
+
+        assert_eq!(old.diff(&old.clone()), []);
+    }
+
+    #[test]
+    fn changed_added_and_removed_scores_are_sorted() {
+        let old = stats(&[
+            ("ville", "deaths", 3),
+            ("anna", "deaths", 1),
+            ("anna", "kills", 2),
+        ]);
+        let new = stats(&[
+            ("ville", "deaths", 4),
+            ("anna", "deaths", 1),
+            ("bob", "kills", 5),
+            ("anna", "kills", 0),
+        ]);
+
+        assert_eq!(
+            old.diff(&new),
+            [
+                diff("deaths", "ville", Some(3), Some(4)),
+                diff("kills", "anna", Some(2), Some(0)),
+                diff("kills", "bob", None, Some(5)),
+            ]
+        );
+        assert_eq!(
+            new.diff(&old),
+            [
+                diff("deaths", "ville", Some(4), Some(3)),
+                diff("kills", "anna", Some(0), Some(2)),
+                diff("kills", "bob", Some(5), None),
+            ]
+        );
+    }
+}
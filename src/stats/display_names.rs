@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use async_std::future::timeout;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, MySqlConnection};
+
+use super::{
+    sql::{execute, table_exists, SqlOptions},
+    Objectives,
+};
+use crate::error::Error;
+
+impl SqlOptions {
+    /// Closes the open row in `objective_display_names` of every objective
+    /// whose display name changed and opens one with the new name from
+    /// `timestamp`. Databases without the table are left alone until they
+    /// are upgraded.
+    pub(super) async fn record_display_names(
+        &self,
+        conn: &mut MySqlConnection,
+        objectives: &Objectives,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let tables = self.tables()?;
+
+        if !table_exists(&mut *conn, &tables.objective_display_names).await? {
+            return Ok(());
+        }
+
+        let open: HashMap<String, Option<String>> = timeout(
+            self.query_timeout,
+            query_as(&format!(
+                "SELECT objective_name, display_name FROM {} WHERE valid_to IS NULL",
+                tables.objective_display_names
+            ))
+            .fetch_all(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??
+        .into_iter()
+        .collect();
+
+        let close = format!(
+            "UPDATE {} SET valid_to = ? WHERE objective_name = ? AND valid_to IS NULL",
+            tables.objective_display_names
+        );
+        let insert = self.insert_sql(
+            "INSERT INTO",
+            &tables.objective_display_names,
+            &["objective_name", "display_name", "valid_from"],
+        );
+
+        for (name, objective) in objectives.iter() {
+            match open.get(name) {
+                Some(Some(current)) if *current == objective.display_name => continue,
+                Some(_) => {
+                    execute(query(&close).bind(timestamp).bind(name), &mut *conn, self).await?
+                }
+                None => (),
+            }
+
+            execute(
+                self.bind_tenant(
+                    query(&insert)
+                        .bind(name)
+                        .bind(&objective.display_name)
+                        .bind(timestamp),
+                ),
+                &mut *conn,
+                self,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// The display name every objective had at `at` by objective name.
+    /// Objectives first written after `at` are left out.
+    pub async fn display_names_at(
+        &self,
+        conn: &mut MySqlConnection,
+        at: DateTime<Utc>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let tables = self.tables()?;
+
+        let rows: Vec<(String, Option<String>)> = timeout(
+            self.query_timeout,
+            query_as(&format!(
+                "SELECT objective_name, display_name FROM {} \
+                WHERE valid_from <= ? AND (valid_to IS NULL OR valid_to > ?)",
+                tables.objective_display_names
+            ))
+            .bind(at)
+            .bind(at)
+            .fetch_all(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??;
+
+        Ok(rows
+            .into_iter()
+            .map(|(name, display_name)| {
+                let display_name = display_name.unwrap_or_else(|| name.clone());
+                (name, display_name)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,255 @@
+use std::{borrow::Cow, fmt, fmt::Write};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use sqlx::{query_as, MySqlConnection};
+
+use super::{Map, NameInterner, Objective, PlayerScore, SqlOptions, Stats};
+use crate::error::Error;
+
+const NONCE_LEN: usize = 12;
+
+/// A 256 bit key player names are encrypted with, using AES-256-GCM
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Parses a key written as 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        decode_hex(hex.trim())
+            .and_then(|x| x.try_into().ok())
+            .map(Self)
+            .ok_or(Error::InvalidEncryptionKey)
+    }
+
+    /// Encrypts `name` into the hex of the nonce followed by the ciphertext.
+    ///
+    /// The nonce is derived from the name, so a name always encrypts to the
+    /// same text and can still be used as a key and joined on. This gives
+    /// away which rows have the same player, but not who it is.
+    pub fn encrypt(&self, name: &str) -> String {
+        //the nonce key is derived so the aes key is never used with hmac
+        let nonce_key = Sha256::new()
+            .chain_update(b"player name nonce")
+            .chain_update(self.0)
+            .finalize();
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&nonce_key).expect("hmac takes any key length");
+        mac.update(name.as_bytes());
+        let nonce = mac.finalize().into_bytes();
+        let nonce = &nonce[..NONCE_LEN];
+
+        let ciphertext = Aes256Gcm::new(&self.0.into())
+            .encrypt(Nonce::from_slice(nonce), name.as_bytes())
+            .expect("encrypting into a vec can't fail");
+
+        let mut hex = String::with_capacity((NONCE_LEN + ciphertext.len()) * 2);
+        for byte in nonce.iter().chain(&ciphertext) {
+            write!(hex, "{:02x}", byte).unwrap();
+        }
+
+        hex
+    }
+
+    /// Decrypts a name encrypted with [`EncryptionKey::encrypt`]
+    pub fn decrypt(&self, hex: &str) -> Result<String, Error> {
+        let undecryptable = || Error::UndecryptablePlayerName(hex.to_string());
+
+        let bytes = decode_hex(hex).ok_or_else(undecryptable)?;
+        if bytes.len() < NONCE_LEN {
+            return Err(undecryptable());
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+
+        let name = Aes256Gcm::new(&self.0.into())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable())?;
+
+        String::from_utf8(name).map_err(|_| undecryptable())
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl SqlOptions {
+    /// The player name as it is written to the database
+    pub(super) fn stored_player_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match &self.encrypt_player_names {
+            Some(key) => Cow::Owned(key.encrypt(name)),
+            None => Cow::Borrowed(name),
+        }
+    }
+}
+
+impl Stats {
+    /// Reads the newest scores from a database written with
+    /// [`SqlOptions::encrypt_player_names`] set to `key`, decrypting the
+    /// player names.
+    ///
+    /// The newest scores are the rows with the latest `time` in the stats
+    /// table, or in the stats view when partitioning by date.
+    pub async fn decrypt_sql_export(
+        conn: &mut MySqlConnection,
+        key: &EncryptionKey,
+        opts: &SqlOptions,
+    ) -> Result<Stats, Error> {
+        let tables = opts.tables()?;
+
+        let rows: Vec<(String, Option<String>, Option<String>)> = query_as(&format!(
+            "SELECT objective_name, display_name, criteria_name FROM {}",
+            tables.objectives
+        ))
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let objectives: Map<String, Objective> = rows
+            .into_iter()
+            .map(|(name, display_name, criteria_name)| {
+                let objective = Objective {
+                    name: name.clone(),
+                    criteria_name: criteria_name.unwrap_or_else(|| "dummy".to_string()),
+                    display_auto_update: 0,
+                    display_name: display_name.unwrap_or_else(|| name.clone()),
+                    render_type: "integer".to_string(),
+                };
+
+                (name, objective)
+            })
+            .collect();
+
+        let rows: Vec<(String, String, i64)> = query_as(&format!(
+            "SELECT player_name, objective_name, CAST(score AS SIGNED) FROM {0} \
+            WHERE time = (SELECT MAX(time) FROM {0})",
+            tables.stats
+        ))
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut player_scores: Map<String, Vec<PlayerScore>> = Map::new();
+        let mut names = NameInterner::default();
+
+        for (player_name, objective_name, score) in rows {
+            let player_name = names.intern(&key.decrypt(&player_name)?);
+
+            player_scores
+                .entry(objective_name)
+                .or_default()
+                .push(PlayerScore {
+                    locked: 0,
+                    player_name,
+                    score,
+                });
+        }
+
+        Ok(Stats {
+            objectives,
+            player_scores,
+            player_order: names.into_order(),
+            teams: Vec::new(),
+        })
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    //from_str_radix would also take a sign like +f
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> EncryptionKey {
+        EncryptionKey::from_hex(&"0123456789abcdef".repeat(4)).unwrap()
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in ["ville", "", "Ünïcödé_名前", &"x".repeat(300)] {
+            assert_eq!(key().decrypt(&key().encrypt(name)).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn encryption_is_deterministic() {
+        assert_eq!(key().encrypt("ville"), key().encrypt("ville"));
+        assert_ne!(key().encrypt("ville"), key().encrypt("anna"));
+        assert_ne!(
+            key().encrypt("ville"),
+            EncryptionKey::new([7; 32]).encrypt("ville")
+        );
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let encrypted = key().encrypt("ville");
+
+        assert!(matches!(
+            EncryptionKey::new([7; 32]).decrypt(&encrypted),
+            Err(Error::UndecryptablePlayerName(x)) if x == encrypted
+        ));
+    }
+
+    #[test]
+    fn malformed_ciphertext_is_rejected() {
+        let encrypted = key().encrypt("ville");
+
+        for hex in [
+            &encrypted[1..],
+            &encrypted[..encrypted.len() - 2],
+            "",
+            "00ff",
+            &format!("{}zz", &encrypted[2..]),
+            &format!("+f{}", &encrypted[2..]),
+        ] {
+            assert!(
+                matches!(key().decrypt(hex), Err(Error::UndecryptablePlayerName(_))),
+                "{}",
+                hex
+            );
+        }
+    }
+
+    #[test]
+    fn keys_need_64_hex_digits() {
+        assert!(EncryptionKey::from_hex(&format!(" {} \n", "ab".repeat(32))).is_ok());
+
+        for hex in [
+            "ab".repeat(31),
+            "ab".repeat(33),
+            format!("{}a", "ab".repeat(31)),
+            format!("{}zz", "ab".repeat(31)),
+            format!("{}+a", "ab".repeat(31)),
+            String::new(),
+        ] {
+            assert!(
+                matches!(
+                    EncryptionKey::from_hex(&hex),
+                    Err(Error::InvalidEncryptionKey)
+                ),
+                "{}",
+                hex
+            );
+        }
+    }
+}
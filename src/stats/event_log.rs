@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, Connection, MySqlConnection};
+use uuid::Uuid;
+
+use super::{
+    schema::{add_tenant, TENANT_COLUMN},
+    sql::execute,
+    SqlOptions, Stats,
+};
+use crate::error::Error;
+
+impl SqlOptions {
+    /// The statement creating the append-only `score_events` table written
+    /// by [`Stats::write_to_sql_event_log`]
+    pub fn event_log_sql(&self) -> Result<String, Error> {
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+    event_id UUID NOT NULL,
+    timestamp DATETIME NOT NULL,
+    player_name VARCHAR(255) NOT NULL,
+    objective_name VARCHAR(255) NOT NULL,
+    score BIGINT NOT NULL,
+    {},
+    PRIMARY KEY (event_id, player_name, objective_name),
+    INDEX (timestamp)
+) ENGINE InnoDB PAGE_COMPRESSED = 1 PAGE_COMPRESSION_LEVEL = 9",
+            self.tables()?.score_events,
+            TENANT_COLUMN
+        ))
+    }
+
+    /// Creates the `score_events` table if it is missing, and gives one
+    /// created before rows were tagged with a tenant the column if needed
+    pub async fn create_event_log_table(&self, conn: &mut MySqlConnection) -> Result<(), Error> {
+        execute(query(&self.event_log_sql()?), &mut *conn, self).await?;
+
+        match self.tenant_id {
+            Some(_) => {
+                let table = self.tables()?.score_events;
+                self.apply(conn, &add_tenant(&table)).await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Stats {
+    /// Appends every score to the `score_events` table as one event, rows
+    /// are never updated or deleted so the table keeps the full history.
+    ///
+    /// The whole event is inserted in a single transaction. The latest
+    /// scores are the rows with the highest `timestamp`.
+    pub async fn write_to_sql_event_log(
+        &self,
+        conn: &mut MySqlConnection,
+        event_id: Uuid,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error> {
+        let insert_event = opts.insert_sql(
+            "INSERT INTO",
+            &opts.tables()?.score_events,
+            &[
+                "event_id",
+                "timestamp",
+                "player_name",
+                "objective_name",
+                "score",
+            ],
+        );
+        let event_id = event_id.hyphenated().to_string();
+
+        let mut transaction = conn.begin().await?;
+
+        for (obj_name, player_scores) in self.player_scores.iter() {
+            for player_score in player_scores {
+                execute(
+                    opts.bind_tenant(
+                        query(&insert_event)
+                            .bind(&event_id)
+                            .bind(timestamp)
+                            .bind(opts.stored_player_name(player_score.player_name()))
+                            .bind(obj_name)
+                            .bind(player_score.score),
+                    ),
+                    &mut *transaction,
+                    opts,
+                )
+                .await?;
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
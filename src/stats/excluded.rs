@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use async_std::future::timeout;
+use sqlx::{query, query_scalar, MySqlConnection};
+
+use super::{
+    schema::{add_tenant, excluded_players_table_sql},
+    sql::{execute, table_exists, SqlOptions},
+};
+use crate::error::Error;
+
+impl SqlOptions {
+    /// The players in the `excluded_players` table, whose scores are left
+    /// out of every write. Empty when the table doesn't exist.
+    pub async fn excluded_players(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> Result<HashSet<String>, Error> {
+        let tables = self.tables()?;
+
+        if !table_exists(&mut *conn, &tables.excluded_players).await? {
+            return Ok(HashSet::new());
+        }
+
+        let players: Vec<String> = timeout(
+            self.query_timeout,
+            query_scalar(&format!(
+                "SELECT player_name FROM {}",
+                tables.excluded_players
+            ))
+            .fetch_all(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??;
+
+        Ok(players.into_iter().collect())
+    }
+
+    /// Adds a player to `excluded_players`, creating the table when it
+    /// doesn't exist yet. Returns false when they were already excluded.
+    pub async fn exclude_player(
+        &self,
+        conn: &mut MySqlConnection,
+        player: &str,
+    ) -> Result<bool, Error> {
+        let tables = self.tables()?;
+
+        execute(
+            query(&excluded_players_table_sql(&tables)),
+            &mut *conn,
+            self,
+        )
+        .await?;
+        if self.tenant_id.is_some() {
+            self.apply(&mut *conn, &add_tenant(&tables.excluded_players))
+                .await?;
+        }
+
+        let inserted = timeout(
+            self.query_timeout,
+            self.bind_tenant(
+                query(&self.insert_sql(
+                    "INSERT IGNORE INTO",
+                    &tables.excluded_players,
+                    &["player_name"],
+                ))
+                .bind(player),
+            )
+            .execute(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??
+        .rows_affected();
+
+        Ok(inserted > 0)
+    }
+
+    /// Removes a player from `excluded_players` so their scores are written
+    /// again. Returns false when they weren't excluded.
+    pub async fn include_player(
+        &self,
+        conn: &mut MySqlConnection,
+        player: &str,
+    ) -> Result<bool, Error> {
+        let tables = self.tables()?;
+
+        if !table_exists(&mut *conn, &tables.excluded_players).await? {
+            return Ok(false);
+        }
+
+        let deleted = timeout(
+            self.query_timeout,
+            query(&format!(
+                "DELETE FROM {} WHERE player_name = ?",
+                tables.excluded_players
+            ))
+            .bind(player)
+            .execute(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??
+        .rows_affected();
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+
+use nbt::Map;
+
+use super::{ScoreLookup, Stats};
+use crate::error::Error;
+
+/// A parsed arithmetic expression over objective names
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(i64),
+    Objective(String),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(x) => x as f64,
+            Number::Float(x) => x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "{}", x),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Symbol(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+impl Stats {
+    /// Evaluates `expr` for every player, where identifiers are objective
+    /// names and a missing score counts as 0.
+    ///
+    /// Supports `+ - * /`, integer literals and parentheses. Arithmetic is
+    /// done on integers, division gives a float. Players for whom the
+    /// expression divides by zero are left out of the result. `name` is
+    /// only used in error messages.
+    pub fn computed_column(&self, name: &str, expr: &str) -> Result<Map<String, f64>, Error> {
+        let expr = Parser::new(name, expr)?.parse()?;
+        self.check_objectives(name, &expr)?;
+
+        let scores = self.score_lookup();
+
+        Ok(self
+            .player_names()
+            .into_iter()
+            .filter_map(|player| {
+                evaluate(&expr, &scores, player).map(|value| (player.to_string(), value.as_f64()))
+            })
+            .collect())
+    }
+
+    fn check_objectives(&self, name: &str, expr: &Expr) -> Result<(), Error> {
+        match expr {
+            Expr::Literal(_) => Ok(()),
+            Expr::Objective(objective) if self.objectives.contains_key(objective) => Ok(()),
+            Expr::Objective(objective) => {
+                let mut available: Vec<&str> = self.objectives.keys().map(String::as_str).collect();
+                available.sort_unstable();
+
+                Err(Error::InvalidExpression(format!(
+                    "{}: unknown objective {}, available objectives are {}",
+                    name,
+                    objective,
+                    available.join(", ")
+                )))
+            }
+            Expr::Negate(inner) => self.check_objectives(name, inner),
+            Expr::Binary(_, lhs, rhs) => {
+                self.check_objectives(name, lhs)?;
+                self.check_objectives(name, rhs)
+            }
+        }
+    }
+}
+
+/// `None` when dividing by zero
+fn evaluate(expr: &Expr, scores: &ScoreLookup, player: &str) -> Option<Number> {
+    match expr {
+        Expr::Literal(x) => Some(Number::Int(*x)),
+        Expr::Objective(objective) => Some(Number::Int(
+            scores
+                .get(&(objective.as_str(), player))
+                .copied()
+                .unwrap_or(0),
+        )),
+        Expr::Negate(inner) => Some(match evaluate(inner, scores, player)? {
+            Number::Int(x) => x
+                .checked_neg()
+                .map_or(Number::Float(-(x as f64)), Number::Int),
+            Number::Float(x) => Number::Float(-x),
+        }),
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = evaluate(lhs, scores, player)?;
+            let rhs = evaluate(rhs, scores, player)?;
+
+            match (op, lhs, rhs) {
+                (Op::Div, _, rhs) if rhs.as_f64() == 0.0 => None,
+                (Op::Div, lhs, rhs) => Some(Number::Float(lhs.as_f64() / rhs.as_f64())),
+                (op, Number::Int(lhs), Number::Int(rhs)) => {
+                    let result = match op {
+                        Op::Add => lhs.checked_add(rhs),
+                        Op::Sub => lhs.checked_sub(rhs),
+                        _ => lhs.checked_mul(rhs),
+                    };
+
+                    //overflowing integers continue as floats
+                    Some(result.map_or_else(|| float_op(*op, lhs as f64, rhs as f64), Number::Int))
+                }
+                (op, lhs, rhs) => Some(float_op(*op, lhs.as_f64(), rhs.as_f64())),
+            }
+        }
+    }
+}
+
+fn float_op(op: Op, lhs: f64, rhs: f64) -> Number {
+    Number::Float(match op {
+        Op::Add => lhs + rhs,
+        Op::Sub => lhs - rhs,
+        Op::Mul => lhs * rhs,
+        Op::Div => lhs / rhs,
+    })
+}
+
+struct Parser<'a> {
+    name: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(name: &'a str, expr: &str) -> Result<Self, Error> {
+        Ok(Self {
+            name,
+            tokens: tokenize(name, expr)?,
+            position: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr, Error> {
+        let expr = self.sum()?;
+
+        match self.tokens.get(self.position) {
+            None => Ok(expr),
+            Some(token) => Err(self.error(&format!("unexpected {}", token))),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.product()?;
+
+        while let Some(op) = self.next_op(&[('+', Op::Add), ('-', Op::Sub)]) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.unary()?;
+
+        while let Some(op) = self.next_op(&[('*', Op::Mul), ('/', Op::Div)]) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+
+        match token {
+            Some(Token::Symbol('-')) => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Some(Token::Number(x)) => Ok(Expr::Literal(x)),
+            Some(Token::Ident(objective)) => Ok(Expr::Objective(objective)),
+            Some(Token::Symbol('(')) => {
+                let expr = self.sum()?;
+
+                match self.tokens.get(self.position) {
+                    Some(Token::Symbol(')')) => {
+                        self.position += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(self.error("missing )")),
+                }
+            }
+            Some(token) => Err(self.error(&format!("unexpected {}", token))),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    fn next_op(&mut self, ops: &[(char, Op)]) -> Option<Op> {
+        let Some(Token::Symbol(symbol)) = self.tokens.get(self.position) else {
+            return None;
+        };
+
+        let op = ops.iter().find(|(c, _)| c == symbol)?.1;
+        self.position += 1;
+
+        Some(op)
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidExpression(format!("{}: {}", self.name, message))
+    }
+}
+
+fn tokenize(name: &str, expr: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '+' | '-' | '*' | '/' | '(' | ')' => tokens.push(Token::Symbol(c)),
+            c if c.is_ascii_digit() || is_ident_char(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_digit() || is_ident_char(*c))
+                {
+                    end = i + c.len_utf8();
+                }
+
+                let word = &expr[start..end];
+                tokens.push(match c.is_ascii_digit() {
+                    true => Token::Number(word.parse().map_err(|_| {
+                        Error::InvalidExpression(format!("{}: {} is not an integer", name, word))
+                    })?),
+                    false => Token::Ident(word.to_string()),
+                });
+            }
+            c => {
+                return Err(Error::InvalidExpression(format!(
+                    "{}: unexpected character {:?}",
+                    name, c
+                )))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Objective names can be namespaced like `minecraft.custom:minecraft.jump`
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats() -> Stats {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 4),
+                ("ville", "kills", 10),
+                ("anna", "deaths", 0),
+                ("anna", "kills", 3),
+                ("bob", "kills", 1),
+            ]
+            .into_iter(),
+        );
+
+        Stats::from_gzip_reader(&file[..]).unwrap()
+    }
+
+    fn value(expr: &str) -> f64 {
+        stats().computed_column("test", expr).unwrap()["ville"]
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        for (expr, expected) in [
+            ("1 + 2 * 3", 7.0),
+            ("10 - 4 - 3", 3.0),
+            ("12 / 3 / 2", 2.0),
+            ("kills - deaths * 2", 2.0),
+            ("kills / deaths + 1", 3.5),
+        ] {
+            assert_eq!(value(expr), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn parentheses_group() {
+        for (expr, expected) in [
+            ("(1 + 2) * 3", 9.0),
+            ("kills / (deaths + 1)", 2.0),
+            ("((kills))", 10.0),
+        ] {
+            assert_eq!(value(expr), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn unary_minus() {
+        for (expr, expected) in [
+            ("-deaths", -4.0),
+            ("-deaths + kills", 6.0),
+            ("2 - -3", 5.0),
+            ("--2", 2.0),
+            ("-(kills - deaths)", -6.0),
+            ("-2 * 3", -6.0),
+        ] {
+            assert_eq!(value(expr), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn dividing_by_zero_drops_the_player() {
+        let column = stats().computed_column("kd", "kills / deaths").unwrap();
+
+        assert_eq!(column.get("ville"), Some(&2.5));
+        //anna has 0 deaths and bob none at all, which counts as 0
+        assert_eq!(column.get("anna"), None);
+        assert_eq!(column.get("bob"), None);
+    }
+
+    #[test]
+    fn unknown_objective_lists_the_available_ones() {
+        let e = stats().computed_column("kd", "kills / jumps").unwrap_err();
+
+        assert!(
+            matches!(&e, Error::InvalidExpression(message)
+                if message == "kd: unknown objective jumps, available objectives are deaths, kills"),
+            "{:?}",
+            e
+        );
+    }
+
+    #[test]
+    fn overflowing_integers_continue_as_floats() {
+        assert_eq!(
+            value("9223372036854775807 + kills"),
+            9223372036854775807.0 + 10.0
+        );
+        assert_eq!(
+            value("9223372036854775807 * 2"),
+            9223372036854775807.0 * 2.0
+        );
+        assert_eq!(
+            value("-9223372036854775807 - kills"),
+            -9223372036854775807.0 - 10.0
+        );
+    }
+
+    #[test]
+    fn malformed_expressions_are_errors() {
+        for expr in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 2",
+            "kills $ 2",
+            "99999999999999999999",
+        ] {
+            assert!(
+                matches!(
+                    stats().computed_column("test", expr),
+                    Err(Error::InvalidExpression(_))
+                ),
+                "{}",
+                expr
+            );
+        }
+    }
+}
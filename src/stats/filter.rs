@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use glob::Pattern;
+use regex::Regex;
+
+use super::{split_namespace, ComputedColumn, Criteria, Map, Stats};
+
+/// Restricts which objectives and players end up in an export
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    /// Names of the objectives to keep, every objective is kept when `None`
+    pub objectives: Option<Vec<String>>,
+    /// Names of the players to keep, every player is kept when `None`
+    pub players: Option<Vec<String>>,
+    /// When not empty, only players matching one of these are kept
+    pub include_players: Vec<Regex>,
+    /// Players matching any of these are dropped, like bot accounts
+    pub exclude_players: Vec<Regex>,
+    /// Glob patterns of the namespaces to keep, objectives without a
+    /// namespace are dropped when this is set
+    pub namespaces: Option<Vec<Pattern>>,
+    /// When not empty, only objectives whose criteria matches one of these
+    /// [`Criteria::matches`](super::Criteria::matches) selectors are kept
+    pub criteria: Vec<String>,
+    /// Objectives whose criteria matches any of these selectors are dropped,
+    /// even when they match `criteria`
+    pub exclude_criteria: Vec<String>,
+    /// Scores below this are dropped, unless their objective is in
+    /// `min_scores`
+    pub min_score: Option<i64>,
+    /// Thresholds of single objectives, overriding `min_score`
+    pub min_scores: HashMap<String, i64>,
+}
+
+impl ExportOptions {
+    /// Whether these options keep everything
+    pub fn is_empty(&self) -> bool {
+        self.objectives.is_none()
+            && self.players.is_none()
+            && self.include_players.is_empty()
+            && self.exclude_players.is_empty()
+            && self.namespaces.is_none()
+            && self.criteria.is_empty()
+            && self.exclude_criteria.is_empty()
+            && self.min_score.is_none()
+            && self.min_scores.is_empty()
+    }
+
+    pub fn keeps_objective(&self, name: &str) -> bool {
+        let keeps_name = match &self.objectives {
+            Some(objectives) => objectives.iter().any(|x| x == name),
+            None => true,
+        };
+
+        keeps_name
+            && match (&self.namespaces, split_namespace(name).0) {
+                (Some(patterns), Some(namespace)) => patterns.iter().any(|x| x.matches(namespace)),
+                (Some(_), None) => false,
+                (None, _) => true,
+            }
+    }
+
+    pub fn keeps_criteria(&self, criteria: &Criteria) -> bool {
+        (self.criteria.is_empty() || self.criteria.iter().any(|x| criteria.matches(x)))
+            && !self.exclude_criteria.iter().any(|x| criteria.matches(x))
+    }
+
+    pub fn keeps_player(&self, name: &str) -> bool {
+        let listed = match &self.players {
+            Some(players) => players.iter().any(|x| x == name),
+            None => true,
+        };
+
+        listed
+            && (self.include_players.is_empty()
+                || self.include_players.iter().any(|x| x.is_match(name)))
+            && !self.exclude_players.iter().any(|x| x.is_match(name))
+    }
+
+    /// The include and exclude patterns that match none of the players of
+    /// `stats`, which are probably mistyped
+    pub fn unmatched_player_patterns(&self, stats: &Stats) -> Vec<&Regex> {
+        let players = stats.player_names();
+
+        self.include_players
+            .iter()
+            .chain(&self.exclude_players)
+            .filter(|pattern| !players.iter().any(|x| pattern.is_match(x)))
+            .collect()
+    }
+
+    /// Whether `score` reaches the threshold of `objective`
+    pub fn keeps_score(&self, objective: &str, score: i64) -> bool {
+        match self.min_scores.get(objective).or(self.min_score.as_ref()) {
+            Some(min) => score >= *min,
+            None => true,
+        }
+    }
+}
+
+impl Stats {
+    /// Drops every objective and score `opts` doesn't keep
+    pub fn filter(&mut self, opts: &ExportOptions) {
+        let objectives = &self.objectives;
+        self.player_scores.retain(|name, _| {
+            opts.keeps_objective(name)
+                && objectives
+                    .get(name)
+                    .is_none_or(|x| opts.keeps_criteria(&x.criteria()))
+        });
+        self.objectives
+            .retain(|name, x| opts.keeps_objective(name) && opts.keeps_criteria(&x.criteria()));
+
+        for (objective, player_scores) in self.player_scores.iter_mut() {
+            player_scores.retain(|x| {
+                opts.keeps_player(x.player_name()) && opts.keeps_score(objective, x.score)
+            });
+        }
+
+        self.player_scores
+            .retain(|_, player_scores| !player_scores.is_empty());
+    }
+
+    /// Drops the scores of players whose every score is 0 and who have no
+    /// nonzero value in `computed`, like the computed columns of
+    /// [`CsvOptions`](super::CsvOptions). Returns how many players were
+    /// dropped.
+    pub fn drop_empty_players(&mut self, computed: &[ComputedColumn]) -> usize {
+        let mut keep: HashSet<Arc<str>> = HashSet::new();
+        let mut empty: HashSet<Arc<str>> = HashSet::new();
+
+        for score in self.player_scores.values().flatten() {
+            match score.score != 0 {
+                true => keep.insert(score.player_name.clone()),
+                false => empty.insert(score.player_name.clone()),
+            };
+        }
+
+        for (_, values) in computed {
+            keep.extend(
+                values
+                    .iter()
+                    .filter(|(_, value)| **value != 0.0)
+                    .map(|(player, _)| Arc::from(player.as_str())),
+            );
+        }
+
+        empty.retain(|player| !keep.contains(player));
+
+        for player_scores in self.player_scores.values_mut() {
+            player_scores.retain(|x| !empty.contains(x.player_name()));
+        }
+
+        self.player_scores
+            .retain(|_, player_scores| !player_scores.is_empty());
+
+        empty.len()
+    }
+
+    /// A copy of the stats with only the objectives and scores `opts` keeps
+    pub fn filtered(&self, opts: &ExportOptions) -> Stats {
+        let mut stats = self.clone();
+        stats.filter(opts);
+
+        stats
+    }
+
+    /// The scores of a single player keyed by objective name, `None` when the
+    /// player has no scores at all
+    pub fn scores_of(&self, player: &str) -> Option<Map<&str, i64>> {
+        let scores: Map<&str, i64> = self
+            .player_scores
+            .iter()
+            .filter_map(|(objective, player_scores)| {
+                player_scores
+                    .iter()
+                    .find(|x| x.player_name() == player)
+                    .map(|x| (objective.as_str(), x.score))
+            })
+            .collect();
+
+        match scores.is_empty() {
+            true => None,
+            false => Some(scores),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn players_below_the_threshold_are_dropped_as_empty() {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("anna", "deaths", 12),
+                ("anna", "kills", 3),
+                ("bob", "deaths", 2),
+                ("bob", "kills", 1),
+                ("carl", "deaths", 0),
+                ("carl", "kills", 0),
+            ]
+            .into_iter(),
+        );
+        let mut stats = Stats::from_gzip_reader(&file[..]).unwrap();
+
+        stats.filter(&ExportOptions {
+            min_score: Some(3),
+            ..Default::default()
+        });
+        //bob lost every score to the threshold and carl's zeros went with it
+        assert_eq!(stats.scores_of("bob"), None);
+        assert_eq!(stats.scores_of("carl"), None);
+
+        assert_eq!(stats.drop_empty_players(&[]), 0);
+        assert_eq!(
+            stats.scores_of("anna"),
+            Some(Map::from_iter([("deaths", 12), ("kills", 3)]))
+        );
+    }
+
+    #[test]
+    fn zeros_kept_by_the_threshold_are_dropped_as_empty() {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("anna", "deaths", 12),
+                ("anna", "kills", -1),
+                ("bob", "deaths", 0),
+                ("bob", "kills", -4),
+            ]
+            .into_iter(),
+        );
+        let mut stats = Stats::from_gzip_reader(&file[..]).unwrap();
+
+        stats.filter(&ExportOptions {
+            min_score: Some(0),
+            ..Default::default()
+        });
+        //the negative scores are gone, leaving bob with only a 0
+        assert_eq!(
+            stats.scores_of("bob"),
+            Some(Map::from_iter([("deaths", 0)]))
+        );
+
+        assert_eq!(stats.drop_empty_players(&[]), 1);
+        assert_eq!(stats.scores_of("bob"), None);
+        assert_eq!(
+            stats.scores_of("anna"),
+            Some(Map::from_iter([("deaths", 12)]))
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, Write};
+
+use csv::ByteRecord;
+
+use super::{CsvOptions, Stats};
+use crate::error::Error;
+
+impl Stats {
+    /// Writes a csv with a row per objective counting its scores in `bins`
+    /// equal width bins from the lowest to the highest score, after
+    /// `Objective`, `Min` and `Max` columns. The highest score is counted in
+    /// the last bin, and objectives without scores get empty bounds and
+    /// zero counts.
+    pub fn write_histogram_csv(&self, w: impl Write, bins: usize) -> Result<(), Error> {
+        if bins == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a histogram needs at least one bin",
+            )
+            .into());
+        }
+
+        let titles = self.titles();
+
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut buffer = itoa::Buffer::new();
+        let mut counts = vec![0usize; bins];
+
+        record.push_field(b"Objective");
+        record.push_field(b"Min");
+        record.push_field(b"Max");
+        for bin in 1..=bins {
+            record.push_field(format!("Bin {}", bin).as_bytes());
+        }
+        w.write_byte_record(&record)?;
+
+        for column in self.columns(&titles, &CsvOptions::default(), false)? {
+            let Some(objective) = &column.objective else {
+                continue;
+            };
+
+            let scores: Vec<i64> = self
+                .player_scores
+                .get(objective)
+                .into_iter()
+                .flatten()
+                .map(|x| x.score)
+                .collect();
+
+            record.clear();
+            record.push_field(column.name.as_bytes());
+
+            counts.fill(0);
+
+            match (scores.iter().min(), scores.iter().max()) {
+                (Some(&min), Some(&max)) => {
+                    record.push_field(buffer.format(min).as_bytes());
+                    record.push_field(buffer.format(max).as_bytes());
+
+                    //i128 so the width of the whole i64 range fits
+                    let width = max as i128 - min as i128;
+                    for score in scores {
+                        let bin = match width {
+                            0 => 0,
+                            _ => ((score as i128 - min as i128) * bins as i128 / width) as usize,
+                        };
+                        counts[bin.min(bins - 1)] += 1;
+                    }
+                }
+                _ => {
+                    record.push_field(b"");
+                    record.push_field(b"");
+                }
+            }
+
+            for count in &counts {
+                record.push_field(buffer.format(*count).as_bytes());
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn histogram(scores: &[(&str, &str, i32)], bins: usize) -> Result<String, Error> {
+        let nbt = fixture::scoreboard(
+            &[
+                ("deaths", "deathCount"),
+                ("kills", "playerKillCount"),
+                ("walk", "dummy"),
+            ],
+            scores.iter().copied(),
+        );
+        let mut out = Vec::new();
+
+        Stats::from_gzip_reader(&nbt[..])
+            .unwrap()
+            .write_histogram_csv(&mut out, bins)?;
+
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn scores_are_counted_in_equal_bins() {
+        let scores = [
+            ("a", "deaths", 0),
+            ("b", "deaths", 1),
+            ("c", "deaths", 5),
+            ("d", "deaths", 9),
+            ("e", "deaths", 10),
+            ("a", "kills", 4),
+        ];
+
+        //the highest score goes in the last bin, a single score in the first
+        assert_eq!(
+            histogram(&scores, 2).unwrap(),
+            "Objective,Min,Max,Bin 1,Bin 2\n\
+            deaths,0,10,2,3\n\
+            kills,4,4,1,0\n\
+            walk,,,0,0\n"
+        );
+    }
+
+    #[test]
+    fn the_whole_score_range_fits() {
+        let scores = [
+            ("a", "deaths", i32::MIN),
+            ("b", "deaths", 0),
+            ("c", "deaths", i32::MAX),
+        ];
+
+        assert!(histogram(&scores, 3)
+            .unwrap()
+            .contains("\ndeaths,-2147483648,2147483647,1,1,1\n"));
+    }
+
+    #[test]
+    fn zero_bins_are_refused() {
+        assert!(matches!(
+            histogram(&[], 0),
+            Err(Error::IOError(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use sqlx::{query, Connection, Executor, PgConnection};
+
+use super::Stats;
+use crate::error::Error;
+
+/// Creates the snapshot table and the GIN index letting `scores @> ...`
+/// and `scores ? ...` queries use an index
+const CREATE_PLAYER_SNAPSHOTS: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS player_snapshots (
+    player_name VARCHAR(255) NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    scores JSONB NOT NULL,
+    PRIMARY KEY (player_name, timestamp)
+)",
+    "CREATE INDEX IF NOT EXISTS player_snapshots_scores ON player_snapshots USING GIN (scores)",
+];
+
+const INSERT_PLAYER_SNAPSHOT: &str =
+    "INSERT INTO player_snapshots (player_name, timestamp, scores) VALUES ($1, $2, $3::jsonb)";
+
+impl Stats {
+    /// Inserts a row per player into the PostgreSQL table
+    /// `player_snapshots (player_name, timestamp, scores JSONB)`, creating
+    /// it if needed, where `scores` is like `{"deaths":7,"kills":42}`.
+    ///
+    /// All rows are inserted in one transaction.
+    pub async fn write_to_postgres_jsonb(
+        &self,
+        conn: &mut PgConnection,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        for statement in CREATE_PLAYER_SNAPSHOTS {
+            conn.execute(statement).await?;
+        }
+
+        let mut players: BTreeMap<&str, Map<String, Value>> = BTreeMap::new();
+        for (objective, scores) in self.player_scores.iter() {
+            for score in scores {
+                players
+                    .entry(score.player_name())
+                    .or_default()
+                    .insert(objective.clone(), score.score.into());
+            }
+        }
+
+        let mut transaction = conn.begin().await?;
+
+        for (player, scores) in players {
+            query(INSERT_PLAYER_SNAPSHOT)
+                .bind(player)
+                .bind(timestamp)
+                .bind(Value::Object(scores).to_string())
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
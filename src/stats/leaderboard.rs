@@ -0,0 +1,24 @@
+use super::Stats;
+
+impl Stats {
+    /// The `limit` highest scores of an objective as `(player, score)` pairs,
+    /// ties are ordered by player name. `None` when there is no such objective.
+    pub fn leaderboard(&self, objective: &str, limit: usize) -> Option<Vec<(&str, i64)>> {
+        if !self.objectives.contains_key(objective) {
+            return None;
+        }
+
+        let mut entries: Vec<(&str, i64)> = self
+            .player_scores
+            .get(objective)
+            .into_iter()
+            .flatten()
+            .map(|x| (x.player_name(), x.score))
+            .collect();
+
+        entries.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        entries.truncate(limit);
+
+        Some(entries)
+    }
+}
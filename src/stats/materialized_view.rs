@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use super::{SqlDialect, Stats};
+use crate::error::Error;
+
+/// The latest score of every player in every objective with its rank in
+/// the objective, tied scores share a rank
+const LEADERBOARD_SELECT: &str = "SELECT
+    o.objective_name,
+    o.display_name,
+    p.player_name,
+    s.score,
+    s.time,
+    RANK() OVER (PARTITION BY s.objective_name ORDER BY s.score DESC) AS score_rank
+FROM stats s
+JOIN (
+    SELECT player_name, objective_name, MAX(time) AS time
+    FROM stats
+    GROUP BY player_name, objective_name
+) latest ON latest.player_name = s.player_name
+    AND latest.objective_name = s.objective_name
+    AND latest.time = s.time
+JOIN players p ON p.player_name = s.player_name
+JOIN objectives o ON o.objective_name = s.objective_name";
+
+impl Stats {
+    /// Writes the statements creating a `leaderboard_summary` of the latest
+    /// score of every player in every objective and its rank, indexed for
+    /// top N queries like `WHERE objective_name = ? AND score_rank <= 10`.
+    ///
+    /// PostgreSQL gets a materialized view, MySQL a table created from the
+    /// same select since it has no materialized views. Running the
+    /// statements again refreshes the summary with the latest stats.
+    pub fn write_sql_materialized_view(
+        &self,
+        mut w: impl Write,
+        dialect: SqlDialect,
+    ) -> Result<(), Error> {
+        match dialect {
+            SqlDialect::MySql => write!(
+                w,
+                "DROP TABLE IF EXISTS leaderboard_summary;
+
+CREATE TABLE leaderboard_summary (INDEX leaderboard_summary_rank (objective_name, score_rank))
+{};
+",
+                LEADERBOARD_SELECT
+            )?,
+            SqlDialect::Postgres => write!(
+                w,
+                "CREATE MATERIALIZED VIEW IF NOT EXISTS leaderboard_summary AS
+{};
+
+CREATE INDEX IF NOT EXISTS leaderboard_summary_rank ON leaderboard_summary (objective_name, score_rank);
+
+REFRESH MATERIALIZED VIEW leaderboard_summary;
+",
+                LEADERBOARD_SELECT
+            )?,
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
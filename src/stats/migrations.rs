@@ -0,0 +1,398 @@
+use chrono::Utc;
+use sqlx::{query, query_scalar, Connection, MySqlConnection};
+
+use super::{
+    schema::{
+        add_region, add_tenant, add_world, objective_display_names_table_sql,
+        objective_totals_table_sql, objectives_table_sql, SchemaChange, Tables,
+    },
+    sql::{execute, table_exists, SqlOptions},
+};
+use crate::error::Error;
+
+/// The schema version the migrations bring a database to
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The statements of a step given the existing stats tables, which are
+/// the monthly tables when partitioning by date. Every statement can run
+/// again without changing anything, columns are only added to tables
+/// that don't have them yet.
+type MigrationSql = fn(&SqlOptions, &Tables, &[String]) -> Result<Vec<SchemaChange>, Error>;
+
+/// A step of the schema, its version is its place in `MIGRATIONS`
+struct Migration {
+    description: &'static str,
+    statements: MigrationSql,
+}
+
+/// Only ever appended to, installs record how far into it they are
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "create the objectives, players and stats tables",
+        statements: |opts, tables, _| {
+            let mut statements = vec![
+                SchemaChange::Statement(objectives_table_sql(tables)),
+                SchemaChange::Statement(opts.players_table_sql(tables)),
+            ];
+
+            //monthly tables are created when they are first written to
+            if !opts.partition_by_date {
+                statements.push(SchemaChange::Statement(
+                    opts.stats_table_sql(tables, &tables.stats)?,
+                ));
+            }
+
+            Ok(statements)
+        },
+    },
+    Migration {
+        description: "tag stats with the region of the server",
+        statements: |_, _, stats_tables| Ok(stats_tables.iter().map(|x| add_region(x)).collect()),
+    },
+    Migration {
+        description: "tag stats with the world",
+        statements: |_, _, stats_tables| Ok(stats_tables.iter().map(|x| add_world(x)).collect()),
+    },
+    Migration {
+        description: "create the objective_totals table",
+        statements: |_, tables, _| {
+            Ok(vec![SchemaChange::Statement(objective_totals_table_sql(
+                tables,
+            ))])
+        },
+    },
+    Migration {
+        description: "create the objective_display_names table",
+        statements: |_, tables, _| {
+            Ok(vec![SchemaChange::Statement(
+                objective_display_names_table_sql(tables),
+            )])
+        },
+    },
+    Migration {
+        description: "tag rows with a tenant",
+        statements: |_, tables, stats_tables| {
+            Ok([
+                &tables.objectives,
+                &tables.players,
+                &tables.objective_totals,
+                &tables.objective_display_names,
+            ]
+            .into_iter()
+            .chain(stats_tables)
+            .map(|x| add_tenant(x))
+            .collect())
+        },
+    },
+];
+
+/// A migration that hasn't been applied to a database yet
+#[derive(Debug, Clone)]
+pub struct PendingMigration {
+    pub version: u32,
+    pub description: &'static str,
+    pub statements: Vec<String>,
+    changes: Vec<SchemaChange>,
+}
+
+impl SqlOptions {
+    /// The schema version recorded in the database, 0 for databases set
+    /// up before versions were recorded
+    pub async fn schema_version(&self, conn: &mut MySqlConnection) -> Result<u32, Error> {
+        let tables = self.tables()?;
+
+        if !table_exists(&mut *conn, &tables.schema_migrations).await? {
+            return Ok(0);
+        }
+
+        let version: i64 = query_scalar(&format!(
+            "SELECT CAST(COALESCE(MAX(version), 0) AS SIGNED) FROM {}",
+            tables.schema_migrations
+        ))
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(version as u32)
+    }
+
+    /// Fails with [`Error::SchemaTooNew`] when the database was upgraded
+    /// by a newer version of this crate, whose schema writes may not fit
+    pub async fn check_schema_version(&self, conn: &mut MySqlConnection) -> Result<(), Error> {
+        match self.schema_version(conn).await? {
+            version if version > SCHEMA_VERSION => {
+                Err(Error::SchemaTooNew(version, SCHEMA_VERSION))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The migrations the database is missing, in the order they apply
+    pub async fn pending_migrations(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> Result<Vec<PendingMigration>, Error> {
+        let version = self.schema_version(&mut *conn).await?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::SchemaTooNew(version, SCHEMA_VERSION));
+        }
+
+        let stats_tables = match self.partition_by_date {
+            true => self.partitions(&mut *conn).await?,
+            false => vec![self.tables()?.stats],
+        };
+
+        let mut pending = self.migrations_after(version, &stats_tables)?;
+
+        //tables set up before versions were recorded may have some columns
+        for migration in pending.iter_mut() {
+            let mut changes = Vec::new();
+            for change in migration.changes.drain(..) {
+                if !self.is_applied(&mut *conn, &change).await? {
+                    changes.push(change);
+                }
+            }
+            migration.statements = changes.iter().map(SchemaChange::sql).collect();
+            migration.changes = changes;
+        }
+
+        Ok(pending)
+    }
+
+    /// The migrations newer than `version` given the existing stats tables
+    fn migrations_after(
+        &self,
+        version: u32,
+        stats_tables: &[String],
+    ) -> Result<Vec<PendingMigration>, Error> {
+        let tables = self.tables()?;
+
+        MIGRATIONS
+            .iter()
+            .enumerate()
+            .map(|(i, migration)| (i as u32 + 1, migration))
+            .filter(|(migration_version, _)| *migration_version > version)
+            .map(|(version, migration)| {
+                let changes = (migration.statements)(self, &tables, stats_tables)?;
+
+                Ok(PendingMigration {
+                    version,
+                    description: migration.description,
+                    statements: changes.iter().map(SchemaChange::sql).collect(),
+                    changes,
+                })
+            })
+            .collect()
+    }
+
+    /// Applies the pending migrations one at a time, recording each in
+    /// `schema_migrations`, and returns them.
+    ///
+    /// Every migration runs in its own transaction, but MySQL commits
+    /// schema changes right away so the statements are written to be
+    /// repeatable instead, and columns are only added when missing. A migration that fails halfway is applied again
+    /// by the next upgrade.
+    pub async fn migrate(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> Result<Vec<PendingMigration>, Error> {
+        self.migrate_to(conn, SCHEMA_VERSION).await
+    }
+
+    /// Like [`SqlOptions::migrate`], stopping after `target`
+    async fn migrate_to(
+        &self,
+        conn: &mut MySqlConnection,
+        target: u32,
+    ) -> Result<Vec<PendingMigration>, Error> {
+        let mut pending = self.pending_migrations(&mut *conn).await?;
+        pending.retain(|x| x.version <= target);
+        let tables = self.tables()?;
+
+        execute(
+            query(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+    version INT UNSIGNED NOT NULL PRIMARY KEY,
+    description VARCHAR(255),
+    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+)",
+                tables.schema_migrations
+            )),
+            &mut *conn,
+            self,
+        )
+        .await?;
+
+        let record = format!(
+            "INSERT INTO {} (version, description, applied_at) VALUES (?,?,?)",
+            tables.schema_migrations
+        );
+
+        for migration in pending.iter() {
+            let mut transaction = conn.begin().await?;
+
+            //a column may have been added by an earlier migration's CREATE
+            for change in migration.changes.iter() {
+                self.apply(&mut transaction, change).await?;
+            }
+
+            execute(
+                query(&record)
+                    .bind(migration.version)
+                    .bind(migration.description)
+                    .bind(Utc::now()),
+                &mut *transaction,
+                self,
+            )
+            .await?;
+
+            transaction.commit().await?;
+        }
+
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn versions(migrations: &[PendingMigration]) -> Vec<u32> {
+        migrations.iter().map(|x| x.version).collect()
+    }
+
+    #[test]
+    fn fresh_install_runs_every_migration() {
+        let opts = SqlOptions::default();
+
+        let pending = opts.migrations_after(0, &["stats".to_string()]).unwrap();
+
+        assert_eq!(versions(&pending), (1..=SCHEMA_VERSION).collect::<Vec<_>>());
+        assert!(pending[0].statements[0].starts_with("CREATE TABLE IF NOT EXISTS objectives"));
+    }
+
+    #[test]
+    fn upgrade_runs_only_newer_migrations() {
+        let opts = SqlOptions::default();
+
+        let pending = opts.migrations_after(3, &["stats".to_string()]).unwrap();
+
+        assert_eq!(versions(&pending), (4..=SCHEMA_VERSION).collect::<Vec<_>>());
+        assert!(opts
+            .migrations_after(SCHEMA_VERSION, &["stats".to_string()])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn every_monthly_table_is_migrated() {
+        let opts = SqlOptions {
+            partition_by_date: true,
+            ..Default::default()
+        };
+        let months = ["stats_2026_09".to_string(), "stats_2026_10".to_string()];
+
+        let pending = opts.migrations_after(0, &months).unwrap();
+
+        //the monthly tables are created on their first write
+        assert_eq!(pending[0].statements.len(), 2);
+        assert_eq!(pending[1].statements.len(), 2);
+        assert!(pending[1].statements[1].starts_with("ALTER TABLE stats_2026_10"));
+    }
+
+    #[test]
+    fn every_statement_is_repeatable() {
+        let opts = SqlOptions::default();
+
+        for migration in opts.migrations_after(0, &["stats".to_string()]).unwrap() {
+            for change in migration.changes {
+                //MySQL has no ADD COLUMN IF NOT EXISTS, additions are checked instead
+                match change {
+                    SchemaChange::Statement(statement) => {
+                        assert!(statement.contains("IF NOT EXISTS"), "{}", statement)
+                    }
+                    SchemaChange::AddColumn { definition, .. } => {
+                        assert!(!definition.contains("IF NOT EXISTS"), "{}", definition)
+                    }
+                    SchemaChange::AddIndex { .. } => {}
+                }
+            }
+        }
+    }
+
+    /// Connects to the disposable MySQL or MariaDB database in
+    /// `NBTTOOL_TEST_MYSQL_URL` and drops the tables of `prefix`, `None`
+    /// when no database is configured
+    fn disposable(prefix: &str) -> Option<(SqlOptions, MySqlConnection)> {
+        let url = std::env::var("NBTTOOL_TEST_MYSQL_URL").ok()?;
+        let opts = SqlOptions {
+            schema_prefix: Some(prefix.to_string()),
+            ..Default::default()
+        };
+        let tables = opts.tables().unwrap();
+
+        let mut conn = block_on(MySqlConnection::connect(&url)).unwrap();
+        for table in [
+            &tables.players,
+            &tables.objectives,
+            &tables.stats,
+            &tables.objective_totals,
+            &tables.objective_display_names,
+            &tables.schema_migrations,
+        ] {
+            block_on(query(&format!("DROP TABLE IF EXISTS {}", table)).execute(&mut conn)).unwrap();
+        }
+
+        Some((opts, conn))
+    }
+
+    #[test]
+    fn fresh_install_against_a_database() {
+        let Some((opts, mut conn)) = disposable("migrations_fresh") else {
+            return;
+        };
+
+        block_on(async {
+            let applied = opts.migrate(&mut conn).await.unwrap();
+            assert_eq!(versions(&applied), (1..=SCHEMA_VERSION).collect::<Vec<_>>());
+            assert_eq!(
+                opts.schema_version(&mut conn).await.unwrap(),
+                SCHEMA_VERSION
+            );
+
+            assert!(opts.migrate(&mut conn).await.unwrap().is_empty());
+            opts.check_schema_version(&mut conn).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn stepwise_upgrade_against_a_database() {
+        let Some((opts, mut conn)) = disposable("migrations_stepwise") else {
+            return;
+        };
+        let tables = opts.tables().unwrap();
+
+        block_on(async {
+            for target in 1..=SCHEMA_VERSION {
+                let applied = opts.migrate_to(&mut conn, target).await.unwrap();
+                assert_eq!(versions(&applied), vec![target]);
+                assert_eq!(opts.schema_version(&mut conn).await.unwrap(), target);
+            }
+
+            //a database upgraded by a newer release is refused
+            query(&format!(
+                "INSERT INTO {} (version, description) VALUES (?, 'from the future')",
+                tables.schema_migrations
+            ))
+            .bind(SCHEMA_VERSION + 1)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+            assert!(matches!(
+                opts.check_schema_version(&mut conn).await,
+                Err(Error::SchemaTooNew(version, SCHEMA_VERSION)) if version == SCHEMA_VERSION + 1
+            ));
+        });
+    }
+}
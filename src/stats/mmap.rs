@@ -0,0 +1,163 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::Read,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use memmap2::Mmap;
+
+use super::{NameMerge, NameNormalization, Stats};
+use crate::error::Error;
+
+/// Files modified more recently than this may still be written to, and are
+/// read normally instead of mapped
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+impl Stats {
+    /// Parses the gzipped nbt file at `path` through a memory map, which is
+    /// considerably faster than reading on some network filesystems.
+    ///
+    /// A mapped file that shrinks while it is read ends the process with
+    /// SIGBUS, so files modified in the last couple of seconds are read
+    /// normally instead of mapped. The file is read again normally when it
+    /// can't be mapped, when its size or modification time changed while
+    /// the map was decoded, or when decoding fails with an I/O error. Any
+    /// other decode error is returned as is.
+    pub fn from_path_mmap(path: &Path) -> Result<Self, Error> {
+        Ok(Self::from_path_mmap_normalized(path, &NameNormalization::default())?.0)
+    }
+
+    /// Like [`Stats::from_path_mmap`], normalizing the player names while
+    /// parsing like [`Stats::from_gzip_reader_normalized`]
+    pub fn from_path_mmap_normalized(
+        path: &Path,
+        normalization: &NameNormalization,
+    ) -> Result<(Self, Vec<NameMerge>), Error> {
+        let parse = |src: &mut dyn Read| Self::from_gzip_reader_normalized(src, normalization);
+
+        let mut file = File::open(path)?;
+        let before = file.metadata()?;
+
+        if recently_modified(&before) {
+            return parse(&mut file);
+        }
+
+        // SAFETY: the map is only read from while decoding and is dropped
+        // right after, files that are still being written aren't mapped and
+        // a concurrent rewrite is noticed from the metadata
+        let map = match unsafe { Mmap::map(&file) } {
+            Ok(map) => map,
+            Err(_) => return parse(&mut file),
+        };
+
+        let result = match map.len() as u64 == before.len() {
+            true => parse(&mut &map[..]),
+            false => return parse(&mut File::open(path)?),
+        };
+        drop(map);
+
+        if changed(&before, &fs::metadata(path)?) {
+            return parse(&mut File::open(path)?);
+        }
+
+        match result {
+            Err(e) if is_io_error(&e) => parse(&mut File::open(path)?),
+            result => result,
+        }
+    }
+}
+
+fn recently_modified(metadata: &Metadata) -> bool {
+    match metadata.modified() {
+        Ok(modified) => SystemTime::now()
+            .duration_since(modified)
+            .map_or(true, |age| age < SETTLE_TIME),
+        //without a modification time changes can't be noticed either
+        Err(_) => true,
+    }
+}
+
+fn changed(before: &Metadata, after: &Metadata) -> bool {
+    before.len() != after.len() || before.modified().ok() != after.modified().ok()
+}
+
+/// Errors a rewrite of the mapped file shows up as, like an unexpected end
+/// of the gzip stream
+fn is_io_error(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::IOError(_) | Error::NBTError(nbt::Error::IoError(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::FileTimes;
+
+    use super::*;
+    use crate::fixture;
+
+    /// A scoreboard file old enough to be mapped
+    fn settled(test: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = fixture::temp_dir(test).join("scoreboard.dat");
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(SystemTime::now() - SETTLE_TIME * 10))
+            .unwrap();
+        path
+    }
+
+    fn scoreboard() -> Vec<u8> {
+        fixture::scoreboard(
+            &[("deaths", "deathCount")],
+            [("ville", "deaths", 3)].into_iter(),
+        )
+    }
+
+    #[test]
+    fn mapped_file_is_parsed() {
+        let path = settled("mmap_parsed", &scoreboard());
+
+        let stats = Stats::from_path_mmap(&path).unwrap();
+
+        assert_eq!(stats.player_scores()["deaths"][0].score(), 3);
+    }
+
+    #[test]
+    fn recently_modified_file_is_read_normally() {
+        let path = fixture::temp_dir("mmap_recent").join("scoreboard.dat");
+        fs::write(&path, scoreboard()).unwrap();
+
+        assert!(recently_modified(&fs::metadata(&path).unwrap()));
+        assert_eq!(
+            Stats::from_path_mmap(&path).unwrap().player_scores()["deaths"][0].score(),
+            3
+        );
+    }
+
+    #[test]
+    fn truncated_file_is_an_io_error() {
+        let file = scoreboard();
+        let path = settled("mmap_truncated", &file[..file.len() / 2]);
+
+        let e = Stats::from_path_mmap(&path).unwrap_err();
+
+        assert!(is_io_error(&e), "{:?}", e);
+    }
+
+    #[test]
+    fn other_decode_errors_are_returned() {
+        //a valid gzip stream of something that isn't a compound
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, &[0x01, 0x00, 0x00, 0x00]).unwrap();
+        let path = settled("mmap_not_nbt", &gz.finish().unwrap());
+
+        let e = Stats::from_path_mmap(&path).unwrap_err();
+
+        assert!(!is_io_error(&e), "{:?}", e);
+    }
+}
@@ -1,150 +1,235 @@
+mod activity;
+mod cache;
+mod cdc;
+mod criteria;
+mod cross_server;
+mod csv_export;
+mod csv_import;
+mod datapackage;
+mod diff;
+mod display_names;
+mod encryption;
+mod event_log;
+mod excluded;
+mod expression;
+mod filter;
+mod histogram;
+#[cfg(feature = "postgres")]
+mod jsonb;
+mod leaderboard;
+mod materialized_view;
+mod migrations;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod moving_average;
+mod normalize;
+mod player_order;
+mod row_level_security;
+mod sanitize;
+mod schema;
+mod sparkline;
+mod split;
+mod sql;
+mod stream;
+mod summary;
+mod teams;
+#[cfg(feature = "templates")]
+mod template;
+mod timeseries;
+mod uuids;
+mod velocity;
+mod wide;
+mod worlds;
+
 use std::{
-    io::{self, Write},
-    iter::once,
-    sync,
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    sync::Arc,
 };
 
 use crate::error::Error;
-use chrono::{DateTime, TimeZone, Utc};
 use nbt::{from_gzip_reader, Blob, Map, Value};
 use serde::{Deserialize, Serialize};
-use sqlx::{mysql::MySqlQueryResult, query, MySqlConnection};
+use sqlx::mysql::MySqlQueryResult;
+
+pub use criteria::Criteria;
+pub use csv_export::{
+    ColumnOrder, ColumnType, CombineMode, ComputedColumn, CsvColumn, CsvOptions, FlagCondition,
+    HeaderCollision, HeaderMap, HeaderNames, ObjectiveFormatter,
+};
+pub use csv_import::CsvImportOptions;
+pub use diff::ScoreDiff;
+pub use encryption::EncryptionKey;
+pub use filter::ExportOptions;
+pub use migrations::{PendingMigration, SCHEMA_VERSION};
+pub use normalize::{NameMerge, NameNormalization, NormalForm};
+pub use player_order::{natural_cmp, PlayerOrder};
+pub use sanitize::{sanitize_file_name, strip_formatting, UniqueNames};
+pub use split::ObjectiveFile;
+pub use sql::{write_objectives_to_sql, SqlDialect, SqlOptions, SqlWriteReport};
+pub use stream::{stream_csv_long, stream_scores, stream_scores_normalized, ScoreEntry};
+pub use summary::{ObjectiveStats, ObjectiveSummary, Summary};
+pub use teams::{Team, TeamAggregate, UNASSIGNED};
+#[cfg(feature = "templates")]
+pub use template::{builtin_template, BUILTIN_TEMPLATES};
+pub use timeseries::TimeSeries;
+pub use uuids::{read_usercache, UuidMap};
+pub use worlds::{scoreboard_path, ObjectiveConflict, Worlds};
 
 pub type PlayerScores = Map<String, Vec<PlayerScore>>;
 pub type Objectives = Map<String, Objective>;
 
-const PLAYERSCORES: &'static str = "PlayerScores";
-const OBJECTIVES: &'static str = "Objectives";
+/// Scores by `(objective, player)`
+pub(super) type ScoreLookup<'a> = HashMap<(&'a str, &'a str), i64>;
+
+const PLAYERSCORES: &str = "PlayerScores";
+const OBJECTIVES: &str = "Objectives";
+/// Paths of the lists in errors locating a malformed element
+const PLAYERSCORES_PATH: &str = "data.PlayerScores";
+const OBJECTIVES_PATH: &str = "data.Objectives";
 
 ///TODO
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Stats {
     objectives: Objectives,
     player_scores: PlayerScores,
+    /// Every player in the order they first appear in the source, the
+    /// cache stores it separately
+    #[serde(skip)]
+    player_order: Vec<Arc<str>>,
+    #[serde(default)]
+    teams: Vec<Team>,
 }
 
 impl Stats {
     pub fn from_gzip_reader(src: impl io::Read) -> Result<Self, Error> {
-        Self::parse_blob(from_gzip_reader(src)?)
+        Ok(Self::from_gzip_reader_normalized(src, &NameNormalization::default())?.0)
     }
 
-    fn parse_blob(blob: Blob) -> Result<Self, Error> {
-        let data: &nbt::Value = blob.get("data").ok_or(Error::NBTMissingField("data"))?;
-
-        let mut objectives: Map<String, Objective> = Map::new();
+    /// Parses the gzipped nbt from `src` normalizing every player name as it
+    /// is read, returning the names that merged. Of merged names with a score
+    /// in the same objective the score that comes first in the source is
+    /// kept.
+    pub fn from_gzip_reader_normalized(
+        src: impl io::Read,
+        normalization: &NameNormalization,
+    ) -> Result<(Self, Vec<NameMerge>), Error> {
+        Self::parse_blob(from_gzip_reader(src)?, normalization)
+    }
 
-        let raw_objectives = match data {
-            Value::Compound(x) => x
-                .get(OBJECTIVES)
-                .ok_or(Error::NBTMissingField(OBJECTIVES))?,
-            _ => panic!(),
+    fn parse_blob(
+        blob: Blob,
+        normalization: &NameNormalization,
+    ) -> Result<(Self, Vec<NameMerge>), Error> {
+        let data = match blob.get("data").ok_or(Error::NBTMissingField("data"))? {
+            Value::Compound(x) => x,
+            _ => {
+                return Err(Error::NBTWrongType {
+                    field: "data",
+                    expected: "compound",
+                })
+            }
         };
 
-        match raw_objectives {
-            Value::List(raw_objectives) => {
-                for objective in raw_objectives {
-                    match objective {
-                        nbt::Value::Compound(objective_map) => {
-                            let key = &objective_map.get("Name").unwrap().to_string();
+        let raw_objectives = data
+            .get(OBJECTIVES)
+            .ok_or(Error::NBTMissingField(OBJECTIVES))?;
 
-                            objectives.insert(key.clone(), objective.try_into()?);
-                        }
-                        _ => (),
-                    }
-                }
-            }
-            _ => panic!("Why is this not a list?"),
-        }
+        let objectives = parse_objectives(raw_objectives)?;
 
         let mut player_scores: Map<String, Vec<PlayerScore>> = Map::new();
-
-        let raw_player_scores = match data {
-            Value::Compound(x) => x
-                .get(PLAYERSCORES)
-                .ok_or(Error::NBTMissingField(PLAYERSCORES))?,
-            _ => panic!(),
-        };
-
-        match raw_player_scores {
-            Value::List(raw_player_scores) => {
-                for player_score in raw_player_scores {
-                    match player_score {
-                        Value::Compound(player_scores_map) => {
-                            let key = &player_scores_map.get("Objective").unwrap().to_string();
-
-                            match player_scores.contains_key(key) {
-                                true => player_scores
-                                    .get_mut(key)
-                                    .unwrap()
-                                    .push(player_score.try_into()?),
-                                false => {
-                                    player_scores
-                                        .insert(key.clone(), vec![player_score.try_into()?]);
-                                }
-                            }
-                        }
-                        _ => (),
+        let mut names = NameInterner::normalizing(*normalization);
+
+        //some tools leave the list out instead of writing an empty one
+        let raw_player_scores = data.get(PLAYERSCORES);
+
+        match raw_player_scores.map_or(Some(&[][..]), list_items) {
+            Some(raw_player_scores) => {
+                for (i, player_score) in raw_player_scores.iter().enumerate() {
+                    if let Value::Compound(player_scores_map) = player_score {
+                        let key = player_scores_map
+                            .get("Objective")
+                            .ok_or(Error::NBTMissingField("Objective"))
+                            .map_err(|e| e.in_nbt_list(PLAYERSCORES_PATH, i))?
+                            .to_string();
+
+                        let player_score =
+                            PlayerScore::from_value(player_score, |x| names.intern(x))
+                                .map_err(|e| e.in_nbt_list(PLAYERSCORES_PATH, i))?;
+
+                        player_scores.entry(key).or_default().push(player_score);
                     }
                 }
             }
-            _ => panic!("Why is this not a list?"),
+            None => {
+                return Err(Error::NBTWrongType {
+                    field: PLAYERSCORES,
+                    expected: "list",
+                })
+            }
         }
 
-        Ok(Stats {
+        let teams = teams::parse_teams(data);
+
+        let merges = names.merges();
+
+        let mut stats = Stats {
             objectives,
             player_scores,
-        })
+            player_order: names.into_order(),
+            teams,
+        };
+        if !merges.is_empty() {
+            stats.drop_merged_scores();
+        }
+
+        Ok((stats, merges))
     }
 
-    pub async fn write_to_sql(
-        &self,
-        conn: &mut MySqlConnection,
-        timestamp: DateTime<Utc>,
-    ) -> Result<(), Error> {
-        let players = self.get_player_list();
-
-        for p in players.iter() {
-            query("INSERT IGNORE INTO players (player_name) VALUES (?)")
-                .bind(p)
-                .execute(&mut *conn)
-                .await?;
-        }
+    /// Makes every score of a player share one copy of the name, used
+    /// after deserializing which gives each score its own copy
+    fn intern_player_names(&mut self) {
+        let mut names = NameInterner::default();
 
-        for (name, obj) in self.objectives.iter() {
-            query(
-                "INSERT IGNORE INTO objectives (objective_name, display_name, criteria_name) VALUES (?,?,?);",
-            )
-            .bind(name)
-            .bind(&obj.display_name)
-            .bind(&obj.criteria_name)
-            .execute(&mut *conn)
-            .await?;
+        for player in self.player_order.iter_mut() {
+            *player = names.intern(player);
         }
 
-        for (obj_name, player_scores) in self.player_scores.iter() {
-            for player_score in player_scores {
-                query(
-                    "INSERT INTO stats (score, player_name, objective_name, time) VALUES (?,?,?,?)",
-                )
-                .bind(&player_score.score)
-                .bind(&player_score.player_name)
-                .bind(&obj_name)
-                .bind(&timestamp)
-                .execute(&mut *conn)
-                .await?;
-            }
+        for player_score in self.player_scores.values_mut().flatten() {
+            player_score.player_name = names.intern(&player_score.player_name);
         }
+    }
 
-        Ok(())
+    pub fn objectives(&self) -> &Objectives {
+        &self.objectives
+    }
+
+    pub fn player_scores(&self) -> &PlayerScores {
+        &self.player_scores
+    }
+
+    /// Whether there isn't a single score, like in a fresh world. There may
+    /// still be objectives.
+    pub fn is_empty(&self) -> bool {
+        self.player_scores.values().all(Vec::is_empty)
+    }
+
+    /// The number of players with at least one score
+    pub fn player_count(&self) -> usize {
+        self.player_names().len()
     }
 
     pub fn get_player_list(&self) -> Vec<String> {
-        let mut players: Vec<String> = self
+        self.player_names().into_iter().map(String::from).collect()
+    }
+
+    /// Sorted and deduplicated player names borrowed from the scores
+    pub(super) fn player_names(&self) -> Vec<&str> {
+        let mut players: Vec<&str> = self
             .player_scores
-            .iter()
-            .map(|x| x.1)
+            .values()
             .flatten()
-            .map(|x| x.player_name.clone())
+            .map(|x| x.player_name())
             .collect();
 
         players.sort_unstable();
@@ -153,55 +238,72 @@ impl Stats {
         players
     }
 
-    pub fn write_csv(&self, w: impl Write) -> Result<(), Error> {
-        let mut titles: Vec<String> = self.objectives.iter().map(|x| x.0.clone()).collect();
-        titles.sort_unstable();
-
-        let mut w = csv::Writer::from_writer(w);
+    /// Every score by objective and player, built once by writers that
+    /// would otherwise search the scores of an objective for every cell
+    pub(super) fn score_lookup(&self) -> ScoreLookup<'_> {
+        let mut lookup = HashMap::new();
 
-        let mut top_row = vec!["Players".to_string()];
-
-        {
-            for i in &titles {
-                top_row.push(self.objectives.get(i).unwrap().display_name.clone())
+        for (objective, scores) in self.player_scores.iter() {
+            for score in scores {
+                lookup
+                    .entry((objective.as_str(), score.player_name()))
+                    .or_insert(score.score);
             }
         }
 
-        let players = self.get_player_list();
-
-        w.write_record(top_row)?;
-
-        //loops over every player gathering all the stats
-        for player in players {
-            let mut row: Vec<String> = Vec::new();
+        lookup
+    }
+}
 
-            row.push(player.clone());
+/// The elements of an nbt list. Decoding through serde can't tell an empty
+/// list from an empty array, so empty arrays count as empty lists.
+fn list_items(value: &Value) -> Option<&[Value]> {
+    match value {
+        Value::List(items) => Some(items),
+        Value::ByteArray(x) if x.is_empty() => Some(&[]),
+        Value::IntArray(x) if x.is_empty() => Some(&[]),
+        Value::LongArray(x) if x.is_empty() => Some(&[]),
+        _ => None,
+    }
+}
 
-            //gathers all the stats for a specific player
-            for title in &titles {
-                row.push(
-                    self.player_scores
-                        .get(title)
-                        .and_then(|x| {
-                            x.iter()
-                                .find(|x| x.player_name == player)
-                                .and_then(|x| Some(x.score.to_string()))
-                        })
-                        .unwrap_or(String::from("0")),
-                );
+fn parse_objectives(raw_objectives: &Value) -> Result<Objectives, Error> {
+    let mut objectives: Map<String, Objective> = Map::new();
+
+    match list_items(raw_objectives) {
+        Some(raw_objectives) => {
+            for (i, objective) in raw_objectives.iter().enumerate() {
+                if let nbt::Value::Compound(objective_map) = objective {
+                    let key = objective_map
+                        .get("Name")
+                        .ok_or(Error::NBTMissingField("Name"))
+                        .map_err(|e| e.in_nbt_list(OBJECTIVES_PATH, i))?
+                        .to_string();
+
+                    objectives.insert(
+                        key,
+                        objective
+                            .try_into()
+                            .map_err(|e: Error| e.in_nbt_list(OBJECTIVES_PATH, i))?,
+                    );
+                }
             }
-            w.write_record(row)?;
         }
-
-        w.flush()?;
-
-        Ok(())
+        None => {
+            return Err(Error::NBTWrongType {
+                field: OBJECTIVES,
+                expected: "list",
+            })
+        }
     }
+
+    Ok(objectives)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct Objective {
+    name: String,
     criteria_name: String,
     display_auto_update: i8,
     display_name: String,
@@ -209,6 +311,7 @@ pub struct Objective {
 }
 
 impl Objective {
+    #[allow(dead_code)]
     async fn insert_to_db(
         &self,
         conn: &mut sqlx::MySqlConnection,
@@ -219,59 +322,126 @@ impl Objective {
                 .bind(&self.display_name);
         query.execute(conn).await
     }
+
+    /// The objective name used in commands
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The part of the name before the first `:` or `.`, like `bac_obtained`
+    /// for `bac_obtained:minecraft/diamond`, if the name has one
+    pub fn namespace(&self) -> Option<&str> {
+        split_namespace(&self.name).0
+    }
+
+    /// The name without its namespace
+    pub fn path(&self) -> &str {
+        split_namespace(&self.name).1
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn criteria(&self) -> Criteria {
+        self.criteria_name.as_str().into()
+    }
+}
+
+/// Splits an objective name into its namespace and path, datapacks name
+/// their objectives like `namespace:path` or `namespace.path`
+pub fn split_namespace(name: &str) -> (Option<&str>, &str) {
+    match name.find([':', '.']) {
+        Some(i) if i > 0 => (Some(&name[..i]), &name[i + 1..]),
+        _ => (None, name),
+    }
 }
 
 impl TryFrom<&Value> for Objective {
     type Error = Error;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
-        use Error::LOLError;
+        use Error::{NBTMissingField, NBTWrongType};
 
         match value {
             Value::Compound(val) => Ok(Self {
+                name: {
+                    match val.get("Name").ok_or(NBTMissingField("Name"))? {
+                        Value::String(s) => Ok(s.clone()),
+                        _ => Err(NBTWrongType {
+                            field: "Name",
+                            expected: "string",
+                        }),
+                    }?
+                },
                 criteria_name: {
-                    match val.get("CriteriaName").ok_or(LOLError)? {
+                    match val
+                        .get("CriteriaName")
+                        .ok_or(NBTMissingField("CriteriaName"))?
+                    {
                         Value::String(s) => Ok(s.clone()),
-                        _ => Err(LOLError),
+                        _ => Err(NBTWrongType {
+                            field: "CriteriaName",
+                            expected: "string",
+                        }),
                     }?
                 },
                 display_auto_update: {
-                    match val.get("display_auto_update").ok_or(LOLError)? {
-                        Value::Byte(s) => Ok(s.clone()),
-                        _ => Err(LOLError),
+                    match val
+                        .get("display_auto_update")
+                        .ok_or(NBTMissingField("display_auto_update"))?
+                    {
+                        Value::Byte(s) => Ok(*s),
+                        _ => Err(NBTWrongType {
+                            field: "display_auto_update",
+                            expected: "byte",
+                        }),
                     }?
                 },
                 display_name: {
-                    match val.get("DisplayName").ok_or(LOLError)? {
+                    match val
+                        .get("DisplayName")
+                        .ok_or(NBTMissingField("DisplayName"))?
+                    {
                         Value::String(s) => {
                             let mut chars = s.chars();
                             chars.next();
                             chars.next_back();
                             Ok(chars.as_str().to_string())
                         }
-                        _ => Err(LOLError),
+                        _ => Err(NBTWrongType {
+                            field: "DisplayName",
+                            expected: "string",
+                        }),
                     }?
                 },
                 render_type: {
-                    match val.get("RenderType").ok_or(LOLError)? {
+                    match val.get("RenderType").ok_or(NBTMissingField("RenderType"))? {
                         Value::String(s) => Ok(s.clone()),
-                        _ => Err(LOLError),
+                        _ => Err(NBTWrongType {
+                            field: "RenderType",
+                            expected: "string",
+                        }),
                     }?
                 },
             }),
-            _ => Err(LOLError),
+            _ => Err(NBTWrongType {
+                field: OBJECTIVES,
+                expected: "list of compounds",
+            }),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerScore {
     locked: i8,
-    player_name: String,
+    player_name: Arc<str>,
     score: i64,
 }
 
 impl PlayerScore {
+    #[allow(dead_code)]
     async fn insert_with_name(
         &self,
         conn: &mut sqlx::MySqlConnection,
@@ -279,45 +449,382 @@ impl PlayerScore {
     ) -> Result<MySqlQueryResult, sqlx::Error> {
         let query =
             sqlx::query("INSERT INTO stats (score,player_name,objective_name) VALUES (?,?,?)")
-                .bind(&self.score)
-                .bind(&self.player_name)
-                .bind(&obj.criteria_name);
+                .bind(self.score)
+                .bind(self.player_name())
+                .bind(obj.criteria_name);
 
         query.execute(conn).await
     }
+
+    pub fn player_name(&self) -> &str {
+        &self.player_name
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+}
+
+/// Hands out a single shared copy of each player name, a player has a
+/// score in most objectives so this keeps the name from being stored once per score
+#[derive(Default)]
+struct NameInterner {
+    names: HashSet<Arc<str>>,
+    /// The names in the order they were first interned
+    order: Vec<Arc<str>>,
+    /// Applied to every name before it is interned
+    normalization: NameNormalization,
+    /// The names every normalized name was interned from, only kept when
+    /// normalizing
+    originals: BTreeMap<Arc<str>, Vec<String>>,
+}
+
+impl NameInterner {
+    fn normalizing(normalization: NameNormalization) -> Self {
+        Self {
+            normalization,
+            ..Default::default()
+        }
+    }
+
+    fn intern(&mut self, name: &str) -> Arc<str> {
+        if self.normalization.is_noop() {
+            return self.intern_normalized(name);
+        }
+
+        let normalized = self.intern_normalized(&self.normalization.apply(name));
+        let originals = self.originals.entry(normalized.clone()).or_default();
+        if !originals.iter().any(|x| x == name) {
+            originals.push(name.to_string());
+        }
+
+        normalized
+    }
+
+    fn intern_normalized(&mut self, name: &str) -> Arc<str> {
+        match self.names.get(name) {
+            Some(name) => name.clone(),
+            None => {
+                let name: Arc<str> = Arc::from(name);
+                self.names.insert(name.clone());
+                self.order.push(name.clone());
+                name
+            }
+        }
+    }
+
+    fn into_order(self) -> Vec<Arc<str>> {
+        self.order
+    }
 }
 
 impl TryFrom<&Value> for PlayerScore {
     type Error = Error;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
-        use Error::{LOLError, NOTLOLError};
+        Self::from_value(value, |x| Arc::from(x))
+    }
+}
+
+impl PlayerScore {
+    /// Reads a score from the nbt, `name` turns the borrowed player name
+    /// into the shared one so interned names are never allocated per score
+    fn from_value(value: &Value, name: impl FnOnce(&str) -> Arc<str>) -> Result<Self, Error> {
+        use Error::{NBTMissingField, NBTWrongType};
 
         match value {
             Value::Compound(val) => Ok(Self {
                 locked: {
-                    match val.get("Locked").ok_or(NOTLOLError)? {
-                        Value::Byte(s) => Ok(s.clone()),
-                        _ => Err(LOLError),
+                    match val.get("Locked").ok_or(NBTMissingField("Locked"))? {
+                        Value::Byte(s) => Ok(*s),
+                        _ => Err(NBTWrongType {
+                            field: "Locked",
+                            expected: "byte",
+                        }),
                     }?
                 },
                 player_name: {
-                    match val.get("Name").ok_or(NOTLOLError)? {
-                        Value::String(s) => Ok(s.clone()),
-                        _ => Err(NOTLOLError),
+                    match val.get("Name").ok_or(NBTMissingField("Name"))? {
+                        Value::String(s) => Ok(name(s)),
+                        _ => Err(NBTWrongType {
+                            field: "Name",
+                            expected: "string",
+                        }),
                     }?
                 },
                 score: {
-                    match val.get("Score").ok_or(NOTLOLError)? {
+                    match val.get("Score").ok_or(NBTMissingField("Score"))? {
                         Value::Long(s) => Ok(*s),
                         Value::Int(s) => Ok(*s as i64),
                         Value::Short(s) => Ok(*s as i64),
                         Value::Byte(s) => Ok(*s as i64),
-                        _ => Err(NOTLOLError),
+                        _ => Err(NBTWrongType {
+                            field: "Score",
+                            expected: "number",
+                        }),
                     }?
                 },
             }),
-            _ => Err(NOTLOLError),
+            _ => Err(NBTWrongType {
+                field: PLAYERSCORES,
+                expected: "list of compounds",
+            }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn player_names_are_shared_between_scores() {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [("ville", "deaths", 3), ("ville", "kills", 1)].into_iter(),
+        );
+
+        let stats = Stats::from_gzip_reader(&file[..]).unwrap();
+        let deaths = &stats.player_scores["deaths"][0];
+        let kills = &stats.player_scores["kills"][0];
+
+        assert!(Arc::ptr_eq(&deaths.player_name, &kills.player_name));
+        assert_eq!(deaths.player_name(), kills.player_name());
+        assert_eq!(
+            serde_json::to_value(deaths).unwrap()["player_name"],
+            "ville"
+        );
+    }
+
+    /// "ville" written as plain ascii, with a zero-width joiner and a
+    /// combining diaeresis that nfc composes
+    fn lookalikes() -> Vec<u8> {
+        fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "deaths", 3),
+                ("vi\u{200D}lle", "deaths", 9),
+                ("vi\u{200D}lle", "kills", 1),
+                ("a\u{0308}iti", "kills", 2),
+            ]
+            .into_iter(),
+        )
+    }
+
+    #[test]
+    fn names_are_normalized_while_parsing() {
+        let normalization = NameNormalization {
+            form: Some(NormalForm::Nfc),
+            strip_invisible: true,
+        };
+
+        let (stats, merges) =
+            Stats::from_gzip_reader_normalized(&lookalikes()[..], &normalization).unwrap();
+
+        assert_eq!(
+            merges,
+            [NameMerge {
+                name: "ville".to_string(),
+                merged: vec!["ville".to_string(), "vi\u{200D}lle".to_string()],
+            }]
+        );
+        //the first score of the merged players is kept
+        assert_eq!(stats.player_scores["deaths"].len(), 1);
+        assert_eq!(stats.player_scores["deaths"][0].score(), 3);
+        assert_eq!(stats.player_scores["kills"][0].player_name(), "ville");
+        assert_eq!(stats.player_scores["kills"][1].player_name(), "\u{E4}iti");
+        assert_eq!(stats.get_player_list(), ["ville", "\u{E4}iti"]);
+    }
+
+    #[test]
+    fn parsing_without_normalization_keeps_names() {
+        let (stats, merges) =
+            Stats::from_gzip_reader_normalized(&lookalikes()[..], &NameNormalization::default())
+                .unwrap();
+
+        assert!(merges.is_empty());
+        assert_eq!(stats.player_count(), 3);
+    }
+
+    #[test]
+    fn streamed_names_are_normalized() {
+        let normalization = NameNormalization {
+            form: None,
+            strip_invisible: true,
+        };
+        let mut players = Vec::new();
+
+        stream_scores_normalized(&lookalikes()[..], &normalization, |entry| {
+            players.push(entry.player_name);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(players, ["ville", "ville", "ville", "a\u{0308}iti"]);
+    }
+
+    fn csv(stats: &Stats) -> String {
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+        String::from_utf8(csv).unwrap()
+    }
+
+    #[test]
+    fn fresh_world_is_empty() {
+        let file = fixture::scoreboard::<&str, &str>(&[], std::iter::empty());
+
+        let stats = Stats::from_gzip_reader(&file[..]).unwrap();
+
+        assert!(stats.is_empty());
+        assert_eq!(stats.player_count(), 0);
+        assert!(stats.objectives().is_empty());
+        assert_eq!(csv(&stats), "Players\n");
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["objectives"], serde_json::json!({}));
+        assert_eq!(json["player_scores"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn objectives_without_a_player_scores_list_are_empty() {
+        let file = fixture::scoreboard_without_scores(&[("deaths", "deathCount")]);
+
+        let stats = Stats::from_gzip_reader(&file[..]).unwrap();
+
+        assert!(stats.is_empty());
+        assert_eq!(stats.objectives().len(), 1);
+        assert_eq!(csv(&stats), "Players,deaths\n");
+
+        let mut streamed = 0;
+        let objectives = stream_scores(&file[..], |_| {
+            streamed += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(streamed, 0);
+        assert_eq!(objectives.len(), 1);
+    }
+
+    #[test]
+    fn objectives_without_scores_are_empty() {
+        let file =
+            fixture::scoreboard::<&str, &str>(&[("deaths", "deathCount")], std::iter::empty());
+
+        let stats = Stats::from_gzip_reader(&file[..]).unwrap();
+
+        assert!(stats.is_empty());
+        assert_eq!(Stats::objectives_only(&file[..]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn malformed_scores_name_the_field() {
+        let score = |name: Value| {
+            Value::Compound(Map::from_iter([
+                ("Locked".to_string(), Value::Byte(0)),
+                ("Name".to_string(), name),
+            ]))
+        };
+
+        assert!(matches!(
+            PlayerScore::try_from(&score(Value::String("ville".to_string()))),
+            Err(Error::NBTMissingField("Score"))
+        ));
+        assert!(matches!(
+            PlayerScore::try_from(&score(Value::Int(3))),
+            Err(Error::NBTWrongType {
+                field: "Name",
+                expected: "string"
+            })
+        ));
+        assert!(matches!(
+            Objective::try_from(&Value::Int(3)),
+            Err(Error::NBTWrongType {
+                field: OBJECTIVES,
+                ..
+            })
+        ));
+    }
+
+    /// A gzipped scoreboard with `data` as its data
+    fn file(data: Value) -> Vec<u8> {
+        let mut blob = Blob::new();
+        blob.insert("data", data).unwrap();
+
+        let mut file = Vec::new();
+        blob.to_gzip_writer(&mut file).unwrap();
+        file
+    }
+
+    #[test]
+    fn malformed_lists_are_errors() {
+        let parse = |data: Value| Stats::from_gzip_reader(&file(data)[..]).unwrap_err();
+
+        assert!(matches!(
+            parse(Value::Int(1)),
+            Error::NBTWrongType { field: "data", .. }
+        ));
+        assert!(matches!(
+            parse(Value::Compound(Map::from_iter([(
+                OBJECTIVES.to_string(),
+                Value::Int(1)
+            )]))),
+            Error::NBTWrongType {
+                field: OBJECTIVES,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse(Value::Compound(Map::from_iter([
+                (OBJECTIVES.to_string(), Value::List(Vec::new())),
+                (PLAYERSCORES.to_string(), Value::Int(1)),
+            ]))),
+            Error::NBTWrongType {
+                field: PLAYERSCORES,
+                ..
+            }
+        ));
+        assert_eq!(
+            parse(Value::Compound(Map::from_iter([(
+                OBJECTIVES.to_string(),
+                Value::List(vec![Value::Compound(Map::new())]),
+            )])))
+            .to_string(),
+            "NBT data file is missing the required field \"Name\" in data.Objectives[0]"
+        );
+    }
+
+    #[test]
+    fn malformed_list_elements_are_located() {
+        let score = |score: Option<i32>| {
+            let mut score: Map<String, Value> = score
+                .map(|x| ("Score".to_string(), Value::Int(x)))
+                .into_iter()
+                .collect();
+            score.insert("Objective".to_string(), Value::String("deaths".to_string()));
+            score.insert("Name".to_string(), Value::String("ville".to_string()));
+            score.insert("Locked".to_string(), Value::Byte(0));
+            Value::Compound(score)
+        };
+        let file = file(Value::Compound(Map::from_iter([
+            (OBJECTIVES.to_string(), Value::List(Vec::new())),
+            (
+                PLAYERSCORES.to_string(),
+                Value::List(vec![score(Some(1)), score(None)]),
+            ),
+        ])));
+
+        let message =
+            "NBT data file is missing the required field \"Score\" in data.PlayerScores[1]";
+        assert_eq!(
+            Stats::from_gzip_reader(&file[..]).unwrap_err().to_string(),
+            message
+        );
+        assert_eq!(
+            stream_scores(&file[..], |_| Ok(()))
+                .unwrap_err()
+                .to_string(),
+            message
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, fmt::Write as _, io, io::Write};
+
+use chrono::{DateTime, Utc};
+use csv::ByteRecord;
+
+use super::{csv_export::resolve_header_collisions, ColumnType, CsvColumn, HeaderCollision, Stats};
+use crate::error::Error;
+
+impl Stats {
+    /// Writes a csv like [`Stats::write_csv`] where every score is the mean
+    /// of that player's score over all snapshots in `window`.
+    ///
+    /// Players and objectives missing from a snapshot count as a score of 0
+    /// in it. Headers use the display names of the newest snapshot that has
+    /// the objective. The means are written with `float_precision` decimal
+    /// places.
+    pub fn write_csv_moving_average(
+        w: impl Write,
+        window: &[(DateTime<Utc>, Stats)],
+        float_precision: u8,
+    ) -> Result<(), Error> {
+        if window.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the window needs at least one snapshot",
+            )
+            .into());
+        }
+
+        let mut snapshots: Vec<&(DateTime<Utc>, Stats)> = window.iter().collect();
+        snapshots.sort_by_key(|(timestamp, _)| *timestamp);
+
+        //newer snapshots are inserted last so their display names win
+        let mut titles: BTreeMap<&str, &str> = BTreeMap::new();
+        for (_, stats) in snapshots.iter() {
+            for (name, objective) in stats.objectives.iter() {
+                titles.insert(name, objective.display_name());
+            }
+        }
+
+        //sums of every player's scores across the window, per objective
+        let mut sums: BTreeMap<&str, Vec<i128>> = BTreeMap::new();
+        for (_, stats) in snapshots.iter() {
+            for (i, title) in titles.keys().enumerate() {
+                for score in stats.player_scores.get(*title).into_iter().flatten() {
+                    sums.entry(score.player_name())
+                        .or_insert_with(|| vec![0; titles.len()])[i] += score.score as i128;
+                }
+            }
+        }
+
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+
+        let mut columns: Vec<CsvColumn> = titles
+            .iter()
+            .map(|(name, display_name)| CsvColumn {
+                name: display_name.to_string(),
+                column_type: ColumnType::Number,
+                objective: Some(name.to_string()),
+                criteria: None,
+            })
+            .collect();
+        resolve_header_collisions(&mut columns, HeaderCollision::Disambiguate)?;
+
+        record.push_field(b"Players");
+        for column in &columns {
+            record.push_field(column.name.as_bytes());
+        }
+        w.write_byte_record(&record)?;
+
+        let count = snapshots.len() as f64;
+
+        let mut formatted_buffer = String::new();
+
+        for (player, scores) in sums.iter() {
+            record.clear();
+            record.push_field(player.as_bytes());
+
+            for sum in scores {
+                formatted_buffer.clear();
+                write!(
+                    formatted_buffer,
+                    "{:.prec$}",
+                    *sum as f64 / count,
+                    prec = float_precision as usize
+                )
+                .unwrap();
+                record.push_field(formatted_buffer.as_bytes());
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::fixture;
+
+    fn snapshot(day: u32, scores: &[(&str, &str, i32)]) -> (DateTime<Utc>, Stats) {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.iter().copied(),
+        );
+
+        (
+            Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            Stats::from_gzip_reader(&nbt[..]).unwrap(),
+        )
+    }
+
+    fn moving_average(window: &[(DateTime<Utc>, Stats)], float_precision: u8) -> String {
+        let mut out = Vec::new();
+        Stats::write_csv_moving_average(&mut out, window, float_precision).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn missing_scores_count_as_zero() {
+        let window = [
+            snapshot(1, &[("ville", "deaths", 2), ("ville", "kills", 1)]),
+            snapshot(2, &[("ville", "deaths", 4), ("anna", "kills", 3)]),
+            snapshot(3, &[("ville", "deaths", 9), ("anna", "kills", 3)]),
+        ];
+
+        assert_eq!(
+            moving_average(&window, 2),
+            "Players,deaths,kills\nanna,0.00,2.00\nville,5.00,0.33\n"
+        );
+    }
+
+    #[test]
+    fn headers_come_from_the_newest_snapshot() {
+        let mut newest = snapshot(2, &[("ville", "deaths", 4)]);
+        newest.1.objectives.get_mut("deaths").unwrap().display_name = "Deaths".to_string();
+
+        //the window is sorted by time, not by the order it is given in
+        let window = [newest, snapshot(1, &[("ville", "deaths", 2)])];
+
+        assert_eq!(
+            moving_average(&window, 0),
+            "Players,Deaths,kills\nville,3,0\n"
+        );
+    }
+
+    #[test]
+    fn empty_windows_are_rejected() {
+        let err = Stats::write_csv_moving_average(Vec::new(), &[], 2).unwrap_err();
+
+        assert_eq!(err.to_string(), "the window needs at least one snapshot");
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use super::{NameInterner, Stats};
+
+/// Characters that take up no space, dropped with
+/// [`NameNormalization::strip_invisible`]
+pub(super) const INVISIBLE_CHARS: [char; 10] = [
+    '\u{00AD}', '\u{180E}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{2061}', '\u{2062}',
+    '\u{2063}', '\u{FEFF}',
+];
+
+/// Unicode normalization forms usable for player names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalForm {
+    /// Canonical composition, only merges names that look exactly the same
+    Nfc,
+    /// Compatibility composition, also merges look-alikes like full width
+    /// letters and ligatures
+    Nfkc,
+}
+
+/// How player names are cleaned up so names that look the same end up
+/// the same
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameNormalization {
+    pub form: Option<NormalForm>,
+    /// Drop zero-width characters like zero-width joiners and spaces
+    pub strip_invisible: bool,
+}
+
+/// Player names that became the same name after normalizing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameMerge {
+    /// The normalized name
+    pub name: String,
+    /// The original names, in the order they first appeared
+    pub merged: Vec<String>,
+}
+
+impl NameNormalization {
+    pub fn is_noop(&self) -> bool {
+        self.form.is_none() && !self.strip_invisible
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        let visible = name
+            .chars()
+            .filter(|c| !(self.strip_invisible && INVISIBLE_CHARS.contains(c)));
+
+        match self.form {
+            Some(NormalForm::Nfc) => visible.nfc().collect(),
+            Some(NormalForm::Nfkc) => visible.nfkc().collect(),
+            None => visible.collect(),
+        }
+    }
+}
+
+impl NameInterner {
+    /// The normalized names that more than one name was interned as, by name
+    pub(super) fn merges(&self) -> Vec<NameMerge> {
+        self.originals
+            .iter()
+            .filter(|(_, merged)| merged.len() > 1)
+            .map(|(name, merged)| NameMerge {
+                name: name.to_string(),
+                merged: merged.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Stats {
+    /// Normalizes every player name, returning the names that merged. Only
+    /// needed for stats that weren't normalized while parsing, like ones
+    /// read from a csv, see [`Stats::from_gzip_reader_normalized`].
+    ///
+    /// When merged names both have a score in the same objective the score
+    /// that comes first in the source is kept.
+    pub fn normalize_player_names(&mut self, normalization: &NameNormalization) -> Vec<NameMerge> {
+        if normalization.is_noop() {
+            return Vec::new();
+        }
+
+        let mut names = NameInterner::normalizing(*normalization);
+
+        for player in self.player_order.iter() {
+            names.intern(player);
+        }
+
+        for score in self.player_scores.values_mut().flatten() {
+            score.player_name = names.intern(score.player_name());
+        }
+
+        let merges = names.merges();
+
+        self.player_order = names.into_order();
+        if !merges.is_empty() {
+            self.drop_merged_scores();
+        }
+
+        merges
+    }
+
+    /// Keeps the first score of every player in each objective, after
+    /// normalizing made several names the same
+    pub(super) fn drop_merged_scores(&mut self) {
+        for scores in self.player_scores.values_mut() {
+            let mut seen = HashSet::new();
+            scores.retain(|x| seen.insert(x.player_name.clone()));
+        }
+    }
+}
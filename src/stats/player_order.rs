@@ -0,0 +1,155 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    str::Chars,
+};
+
+use super::Stats;
+
+/// Orders players are written in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerOrder {
+    /// By the bytes of the name, so `Zebra` comes before `anna` and
+    /// `Player10` before `Player2`
+    #[default]
+    Lexical,
+    /// Ignoring case and comparing runs of digits as numbers
+    Natural,
+    /// In the order the players first appear in the source
+    None,
+}
+
+impl Stats {
+    /// The deduplicated player names in `order`
+    pub fn players_in_order(&self, order: PlayerOrder) -> Vec<&str> {
+        match order {
+            PlayerOrder::Lexical => self.player_names(),
+            PlayerOrder::Natural => {
+                let mut players = self.player_names();
+                players.sort_by(|a, b| natural_cmp(a, b));
+                players
+            }
+            PlayerOrder::None => {
+                let present: HashSet<&str> = self.player_names().into_iter().collect();
+
+                self.player_order
+                    .iter()
+                    .map(|x| &**x)
+                    .filter(|x| present.contains(x))
+                    .collect()
+            }
+        }
+    }
+
+    /// Reorders the scores of every objective so players come in `order`,
+    /// which is the order they get serialized in
+    pub fn sort_scores(&mut self, order: PlayerOrder) {
+        let ranks: Vec<String> = self
+            .players_in_order(order)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let ranks: HashMap<&str, usize> = ranks
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.as_str(), i))
+            .collect();
+
+        for scores in self.player_scores.values_mut() {
+            scores.sort_by_key(|x| ranks[x.player_name()]);
+        }
+    }
+}
+
+/// Compares names ignoring case with runs of digits compared as numbers,
+/// so `player2` comes before `Player10`. Names that only differ in case
+/// fall back to comparing bytes.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digit_run(&mut a_chars);
+                let y = digit_run(&mut b_chars);
+
+                //without leading zeros the longer run is the larger number
+                let x_trimmed = x.trim_start_matches('0');
+                let y_trimmed = y.trim_start_matches('0');
+
+                x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                a_chars.next();
+                b_chars.next();
+                ordering
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_comparisons() {
+        for (a, b, expected) in [
+            ("player2", "Player10", Ordering::Less),
+            ("Player10", "player2", Ordering::Greater),
+            ("anna", "Bob", Ordering::Less),
+            ("Zebra", "anna", Ordering::Greater),
+            ("p2a", "p2b", Ordering::Less),
+            ("a", "ab", Ordering::Less),
+            ("1", "a", Ordering::Less),
+            ("ville", "ville", Ordering::Equal),
+            //only differing in case or leading zeros falls back to the bytes
+            ("A", "a", Ordering::Less),
+            ("x01", "x1", Ordering::Less),
+            ("x1", "x01", Ordering::Greater),
+            //runs longer than any integer
+            (
+                "p99999999999999999999999",
+                "p100000000000000000000000",
+                Ordering::Less,
+            ),
+        ] {
+            assert_eq!(natural_cmp(a, b), expected, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn natural_sort() {
+        let mut names = ["Player10", "player2", "bob", "Player1", "Anna", "player02"];
+
+        names.sort_by(|a, b| natural_cmp(a, b));
+
+        assert_eq!(
+            names,
+            ["Anna", "bob", "Player1", "player02", "player2", "Player10"]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use super::{schema::TENANT_COLUMN, SqlDialect, SqlOptions, Stats};
+use crate::error::Error;
+
+/// The setting a session picks its tenant with, like
+/// `SET app.tenant_id = 'survival'`
+const TENANT_SETTING: &str = "app.tenant_id";
+
+impl Stats {
+    /// Writes the PostgreSQL statements giving every table of `opts` a
+    /// `tenant_id` column, enabling row level security on it and creating a
+    /// policy that only lets a session see and write the rows of the tenant
+    /// in its `app.tenant_id` setting. Rows are tagged by writing with
+    /// [`SqlOptions::tenant_id`].
+    ///
+    /// Tables that don't exist yet are skipped, so the statements should be
+    /// run again once they are created. They can be run any number of times.
+    /// The owner of a table isn't restricted by its policies, tenants need
+    /// roles of their own. `schema_migrations` is shared by every tenant
+    /// and left without a policy.
+    ///
+    /// MySQL has no row level security and the monthly tables of
+    /// `partition_by_date` are only known to the database, both are refused.
+    pub fn write_sql_with_row_level_security(
+        &self,
+        mut w: impl Write,
+        opts: &SqlOptions,
+        dialect: SqlDialect,
+    ) -> Result<(), Error> {
+        if dialect != SqlDialect::Postgres {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "row level security needs PostgreSQL",
+            )
+            .into());
+        }
+
+        if opts.partition_by_date {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "row level security doesn't cover the monthly stats tables",
+            )
+            .into());
+        }
+
+        let tables = opts.tables()?;
+
+        for table in [
+            &tables.players,
+            &tables.objectives,
+            &tables.stats,
+            &tables.objective_totals,
+            &tables.objective_display_names,
+            &tables.excluded_players,
+            &tables.score_events,
+            &tables.player_scores_wide,
+        ] {
+            //CREATE POLICY has no IF NOT EXISTS, and fails for missing tables
+            write!(
+                w,
+                "ALTER TABLE IF EXISTS {table} ADD COLUMN IF NOT EXISTS {column};
+ALTER TABLE IF EXISTS {table} ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS {table}_tenant ON {table};
+DO $$
+BEGIN
+    IF to_regclass('{table}') IS NOT NULL THEN
+        CREATE POLICY {table}_tenant ON {table}
+            USING (tenant_id = current_setting('{setting}', true));
+    END IF;
+END
+$$;
+
+",
+                table = table,
+                column = TENANT_COLUMN,
+                setting = TENANT_SETTING
+            )?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats() -> Stats {
+        Stats::from_gzip_reader(
+            &fixture::scoreboard_without_scores(&[("deaths", "deathCount")])[..],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn every_table_gets_a_policy() {
+        let opts = SqlOptions {
+            schema_prefix: Some("smp".to_string()),
+            ..Default::default()
+        };
+        let mut sql = Vec::new();
+
+        stats()
+            .write_sql_with_row_level_security(&mut sql, &opts, SqlDialect::Postgres)
+            .unwrap();
+
+        let sql = String::from_utf8(sql).unwrap();
+        assert_eq!(sql.matches("CREATE POLICY").count(), 8);
+        assert!(sql.contains("CREATE POLICY smp_stats_tenant ON smp_stats"));
+        assert!(!sql.contains("schema_migrations"));
+    }
+
+    #[test]
+    fn mysql_is_refused() {
+        let e = stats()
+            .write_sql_with_row_level_security(
+                Vec::new(),
+                &SqlOptions::default(),
+                SqlDialect::MySql,
+            )
+            .unwrap_err();
+
+        assert!(matches!(e, Error::IOError(e) if e.kind() == io::ErrorKind::InvalidInput));
+    }
+}
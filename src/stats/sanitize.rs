@@ -0,0 +1,205 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use super::{normalize::INVISIBLE_CHARS, Stats};
+
+/// Starts a formatting code in chat components and names
+const FORMATTING_SIGN: char = '§';
+
+/// Device names Windows won't create files for, whatever the extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Hands out sanitized names that are unique among each other, so two
+/// objectives never end up writing to the same place
+///
+/// Names that end up the same, ignoring case, get a `_2`, `_3`, … suffix
+/// in the order they are asked for.
+#[derive(Debug, Default)]
+pub struct UniqueNames {
+    used: HashSet<String>,
+}
+
+impl UniqueNames {
+    /// The [`sanitize_file_name`] of `name`, made unique
+    pub fn file_name(&mut self, name: &str) -> String {
+        self.unique(sanitize_file_name(name))
+    }
+
+    fn unique(&mut self, base_name: String) -> String {
+        let mut name = base_name.clone();
+        let mut n = 1;
+
+        while !self.used.insert(name.to_lowercase()) {
+            n += 1;
+            name = format!("{}_{}", base_name, n);
+        }
+
+        name
+    }
+}
+
+/// Turns an objective name into a file name without an extension that is
+/// safe on every platform: anything but ascii alphanumerics, `-`, `_` and
+/// `.` becomes `_`, and names that would be hidden or reserved on Windows
+/// get an extra `_`
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+
+    if sanitized.is_empty() || sanitized.starts_with('.') {
+        sanitized.insert(0, '_');
+    }
+
+    //windows drops trailing dots, which could make two names the same file
+    if sanitized.ends_with('.') {
+        sanitized.push('_');
+    }
+
+    //the reserved names are reserved with any extension, so the stem is changed
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        sanitized.insert(stem.len(), '_');
+    }
+
+    sanitized
+}
+
+/// Removes `§` formatting codes, like the `§a` that colors text green,
+/// control characters and zero-width characters from a name
+pub fn strip_formatting(name: &str) -> Cow<'_, str> {
+    let is_clean =
+        |c: char| c != FORMATTING_SIGN && !c.is_control() && !INVISIBLE_CHARS.contains(&c);
+
+    if name.chars().all(is_clean) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut stripped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            //the code is the one character after the sign
+            FORMATTING_SIGN => {
+                chars.next();
+            }
+            c if is_clean(c) => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    Cow::Owned(stripped)
+}
+
+impl Stats {
+    /// Player names and objective display names that [`strip_formatting`]
+    /// changes, as `(raw, stripped)` pairs. These are the names the csv
+    /// writer cleans up unless
+    /// [`CsvOptions::preserve_raw_names`](super::CsvOptions::preserve_raw_names)
+    /// is set.
+    pub fn formatted_names(&self) -> Vec<(String, String)> {
+        let display_names = self
+            .titles()
+            .into_iter()
+            .map(|x| self.objectives[x].display_name());
+
+        self.player_names()
+            .into_iter()
+            .chain(display_names)
+            .filter_map(|name| match strip_formatting(name) {
+                Cow::Owned(stripped) => Some((name.to_string(), stripped)),
+                Cow::Borrowed(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn formatting_is_stripped() {
+        for (name, expected) in [
+            ("§aville", "ville"),
+            ("§l§cRed§r Team", "Red Team"),
+            ("ville§", "ville"),
+            ("§§ville", "ville"),
+            ("\u{200B}vil\u{FEFF}le", "ville"),
+            ("vil\tle\n", "ville"),
+            ("Ünïcödé 名前", "Ünïcödé 名前"),
+        ] {
+            assert_eq!(strip_formatting(name), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn clean_names_are_borrowed() {
+        assert!(matches!(strip_formatting("ville"), Cow::Borrowed("ville")));
+        assert!(matches!(strip_formatting("§aville"), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn formatted_names_are_listed() {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount")],
+            [("§aville", "deaths", 1), ("anna", "deaths", 2)].into_iter(),
+        );
+        let stats = Stats::from_gzip_reader(&nbt[..]).unwrap();
+
+        assert_eq!(
+            stats.formatted_names(),
+            [("§aville".to_string(), "ville".to_string())]
+        );
+    }
+
+    #[test]
+    fn file_names_are_sanitized() {
+        for (name, expected) in [
+            ("deaths", "deaths"),
+            (
+                "minecraft.custom:minecraft.jump",
+                "minecraft.custom_minecraft.jump",
+            ),
+            ("a/b\\c d", "a_b_c_d"),
+            ("ünï", "_n_"),
+            ("", "_"),
+            (".hidden", "_.hidden"),
+            ("name.", "name._"),
+            ("con", "con_"),
+            ("CON.txt", "CON_.txt"),
+            ("com1", "com1_"),
+            ("console", "console"),
+        ] {
+            assert_eq!(sanitize_file_name(name), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn colliding_file_names_get_a_suffix() {
+        let mut names = UniqueNames::default();
+
+        let unique: Vec<String> = ["Deaths", "deaths", "deaths", "a:b", "a/b", "x", "x", "x_2"]
+            .into_iter()
+            .map(|x| names.file_name(x))
+            .collect();
+
+        assert_eq!(
+            unique,
+            ["Deaths", "deaths_2", "deaths_3", "a_b", "a_b_2", "x", "x_2", "x_2_2"]
+        );
+    }
+}
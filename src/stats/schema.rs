@@ -0,0 +1,603 @@
+use async_std::future::timeout;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_scalar, MySqlConnection};
+
+use super::sql::{execute, SqlOptions};
+use crate::error::Error;
+
+/// Added to stats tables created before rows were tagged with a region
+const REGION_COLUMN: &str = "region VARCHAR(64) NOT NULL DEFAULT 'unknown'";
+/// Added to stats tables created before rows were tagged with a world
+const WORLD_COLUMN: &str = "world VARCHAR(64) NOT NULL DEFAULT 'unknown'";
+
+/// Added to every table created before rows were tagged with a tenant
+pub(super) const TENANT_COLUMN: &str = "tenant_id VARCHAR(64) NULL DEFAULT NULL";
+
+/// Added to stats tables created without a surrogate key
+const ID_COLUMN: &str = "id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY FIRST";
+
+/// Name of the FULLTEXT index on player names, named so it is only ever
+/// added once
+const PLAYER_FULLTEXT_INDEX: &str = "player_name_fulltext";
+
+/// The table names used for one write, including any schema prefix
+pub(super) struct Tables {
+    pub players: String,
+    pub objectives: String,
+    pub stats: String,
+    pub score_events: String,
+    pub objective_totals: String,
+    pub schema_migrations: String,
+    pub excluded_players: String,
+    pub objective_display_names: String,
+    pub player_scores_wide: String,
+}
+
+/// A statement of the schema. MySQL has no `ADD COLUMN IF NOT EXISTS`,
+/// so columns and indexes are only added after checking the table doesn't
+/// have them yet.
+#[derive(Debug, Clone)]
+pub(super) enum SchemaChange {
+    Statement(String),
+    AddColumn {
+        table: String,
+        column: &'static str,
+        definition: String,
+    },
+    AddIndex {
+        table: String,
+        index: &'static str,
+        definition: String,
+    },
+}
+
+impl SchemaChange {
+    pub(super) fn sql(&self) -> String {
+        match self {
+            SchemaChange::Statement(statement) => statement.clone(),
+            SchemaChange::AddColumn {
+                table, definition, ..
+            } => format!("ALTER TABLE {} ADD COLUMN {}", table, definition),
+            SchemaChange::AddIndex {
+                table, definition, ..
+            } => format!("ALTER TABLE {} ADD {}", table, definition),
+        }
+    }
+}
+
+impl SqlOptions {
+    pub(super) fn tables(&self) -> Result<Tables, Error> {
+        let prefix = match &self.schema_prefix {
+            Some(prefix) => {
+                //the prefix ends up in the statements as is, so only allow plain identifiers
+                if prefix.is_empty()
+                    || !prefix
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    return Err(Error::InvalidSchemaPrefix(prefix.clone()));
+                }
+
+                format!("{}_", prefix)
+            }
+            None => String::new(),
+        };
+
+        Ok(Tables {
+            players: format!("{}players", prefix),
+            objectives: format!("{}objectives", prefix),
+            stats: format!("{}stats", prefix),
+            score_events: format!("{}score_events", prefix),
+            objective_totals: format!("{}objective_totals", prefix),
+            schema_migrations: format!("{}schema_migrations", prefix),
+            excluded_players: format!("{}excluded_players", prefix),
+            objective_display_names: format!("{}objective_display_names", prefix),
+            player_scores_wide: format!("{}player_scores_wide", prefix),
+        })
+    }
+
+    /// The table the stats of `timestamp` are inserted into, which is the
+    /// monthly partition when partitioning by date
+    pub(super) fn stats_table(&self, tables: &Tables, timestamp: DateTime<Utc>) -> String {
+        match self.partition_by_date {
+            true => format!("{}_{}", tables.stats, timestamp.format("%Y%m")),
+            false => tables.stats.clone(),
+        }
+    }
+
+    /// The statements creating the tables used with these options, tables
+    /// that already exist are left untouched.
+    ///
+    /// The statements adding columns and indexes to tables created by older
+    /// versions fail when the table already has them,
+    /// [`SqlOptions::create_tables`] checks each table first.
+    ///
+    /// When partitioning by date the stats tables are created per month by
+    /// [`SqlOptions::partition_sql`] instead.
+    pub fn schema_sql(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .schema_changes()?
+            .iter()
+            .map(SchemaChange::sql)
+            .collect())
+    }
+
+    fn schema_changes(&self) -> Result<Vec<SchemaChange>, Error> {
+        let tables = self.tables()?;
+
+        let mut statements = vec![
+            SchemaChange::Statement(objectives_table_sql(&tables)),
+            SchemaChange::Statement(self.players_table_sql(&tables)),
+            add_tenant(&tables.objectives),
+            add_tenant(&tables.players),
+        ];
+
+        //players tables created without the index get it added
+        if self.fulltext_player_index {
+            statements.push(SchemaChange::AddIndex {
+                table: tables.players.clone(),
+                index: PLAYER_FULLTEXT_INDEX,
+                definition: format!("FULLTEXT INDEX {} (player_name)", PLAYER_FULLTEXT_INDEX),
+            });
+        }
+
+        if !self.partition_by_date {
+            statements.push(SchemaChange::Statement(
+                self.stats_table_sql(&tables, &tables.stats)?,
+            ));
+            statements.push(add_region(&tables.stats));
+            statements.push(add_world(&tables.stats));
+            statements.push(add_tenant(&tables.stats));
+
+            if self.auto_increment_id {
+                statements.push(add_id(&tables.stats));
+            }
+
+            if !self.scaled_scores.is_empty() {
+                statements.push(self.add_scaled_score(&tables.stats));
+            }
+        }
+
+        if self.aggregates {
+            statements.push(SchemaChange::Statement(objective_totals_table_sql(&tables)));
+            statements.push(add_tenant(&tables.objective_totals));
+        }
+
+        statements.push(SchemaChange::Statement(objective_display_names_table_sql(
+            &tables,
+        )));
+        statements.push(add_tenant(&tables.objective_display_names));
+
+        Ok(statements)
+    }
+
+    /// The statements creating the monthly stats table for `timestamp` and
+    /// the `stats` view joining it with the `partitions` that already exist.
+    ///
+    /// Existing partitions get the region, world and tenant columns first so
+    /// they can all be joined in the view. Like with [`SqlOptions::schema_sql`]
+    /// adding a column fails when the partition already has it,
+    /// [`SqlOptions::prepare_partition`] checks each partition first.
+    pub fn partition_sql(
+        &self,
+        timestamp: DateTime<Utc>,
+        partitions: &[String],
+    ) -> Result<Vec<String>, Error> {
+        Ok(self
+            .partition_changes(timestamp, partitions)?
+            .iter()
+            .map(SchemaChange::sql)
+            .collect())
+    }
+
+    fn partition_changes(
+        &self,
+        timestamp: DateTime<Utc>,
+        partitions: &[String],
+    ) -> Result<Vec<SchemaChange>, Error> {
+        let tables = self.tables()?;
+        let partition = self.stats_table(&tables, timestamp);
+
+        let mut view_partitions: Vec<&String> = partitions.iter().collect();
+
+        if !view_partitions.contains(&&partition) {
+            view_partitions.push(&partition);
+        }
+
+        view_partitions.sort_unstable();
+
+        let view_selects: Vec<String> = view_partitions
+            .iter()
+            .map(|partition| format!("SELECT * FROM {}", partition))
+            .collect();
+
+        let mut statements = vec![SchemaChange::Statement(
+            self.stats_table_sql(&tables, &partition)?,
+        )];
+        statements.extend(partitions.iter().map(|partition| add_region(partition)));
+        statements.extend(partitions.iter().map(|partition| add_world(partition)));
+        statements.extend(partitions.iter().map(|partition| add_tenant(partition)));
+
+        //the view needs the same columns in every partition
+        if self.auto_increment_id {
+            statements.extend(partitions.iter().map(|partition| add_id(partition)));
+        }
+        //added to the new partition too so the column ends up last everywhere
+        if !self.scaled_scores.is_empty() {
+            statements.extend(
+                view_partitions
+                    .iter()
+                    .map(|partition| self.add_scaled_score(partition)),
+            );
+        }
+        statements.push(SchemaChange::Statement(format!(
+            "CREATE OR REPLACE VIEW {} AS {}",
+            tables.stats,
+            view_selects.join(" UNION ALL ")
+        )));
+
+        Ok(statements)
+    }
+
+    /// Makes sure the monthly stats table for `timestamp` exists and is part
+    /// of the `stats` view, does nothing unless partitioning by date
+    pub async fn prepare_partition(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        if !self.partition_by_date {
+            return Ok(());
+        }
+
+        let partitions = self.partitions(&mut *conn).await?;
+
+        for change in self.partition_changes(timestamp, &partitions)? {
+            self.apply(&mut *conn, &change).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the stats older than `ttl_days` from the stats table, or from
+    /// every monthly table when partitioning by date, returning how many
+    /// rows were deleted. The objective totals of `aggregates` expire the
+    /// same way. Does nothing without `ttl_days`.
+    pub async fn delete_expired(&self, conn: &mut MySqlConnection) -> Result<u64, Error> {
+        let Some(ttl_days) = self.ttl_days else {
+            return Ok(0);
+        };
+
+        let mut tables = match self.partition_by_date {
+            true => self.partitions(&mut *conn).await?,
+            false => vec![self.tables()?.stats],
+        };
+        if self.aggregates {
+            tables.push(self.tables()?.objective_totals);
+        }
+
+        let mut deleted = 0;
+
+        for table in tables {
+            let delete = format!("DELETE FROM {} WHERE time < NOW() - INTERVAL ? DAY", table);
+
+            deleted += timeout(
+                self.query_timeout,
+                query(&delete).bind(ttl_days).execute(&mut *conn),
+            )
+            .await
+            .map_err(|_| Error::SQLTimeout("query"))??
+            .rows_affected();
+        }
+
+        Ok(deleted)
+    }
+
+    /// The monthly stats tables that exist
+    pub(super) async fn partitions(
+        &self,
+        conn: &mut MySqlConnection,
+    ) -> Result<Vec<String>, Error> {
+        Ok(query_scalar(
+            "SELECT CAST(table_name AS CHAR) FROM information_schema.tables \
+            WHERE table_schema = DATABASE() AND table_type = 'BASE TABLE' AND table_name REGEXP ?",
+        )
+        .bind(format!("^{}_[0-9]{{6}}$", self.tables()?.stats))
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Creates any missing tables, and adds the columns and indexes tables
+    /// created by older versions are missing
+    pub async fn create_tables(&self, conn: &mut MySqlConnection) -> Result<(), Error> {
+        for change in self.schema_changes()? {
+            self.apply(&mut *conn, &change).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `change` unless it adds a column or index the table already has
+    pub(super) async fn apply(
+        &self,
+        conn: &mut MySqlConnection,
+        change: &SchemaChange,
+    ) -> Result<(), Error> {
+        match self.is_applied(&mut *conn, change).await? {
+            true => Ok(()),
+            false => execute(query(&change.sql()), conn, self).await,
+        }
+    }
+
+    /// Whether the table already has the column or index `change` adds
+    pub(super) async fn is_applied(
+        &self,
+        conn: &mut MySqlConnection,
+        change: &SchemaChange,
+    ) -> Result<bool, Error> {
+        let (check, table, name) = match change {
+            SchemaChange::Statement(_) => return Ok(false),
+            SchemaChange::AddColumn { table, column, .. } => (
+                "SELECT COUNT(*) FROM information_schema.columns \
+                WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ?",
+                table,
+                column,
+            ),
+            SchemaChange::AddIndex { table, index, .. } => (
+                "SELECT COUNT(*) FROM information_schema.statistics \
+                WHERE table_schema = DATABASE() AND table_name = ? AND index_name = ?",
+                table,
+                index,
+            ),
+        };
+
+        let count: i64 = timeout(
+            self.query_timeout,
+            query_scalar(check)
+                .bind(table)
+                .bind(*name)
+                .fetch_one(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??;
+
+        Ok(count > 0)
+    }
+}
+
+impl SqlOptions {
+    pub(super) fn players_table_sql(&self, tables: &Tables) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    player_name VARCHAR(255) UNIQUE,
+    {},
+    PRIMARY KEY (id){}
+)",
+            tables.players,
+            TENANT_COLUMN,
+            match self.fulltext_player_index {
+                true => format!(
+                    ",\n    FULLTEXT INDEX {} (player_name)",
+                    PLAYER_FULLTEXT_INDEX
+                ),
+                false => String::new(),
+            }
+        )
+    }
+
+    pub(super) fn stats_table_sql(&self, tables: &Tables, name: &str) -> Result<String, Error> {
+        let compression = match self.use_compression {
+            true => {
+                if ![1, 2, 4, 8, 16].contains(&self.key_block_size) {
+                    return Err(Error::InvalidKeyBlockSize(self.key_block_size));
+                }
+
+                format!(
+                    "ROW_FORMAT=COMPRESSED KEY_BLOCK_SIZE={}",
+                    self.key_block_size
+                )
+            }
+            false => "PAGE_COMPRESSED = 1 PAGE_COMPRESSION_LEVEL = 9".to_string(),
+        };
+
+        let id = match self.auto_increment_id {
+            true => "\n    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,",
+            false => "",
+        };
+
+        let score_type = match self.soft_schema {
+            true => "VARCHAR(255)",
+            false => "BIGINT",
+        };
+
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}
+    score {},
+    player_name VARCHAR(255),
+    objective_name VARCHAR(255),
+    time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    {},
+    {},
+    {},
+    FOREIGN KEY (player_name) REFERENCES {} (player_name),
+    FOREIGN KEY (objective_name) REFERENCES {} (objective_name)
+) ENGINE InnoDB {}",
+            name,
+            id,
+            score_type,
+            REGION_COLUMN,
+            WORLD_COLUMN,
+            TENANT_COLUMN,
+            tables.players,
+            tables.objectives,
+            compression
+        ))
+    }
+}
+
+impl SqlOptions {
+    fn add_scaled_score(&self, table: &str) -> SchemaChange {
+        SchemaChange::AddColumn {
+            table: table.to_string(),
+            column: "scaled_score",
+            definition: format!("scaled_score DECIMAL(38, {})", self.scaled_decimals),
+        }
+    }
+}
+
+pub(super) fn objectives_table_sql(tables: &Tables) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    objective_name VARCHAR(255) UNIQUE,
+    display_name VARCHAR(255),
+    criteria_name VARCHAR(255),
+    {},
+    PRIMARY KEY (id)
+)",
+        tables.objectives, TENANT_COLUMN
+    )
+}
+
+/// The display names an objective had, the current one has no `valid_to`
+pub(super) fn objective_display_names_table_sql(tables: &Tables) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+    objective_name VARCHAR(255) NOT NULL,
+    display_name VARCHAR(255),
+    valid_from TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    valid_to TIMESTAMP NULL DEFAULT NULL,
+    {},
+    INDEX objective_display_names_valid (objective_name, valid_from),
+    FOREIGN KEY (objective_name) REFERENCES {} (objective_name)
+) ENGINE InnoDB",
+        tables.objective_display_names, TENANT_COLUMN, tables.objectives
+    )
+}
+
+/// The totals are small enough to never need partitioning
+pub(super) fn objective_totals_table_sql(tables: &Tables) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+    objective_name VARCHAR(255),
+    time TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    total BIGINT,
+    player_count BIGINT,
+    max_score BIGINT,
+    {},
+    {},
+    {},
+    INDEX objective_totals_time (objective_name, time),
+    FOREIGN KEY (objective_name) REFERENCES {} (objective_name)
+) ENGINE InnoDB",
+        tables.objective_totals, REGION_COLUMN, WORLD_COLUMN, TENANT_COLUMN, tables.objectives
+    )
+}
+
+pub(super) fn excluded_players_table_sql(tables: &Tables) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+    player_name VARCHAR(255) NOT NULL PRIMARY KEY,
+    {}
+) ENGINE InnoDB",
+        tables.excluded_players, TENANT_COLUMN
+    )
+}
+
+fn add_column(table: &str, column: &'static str, definition: &str) -> SchemaChange {
+    SchemaChange::AddColumn {
+        table: table.to_string(),
+        column,
+        definition: definition.to_string(),
+    }
+}
+
+fn add_id(table: &str) -> SchemaChange {
+    add_column(table, "id", ID_COLUMN)
+}
+
+pub(super) fn add_world(table: &str) -> SchemaChange {
+    add_column(table, "world", WORLD_COLUMN)
+}
+
+pub(super) fn add_region(table: &str) -> SchemaChange {
+    add_column(table, "region", REGION_COLUMN)
+}
+
+pub(super) fn add_tenant(table: &str) -> SchemaChange {
+    add_column(table, "tenant_id", TENANT_COLUMN)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Options using every optional column and index
+    fn everything() -> SqlOptions {
+        SqlOptions {
+            fulltext_player_index: true,
+            auto_increment_id: true,
+            aggregates: true,
+            scaled_scores: HashMap::from([("walk".to_string(), 0.01)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn alters_use_syntax_mysql_knows() {
+        let opts = everything();
+        let timestamp = DateTime::from_timestamp(1_790_000_000, 0).unwrap();
+
+        let statements = opts.schema_sql().unwrap().into_iter().chain(
+            opts.partition_sql(timestamp, &["stats_202608".to_string()])
+                .unwrap(),
+        );
+
+        for statement in statements.filter(|x| x.starts_with("ALTER TABLE")) {
+            assert!(!statement.contains("IF NOT EXISTS"), "{}", statement);
+        }
+    }
+
+    #[test]
+    fn additions_name_what_they_add() {
+        let changes = everything().schema_changes().unwrap();
+
+        let mut added: Vec<(&str, &str)> = changes
+            .iter()
+            .filter_map(|x| match x {
+                SchemaChange::Statement(_) => None,
+                SchemaChange::AddColumn { table, column, .. } => Some((table.as_str(), *column)),
+                SchemaChange::AddIndex { table, index, .. } => Some((table.as_str(), *index)),
+            })
+            .collect();
+        added.sort_unstable();
+
+        assert_eq!(
+            added,
+            [
+                ("objective_display_names", "tenant_id"),
+                ("objective_totals", "tenant_id"),
+                ("objectives", "tenant_id"),
+                ("players", "player_name_fulltext"),
+                ("players", "tenant_id"),
+                ("stats", "id"),
+                ("stats", "region"),
+                ("stats", "scaled_score"),
+                ("stats", "tenant_id"),
+                ("stats", "world"),
+            ]
+        );
+        //the column of an addition is the one its definition starts with
+        for change in changes.iter() {
+            if let SchemaChange::AddColumn {
+                column, definition, ..
+            } = change
+            {
+                assert!(definition.starts_with(&format!("{} ", column)));
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::Write;
+
+use csv::ByteRecord;
+
+use super::{CsvOptions, Stats};
+use crate::error::Error;
+
+/// Number of characters in every sparkline
+const SPARKLINE_CHARS: usize = 10;
+/// Each Braille character holds two points, one per dot column
+const SPARKLINE_POINTS: usize = SPARKLINE_CHARS * 2;
+const BRAILLE_BLANK: u32 = 0x2800;
+/// Dots of the left and right column of a Braille character from the
+/// bottom up
+const LEFT_DOTS: [u32; 4] = [0x40, 0x04, 0x02, 0x01];
+const RIGHT_DOTS: [u32; 4] = [0x80, 0x20, 0x10, 0x08];
+
+impl Stats {
+    /// Writes a csv like [`Stats::write_csv`] with an `{objective}_trend`
+    /// column per objective appended after the scores, holding a Braille
+    /// sparkline of the score over `history` and then these stats.
+    ///
+    /// `history` is ordered from oldest to newest, and players missing from
+    /// a snapshot count as a score of 0 in it. Only the newest 20 points are
+    /// drawn, each line is scaled to its own lowest and highest score.
+    pub fn write_csv_with_sparklines(&self, w: impl Write, history: &[Stats]) -> Result<(), Error> {
+        let titles = self.titles();
+        let players = self.player_names();
+
+        let snapshots: Vec<&Stats> = history.iter().chain([self]).collect();
+        let snapshots = &snapshots[snapshots.len().saturating_sub(SPARKLINE_POINTS)..];
+
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+        let mut line = String::new();
+        let mut points = Vec::with_capacity(SPARKLINE_POINTS);
+
+        for column in self.columns(&titles, &CsvOptions::default(), false)? {
+            record.push_field(column.name.as_bytes());
+        }
+        for title in &titles {
+            record.push_field(format!("{}_trend", title).as_bytes());
+        }
+        w.write_byte_record(&record)?;
+
+        for player in players {
+            record.clear();
+            record.push_field(player.as_bytes());
+
+            for title in &titles {
+                record.push_field(
+                    score_buffer
+                        .format(score_of(self, title, player))
+                        .as_bytes(),
+                );
+            }
+
+            for title in &titles {
+                points.clear();
+                points.extend(snapshots.iter().map(|x| score_of(x, title, player)));
+
+                line.clear();
+                push_sparkline(&points, &mut line);
+                record.push_field(line.as_bytes());
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+fn score_of(stats: &Stats, objective: &str, player: &str) -> i64 {
+    stats
+        .player_scores
+        .get(objective)
+        .and_then(|x| x.iter().find(|x| x.player_name() == player))
+        .map_or(0, |x| x.score)
+}
+
+/// Appends a sparkline of `SPARKLINE_CHARS` characters with the points
+/// right aligned, every point is a bar from one to four dots high
+fn push_sparkline(points: &[i64], buffer: &mut String) {
+    let min = points.iter().copied().min().unwrap_or(0) as i128;
+    let max = points.iter().copied().max().unwrap_or(0) as i128;
+
+    let height = |point: i64| match max > min {
+        true => 1 + ((point as i128 - min) * 3 + (max - min) / 2) / (max - min),
+        false => 2,
+    } as usize;
+
+    //leading slots without a point stay blank
+    let mut slots = [0; SPARKLINE_POINTS];
+    for (slot, point) in slots[SPARKLINE_POINTS - points.len()..]
+        .iter_mut()
+        .zip(points)
+    {
+        *slot = height(*point);
+    }
+
+    for pair in slots.chunks(2) {
+        let dots: u32 =
+            LEFT_DOTS[..pair[0]].iter().sum::<u32>() + RIGHT_DOTS[..pair[1]].iter().sum::<u32>();
+        buffer
+            .push(char::from_u32(BRAILLE_BLANK + dots).expect("braille patterns are valid chars"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn sparkline(points: &[i64]) -> String {
+        let mut line = String::new();
+        push_sparkline(points, &mut line);
+        line
+    }
+
+    /// Blank Braille characters before the last one
+    fn padded(last: &str) -> String {
+        "\u{2800}".repeat(SPARKLINE_CHARS - last.chars().count()) + last
+    }
+
+    #[test]
+    fn points_are_scaled_between_the_extremes() {
+        assert_eq!(sparkline(&[]), padded(""));
+        //one dot for the lowest and four for the highest point
+        assert_eq!(sparkline(&[0, 3]), padded("⣸"));
+        assert_eq!(sparkline(&[-10, i64::MAX]), padded("⣸"));
+        //flat lines are drawn at half height
+        assert_eq!(sparkline(&[5, 5, 5]), padded("⢠⣤"));
+        assert_eq!(
+            sparkline(&[0; SPARKLINE_POINTS]),
+            "⣤".repeat(SPARKLINE_CHARS)
+        );
+    }
+
+    #[test]
+    fn trends_follow_the_history() {
+        let stats = |scores: &[(&str, &str, i32)]| {
+            let nbt = fixture::scoreboard(
+                &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+                scores.iter().copied(),
+            );
+            Stats::from_gzip_reader(&nbt[..]).unwrap()
+        };
+        let history = [stats(&[("ville", "deaths", 0), ("ville", "kills", 1)])];
+        let current = stats(&[
+            ("ville", "deaths", 3),
+            ("anna", "kills", 7),
+            ("ville", "kills", 1),
+        ]);
+
+        let mut out = Vec::new();
+        current
+            .write_csv_with_sparklines(&mut out, &history)
+            .unwrap();
+
+        //anna is missing from the history and her deaths from both
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "Players,deaths,kills,deaths_trend,kills_trend\n\
+                anna,0,7,{flat},{rising}\n\
+                ville,3,1,{rising},{flat}\n",
+                flat = padded("⣤"),
+                rising = padded("⣸"),
+            )
+        );
+    }
+
+    #[test]
+    fn only_the_newest_points_are_drawn() {
+        let history: Vec<Stats> = (0..30)
+            .map(|i| {
+                let nbt = fixture::scoreboard(
+                    &[("deaths", "deathCount")],
+                    [("ville", "deaths", i32::from(i >= 10))].into_iter(),
+                );
+                Stats::from_gzip_reader(&nbt[..]).unwrap()
+            })
+            .collect();
+        let (current, history) = history.split_last().unwrap();
+
+        let mut out = Vec::new();
+        current
+            .write_csv_with_sparklines(&mut out, history)
+            .unwrap();
+
+        //the zeros of the first ten snapshots fall out of the line
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "Players,deaths,deaths_trend\nville,1,{}\n",
+                "⣤".repeat(SPARKLINE_CHARS)
+            )
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use csv::ByteRecord;
+
+use super::{sanitize::UniqueNames, Stats};
+use crate::{atomic_file::AtomicFile, error::Error};
+
+/// A file written by [`Stats::write_csv_per_objective`]
+#[derive(Debug, Clone)]
+pub struct ObjectiveFile {
+    pub objective: String,
+    pub path: PathBuf,
+    /// Number of players in the file, not counting the header
+    pub rows: usize,
+}
+
+impl Stats {
+    /// Writes one `player,score` csv per objective into `dir`, named after
+    /// the objective with [`sanitize_file_name`](super::sanitize_file_name). Objectives without
+    /// scores are skipped unless `include_empty` is set.
+    ///
+    /// Names that end up the same, ignoring case, get a `_2`, `_3`, …
+    /// suffix in objective name order. Every file is written atomically.
+    pub fn write_csv_per_objective(
+        &self,
+        dir: &Path,
+        include_empty: bool,
+    ) -> Result<Vec<ObjectiveFile>, Error> {
+        let mut objectives: Vec<&String> = self.objectives.keys().collect();
+        objectives.sort_unstable();
+
+        let mut names = UniqueNames::default();
+        let mut files = Vec::new();
+
+        for objective in objectives {
+            let mut scores: Vec<(&str, i64)> = self
+                .player_scores
+                .get(objective)
+                .into_iter()
+                .flatten()
+                .map(|x| (x.player_name(), x.score))
+                .collect();
+
+            if scores.is_empty() && !include_empty {
+                continue;
+            }
+
+            scores.sort_unstable();
+
+            let name = names.file_name(objective);
+            let path = dir.join(format!("{}.csv", name));
+            let mut file = AtomicFile::create(&path)?;
+            let mut w = csv::Writer::from_writer(&mut file);
+
+            let mut record = ByteRecord::new();
+            let mut score_buffer = itoa::Buffer::new();
+
+            w.write_record(["player", "score"])?;
+
+            for (player, score) in scores.iter() {
+                record.clear();
+                record.push_field(player.as_bytes());
+                record.push_field(score_buffer.format(*score).as_bytes());
+                w.write_byte_record(&record)?;
+            }
+
+            w.flush()?;
+            drop(w);
+            file.commit()?;
+
+            files.push(ObjectiveFile {
+                objective: objective.clone(),
+                path,
+                rows: scores.len(),
+            });
+        }
+
+        Ok(files)
+    }
+}
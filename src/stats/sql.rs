@@ -0,0 +1,573 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use async_std::future::timeout;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    mysql::MySqlArguments, query, query::Query, query_scalar, Connection, Execute, Executor, MySql,
+    MySqlConnection,
+};
+
+use super::{
+    schema::Tables, EncryptionKey, Objectives, PlayerOrder, PlayerScore, ScoreEntry, Stats,
+};
+use crate::error::Error;
+
+/// The database the generated statements are for, the export itself only
+/// writes to MySQL
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    #[default]
+    MySql,
+    Postgres,
+}
+
+/// Options controlling how stats are written to the database
+#[derive(Debug, Clone)]
+pub struct SqlOptions {
+    /// How long a single connection attempt may take
+    pub connect_timeout: Duration,
+    /// How long a single statement may take
+    pub query_timeout: Duration,
+    /// How many times a failed connection attempt is retried
+    pub max_retries: u32,
+    /// Prepended to every table name as `{prefix}_`, may only contain
+    /// alphanumeric characters and underscores
+    pub schema_prefix: Option<String>,
+    /// Insert into monthly `stats_YYYYMM` tables, with a `stats` view
+    /// over all of them, instead of a single `stats` table
+    pub partition_by_date: bool,
+    /// Tag every inserted score with this region, rows without one
+    /// default to `unknown`
+    pub region: Option<String>,
+    /// Tag every inserted score with this world, for servers exporting
+    /// several worlds into the same tables. Rows without one default to
+    /// `unknown`.
+    pub world: Option<String>,
+    /// Create stats tables with `ROW_FORMAT=COMPRESSED` instead of page
+    /// compression, for servers without page compression support
+    pub use_compression: bool,
+    /// The `KEY_BLOCK_SIZE` in kilobytes used with `use_compression`,
+    /// one of 1, 2, 4, 8 or 16
+    pub key_block_size: u8,
+    /// Create the score column of stats tables as `VARCHAR(255)` instead
+    /// of `BIGINT`, leaving type checks to whatever reads the table
+    pub soft_schema: bool,
+    /// Create a FULLTEXT index on the player names of the players table for
+    /// searching by part of a name
+    pub fulltext_player_index: bool,
+    /// Give stats tables an `id BIGINT AUTO_INCREMENT` primary key, for
+    /// ORMs that need a single column key
+    pub auto_increment_id: bool,
+    /// Store player names encrypted with this key as hex, read them back
+    /// with [`Stats::decrypt_sql_export`]
+    pub encrypt_player_names: Option<EncryptionKey>,
+    /// The order players are inserted in
+    pub player_order: PlayerOrder,
+    /// Stats older than this many days are deleted by
+    /// [`SqlOptions::delete_expired`]
+    pub ttl_days: Option<u32>,
+    /// Also store the score of every objective multiplied by its factor
+    /// here in a `scaled_score` column, for datapacks keeping fixed point
+    /// values like money times 100. Objectives without a factor store their
+    /// raw score in it.
+    pub scaled_scores: HashMap<String, f64>,
+    /// Decimal places of the `scaled_score` column, only used when it is
+    /// created
+    pub scaled_decimals: u8,
+    /// Also insert a row per objective into `objective_totals` with the
+    /// total, number of players and highest score of the inserted scores,
+    /// for dashboards that don't want to scan the stats table
+    pub aggregates: bool,
+    /// Tag every inserted row with this tenant in a `tenant_id` column, for
+    /// PostgreSQL row level security restricting each tenant to their own
+    /// rows, see [`Stats::write_sql_with_row_level_security`]. At most 64
+    /// characters.
+    pub tenant_id: Option<String>,
+}
+
+impl Default for SqlOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(30),
+            query_timeout: Duration::from_secs(60),
+            max_retries: 0,
+            schema_prefix: None,
+            partition_by_date: false,
+            region: None,
+            world: None,
+            use_compression: false,
+            key_block_size: 4,
+            soft_schema: false,
+            fulltext_player_index: false,
+            auto_increment_id: false,
+            encrypt_player_names: None,
+            player_order: PlayerOrder::default(),
+            ttl_days: None,
+            scaled_scores: HashMap::new(),
+            scaled_decimals: 2,
+            aggregates: false,
+            tenant_id: None,
+        }
+    }
+}
+
+impl SqlOptions {
+    /// Opens a connection with `conn_factory`, retrying failed or timed out
+    /// attempts up to `max_retries` times
+    pub async fn connect<F, Fut, C>(&self, mut conn_factory: F) -> Result<C, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<C, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let result = match timeout(self.connect_timeout, conn_factory()).await {
+                Ok(conn) => conn.map_err(Error::from),
+                Err(_) => Err(Error::SQLTimeout("connect")),
+            };
+
+            match result {
+                Ok(conn) => return Ok(conn),
+                Err(e) if attempt >= self.max_retries => return Err(e),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
+    /// An insert of `columns` into `table`, which also names the tenant
+    /// column when rows are tagged with a tenant. `insert` is the start of
+    /// the statement like `INSERT IGNORE INTO`.
+    pub(super) fn insert_sql(&self, insert: &str, table: &str, columns: &[&str]) -> String {
+        let mut columns = columns.to_vec();
+
+        if self.tenant_id.is_some() {
+            columns.push("tenant_id");
+        }
+
+        format!(
+            "{} {} ({}) VALUES ({})",
+            insert,
+            table,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(",")
+        )
+    }
+
+    /// Binds the tenant of [`SqlOptions::insert_sql`] if rows are tagged
+    /// with one, always the last value
+    pub(super) fn bind_tenant<'q>(
+        &'q self,
+        query: Query<'q, MySql, MySqlArguments>,
+    ) -> Query<'q, MySql, MySqlArguments> {
+        match &self.tenant_id {
+            Some(tenant_id) => query.bind(tenant_id),
+            None => query,
+        }
+    }
+
+    /// The insert for a single score, which only names the region, world,
+    /// scaled score and tenant columns when they are used so tables without
+    /// them keep working
+    fn insert_stat_sql(&self, tables: &Tables, timestamp: DateTime<Utc>) -> String {
+        let table = self.stats_table(tables, timestamp);
+
+        let mut columns = vec!["score", "player_name", "objective_name", "time"];
+
+        if self.region.is_some() {
+            columns.push("region");
+        }
+        if self.world.is_some() {
+            columns.push("world");
+        }
+        if !self.scaled_scores.is_empty() {
+            columns.push("scaled_score");
+        }
+
+        self.insert_sql("INSERT INTO", &table, &columns)
+    }
+
+    /// The insert for the totals of one objective, naming the region and
+    /// world only when they are used like [`SqlOptions::insert_stat_sql`]
+    fn insert_totals_sql(&self, tables: &Tables) -> String {
+        let mut columns = vec![
+            "objective_name",
+            "time",
+            "total",
+            "player_count",
+            "max_score",
+        ];
+
+        if self.region.is_some() {
+            columns.push("region");
+        }
+        if self.world.is_some() {
+            columns.push("world");
+        }
+
+        self.insert_sql("INSERT INTO", &tables.objective_totals, &columns)
+    }
+
+    /// Binds the region and world if they are used
+    fn bind_location<'q>(
+        &'q self,
+        query: Query<'q, MySql, MySqlArguments>,
+    ) -> Query<'q, MySql, MySqlArguments> {
+        let query = match &self.region {
+            Some(region) => query.bind(region),
+            None => query,
+        };
+
+        match &self.world {
+            Some(world) => query.bind(world),
+            None => query,
+        }
+    }
+
+    /// Binds the values of the optional columns of [`SqlOptions::insert_stat_sql`]
+    fn bind_optional<'q>(
+        &'q self,
+        query: Query<'q, MySql, MySqlArguments>,
+        objective: &str,
+        score: i64,
+    ) -> Query<'q, MySql, MySqlArguments> {
+        let query = self.bind_location(query);
+
+        let query = match self.scaled_scores.is_empty() {
+            true => query,
+            false => {
+                query.bind(score as f64 * self.scaled_scores.get(objective).copied().unwrap_or(1.0))
+            }
+        };
+
+        self.bind_tenant(query)
+    }
+}
+
+/// What a write to the database left out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SqlWriteReport {
+    /// Players skipped because they are in the `excluded_players` table
+    pub excluded_players: usize,
+}
+
+impl Stats {
+    pub async fn write_to_sql(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        self.write_to_connection(conn, timestamp, &SqlOptions::default())
+            .await
+    }
+
+    /// Connects using `conn_factory` and writes the stats, giving up on
+    /// connecting or on a single statement after the configured timeouts.
+    ///
+    /// Only connecting is retried, a failed insert is never repeated.
+    pub async fn write_to_sql_with_options<F, Fut>(
+        &self,
+        conn_factory: F,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<MySqlConnection, sqlx::Error>>,
+    {
+        let mut conn = opts.connect(conn_factory).await?;
+
+        self.write_to_connection(&mut conn, timestamp, opts).await
+    }
+
+    /// Writes the stats into an already open connection using the
+    /// table names and query timeout from `opts`. The inserts run in one
+    /// transaction, so a failed write leaves nothing behind.
+    ///
+    /// Databases with a newer schema than this crate knows are refused
+    /// with [`Error::SchemaTooNew`].
+    ///
+    /// Players in the `excluded_players` table are skipped.
+    pub async fn write_to_connection(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error> {
+        self.write_to_connection_with_report(conn, timestamp, opts)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Like [`Stats::write_to_connection`], reporting how many players
+    /// were skipped
+    pub async fn write_to_connection_with_report(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<SqlWriteReport, Error> {
+        opts.check_schema_version(&mut *conn).await?;
+        opts.prepare_partition(&mut *conn, timestamp).await?;
+
+        let mut transaction = conn.begin().await?;
+        let report = self
+            .insert_into_connection_with_report(&mut transaction, timestamp, opts)
+            .await?;
+        transaction.commit().await?;
+
+        Ok(report)
+    }
+
+    /// Inserts the stats like [`Stats::write_to_connection`] but without
+    /// preparing the partition first, which lets the inserts run in a
+    /// transaction that the partition's DDL would otherwise commit
+    pub async fn insert_into_connection(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error> {
+        self.insert_into_connection_with_report(conn, timestamp, opts)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Like [`Stats::insert_into_connection`], reporting how many players
+    /// were skipped
+    pub async fn insert_into_connection_with_report(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<SqlWriteReport, Error> {
+        let tables = opts.tables()?;
+        let excluded = opts.excluded_players(&mut *conn).await?;
+
+        let (players, skipped): (Vec<&str>, Vec<&str>) = self
+            .players_in_order(opts.player_order)
+            .into_iter()
+            .partition(|x| !excluded.contains(*x));
+
+        let insert_player =
+            opts.insert_sql("INSERT IGNORE INTO", &tables.players, &["player_name"]);
+
+        for p in players.iter() {
+            execute(
+                opts.bind_tenant(query(&insert_player).bind(opts.stored_player_name(p))),
+                &mut *conn,
+                opts,
+            )
+            .await?;
+        }
+
+        let insert_objective = opts.insert_sql(
+            "INSERT IGNORE INTO",
+            &tables.objectives,
+            &["objective_name", "display_name", "criteria_name"],
+        );
+
+        for (name, obj) in self.objectives.iter() {
+            execute(
+                opts.bind_tenant(
+                    query(&insert_objective)
+                        .bind(name)
+                        .bind(&obj.display_name)
+                        .bind(&obj.criteria_name),
+                ),
+                &mut *conn,
+                opts,
+            )
+            .await?;
+        }
+
+        opts.record_display_names(&mut *conn, &self.objectives, timestamp)
+            .await?;
+
+        let insert_stat = opts.insert_stat_sql(&tables, timestamp);
+
+        for (obj_name, player_scores) in self.player_scores.iter() {
+            for player_score in player_scores
+                .iter()
+                .filter(|x| !excluded.contains(x.player_name()))
+            {
+                execute(
+                    opts.bind_optional(
+                        query(&insert_stat)
+                            .bind(player_score.score)
+                            .bind(opts.stored_player_name(player_score.player_name()))
+                            .bind(obj_name)
+                            .bind(timestamp),
+                        obj_name,
+                        player_score.score,
+                    ),
+                    &mut *conn,
+                    opts,
+                )
+                .await?;
+            }
+        }
+
+        if opts.aggregates {
+            let insert_totals = opts.insert_totals_sql(&tables);
+
+            //every objective gets a row, objectives without scores a total of 0
+            for name in self.objectives.keys() {
+                let scores: Vec<&PlayerScore> = self
+                    .player_scores
+                    .get(name)
+                    .map_or(&[][..], Vec::as_slice)
+                    .iter()
+                    .filter(|x| !excluded.contains(x.player_name()))
+                    .collect();
+                let total: i128 = scores.iter().map(|x| x.score as i128).sum();
+
+                execute(
+                    opts.bind_tenant(
+                        opts.bind_location(
+                            query(&insert_totals)
+                                .bind(name)
+                                .bind(timestamp)
+                                .bind(total.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+                                .bind(scores.len() as i64)
+                                .bind(scores.iter().map(|x| x.score).max()),
+                        ),
+                    ),
+                    &mut *conn,
+                    opts,
+                )
+                .await?;
+            }
+        }
+
+        Ok(SqlWriteReport {
+            excluded_players: skipped.len(),
+        })
+    }
+}
+
+impl ScoreEntry {
+    /// Inserts a single streamed score along with its player and objective.
+    ///
+    /// When partitioning by date the partition has to be prepared with
+    /// [`SqlOptions::prepare_partition`] before the first entry.
+    ///
+    /// Only the name of the objective is known at this point, the rest is
+    /// filled in by [`write_objectives_to_sql`] once the stream has ended.
+    pub async fn write_to_sql(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error> {
+        let tables = opts.tables()?;
+
+        execute(
+            opts.bind_tenant(
+                query(&opts.insert_sql("INSERT IGNORE INTO", &tables.players, &["player_name"]))
+                    .bind(opts.stored_player_name(&self.player_name)),
+            ),
+            &mut *conn,
+            opts,
+        )
+        .await?;
+
+        execute(
+            opts.bind_tenant(
+                query(&opts.insert_sql(
+                    "INSERT IGNORE INTO",
+                    &tables.objectives,
+                    &["objective_name"],
+                ))
+                .bind(&self.objective_name),
+            ),
+            &mut *conn,
+            opts,
+        )
+        .await?;
+
+        execute(
+            opts.bind_optional(
+                query(&opts.insert_stat_sql(&tables, timestamp))
+                    .bind(self.score)
+                    .bind(opts.stored_player_name(&self.player_name))
+                    .bind(&self.objective_name)
+                    .bind(timestamp),
+                &self.objective_name,
+                self.score,
+            ),
+            &mut *conn,
+            opts,
+        )
+        .await
+    }
+}
+
+/// Inserts the objectives, updating the display and criteria names of
+/// objectives that already exist
+pub async fn write_objectives_to_sql(
+    conn: &mut MySqlConnection,
+    objectives: &Objectives,
+    opts: &SqlOptions,
+) -> Result<(), Error> {
+    let upsert_objective = format!(
+        "{} ON DUPLICATE KEY UPDATE display_name = VALUES(display_name), criteria_name = VALUES(criteria_name)",
+        opts.insert_sql(
+            "INSERT INTO",
+            &opts.tables()?.objectives,
+            &["objective_name", "display_name", "criteria_name"],
+        )
+    );
+
+    for (name, obj) in objectives.iter() {
+        execute(
+            opts.bind_tenant(
+                query(&upsert_objective)
+                    .bind(name)
+                    .bind(&obj.display_name)
+                    .bind(&obj.criteria_name),
+            ),
+            &mut *conn,
+            opts,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub(super) async fn execute<'q, E>(
+    query: Query<'q, MySql, MySqlArguments>,
+    conn: E,
+    opts: &SqlOptions,
+) -> Result<(), Error>
+where
+    E: Executor<'q, Database = MySql>,
+{
+    let statement = query.sql();
+
+    timeout(opts.query_timeout, query.execute(conn))
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))?
+        .map_err(|error| Error::SQLStatement {
+            statement: statement.to_string(),
+            error,
+        })?;
+
+    Ok(())
+}
+
+/// Whether the current database has a table named `table`
+pub(super) async fn table_exists(conn: &mut MySqlConnection, table: &str) -> Result<bool, Error> {
+    let count: i64 = query_scalar(
+        "SELECT COUNT(*) FROM information_schema.tables \
+        WHERE table_schema = DATABASE() AND table_name = ?",
+    )
+    .bind(table)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(count > 0)
+}
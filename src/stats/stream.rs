@@ -0,0 +1,366 @@
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use csv::ByteRecord;
+use flate2::read::GzDecoder;
+use nbt::Value;
+use serde::Serialize;
+
+use super::{
+    csv_export::LONG_CSV_HEADER, parse_objectives, NameNormalization, Objectives, PlayerScore,
+    Stats, OBJECTIVES, PLAYERSCORES, PLAYERSCORES_PATH,
+};
+use crate::error::Error;
+
+const TAG_END: u8 = 0x00;
+const TAG_STRING: u8 = 0x08;
+const TAG_LIST: u8 = 0x09;
+const TAG_COMPOUND: u8 = 0x0a;
+
+/// A single score from the PlayerScores list
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreEntry {
+    pub objective_name: String,
+    pub player_name: String,
+    pub score: i64,
+}
+
+/// Reads the gzipped nbt from `src` handing every player score to `sink` as
+/// soon as it is decoded, so the scores are never held in memory all at once.
+///
+/// The objectives are small and are returned once the whole file is read.
+pub fn stream_scores(
+    src: impl Read,
+    sink: impl FnMut(ScoreEntry) -> Result<(), Error>,
+) -> Result<Objectives, Error> {
+    stream_scores_normalized(src, &NameNormalization::default(), sink)
+}
+
+/// Like [`stream_scores`], normalizing every player name before it is
+/// handed to `sink`. The scores aren't held on to, so a player whose names
+/// merged can be handed over more than once for an objective.
+pub fn stream_scores_normalized(
+    src: impl Read,
+    normalization: &NameNormalization,
+    mut sink: impl FnMut(ScoreEntry) -> Result<(), Error>,
+) -> Result<Objectives, Error> {
+    let mut src = GzDecoder::new(src);
+
+    let mut objectives = None;
+
+    enter_data(&mut src)?;
+
+    while let Some((id, name)) = read_header(&mut src)? {
+        match (id, name.as_str()) {
+            (TAG_LIST, OBJECTIVES) => {
+                objectives = Some(parse_objectives(&Value::from_reader(id, &mut src)?)?);
+            }
+            (TAG_LIST, PLAYERSCORES) => {
+                stream_player_scores(&mut src, normalization, &mut sink)?;
+            }
+            _ => {
+                Value::from_reader(id, &mut src)?;
+            }
+        }
+    }
+
+    objectives.ok_or(Error::NBTMissingField(OBJECTIVES))
+}
+
+/// Writes a `player,objective,score` row for every score in the gzipped
+/// nbt from `src` that `keep` returns true for, in the order of the file,
+/// without holding the scores in memory. See [`Stats::write_csv_long`].
+pub fn stream_csv_long(
+    src: impl Read,
+    w: impl Write,
+    mut keep: impl FnMut(&ScoreEntry) -> bool,
+) -> Result<Objectives, Error> {
+    let mut w = csv::Writer::from_writer(w);
+    let mut record = ByteRecord::new();
+    let mut score_buffer = itoa::Buffer::new();
+
+    w.write_record(LONG_CSV_HEADER)?;
+
+    let objectives = stream_scores(src, |entry| {
+        if keep(&entry) {
+            record.clear();
+            record.push_field(entry.player_name.as_bytes());
+            record.push_field(entry.objective_name.as_bytes());
+            record.push_field(score_buffer.format(entry.score).as_bytes());
+            w.write_byte_record(&record)?;
+        }
+
+        Ok(())
+    })?;
+
+    w.flush()?;
+
+    Ok(objectives)
+}
+
+impl Stats {
+    /// Reads only the objectives from the gzipped nbt in `src`, stopping as
+    /// soon as they are found. Scores that come before the objectives are
+    /// skipped over without being turned into [`PlayerScore`]s.
+    pub fn objectives_only(src: impl Read) -> Result<Objectives, Error> {
+        let mut src = GzDecoder::new(src);
+
+        enter_data(&mut src)?;
+
+        while let Some((id, name)) = read_header(&mut src)? {
+            match (id, name.as_str()) {
+                (TAG_LIST, OBJECTIVES) => {
+                    return parse_objectives(&Value::from_reader(id, &mut src)?);
+                }
+                _ => {
+                    Value::from_reader(id, &mut src)?;
+                }
+            }
+        }
+
+        Err(Error::NBTMissingField(OBJECTIVES))
+    }
+}
+
+/// Reads up to the first child of the data compound, skipping anything before it
+fn enter_data(src: &mut impl Read) -> Result<(), Error> {
+    match read_header(src)? {
+        Some((TAG_COMPOUND, _)) => (),
+        _ => return Err(Error::NBTMissingField("data")),
+    }
+
+    while let Some((id, name)) = read_header(src)? {
+        if id == TAG_COMPOUND && name == "data" {
+            return Ok(());
+        }
+
+        Value::from_reader(id, src)?;
+    }
+
+    Err(Error::NBTMissingField("data"))
+}
+
+/// Decodes the list elements one at a time, the list header is read here
+fn stream_player_scores(
+    src: &mut impl Read,
+    normalization: &NameNormalization,
+    sink: &mut impl FnMut(ScoreEntry) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let element_id = read_byte(src)?;
+    let len = match Value::from_reader(0x03, src)? {
+        Value::Int(len) => len,
+        _ => unreachable!(),
+    };
+
+    for i in 0..len.max(0) as usize {
+        let player_score = Value::from_reader(element_id, src)?;
+
+        if let Value::Compound(player_scores_map) = &player_score {
+            let objective_name = player_scores_map
+                .get("Objective")
+                .ok_or(Error::NBTMissingField("Objective"))
+                .map_err(|e| e.in_nbt_list(PLAYERSCORES_PATH, i))?
+                .to_string();
+            let score = PlayerScore::from_value(&player_score, |x| match normalization.is_noop() {
+                true => Arc::from(x),
+                false => Arc::from(normalization.apply(x)),
+            })
+            .map_err(|e| e.in_nbt_list(PLAYERSCORES_PATH, i))?;
+
+            sink(ScoreEntry {
+                objective_name,
+                player_name: score.player_name.to_string(),
+                score: score.score,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a tag id and name, returns None at the end of a compound
+fn read_header(src: &mut impl Read) -> Result<Option<(u8, String)>, Error> {
+    let id = read_byte(src)?;
+
+    if id == TAG_END {
+        return Ok(None);
+    }
+
+    match Value::from_reader(TAG_STRING, src)? {
+        Value::String(name) => Ok(Some((id, name))),
+        _ => unreachable!(),
+    }
+}
+
+fn read_byte(src: &mut impl Read) -> Result<u8, Error> {
+    let mut byte = [0];
+    src.read_exact(&mut byte)?;
+
+    Ok(byte[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use super::*;
+    use crate::fixture;
+
+    /// Tracks the bytes allocated by each thread, so tests running at the
+    /// same time don't count each other's allocations
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+        static PEAK: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                //the thread locals are gone while a thread is shutting down
+                let _ = ALLOCATED.try_with(|allocated| {
+                    allocated.set(allocated.get() + layout.size());
+                    let _ = PEAK.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+                });
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            let _ = ALLOCATED
+                .try_with(|allocated| allocated.set(allocated.get().saturating_sub(layout.size())));
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// The most memory in use at once by `f` beyond what was in use before it
+    fn peak_memory(f: impl FnOnce()) -> usize {
+        let before = ALLOCATED.with(Cell::get);
+        PEAK.with(|peak| peak.set(before));
+
+        f();
+
+        PEAK.with(Cell::get) - before
+    }
+
+    fn stream_peak(players: usize) -> usize {
+        let file = fixture::generated(players, 10);
+        let mut count = 0;
+
+        let peak = peak_memory(|| {
+            stream_scores(&file[..], |_| {
+                count += 1;
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(count, players * 10);
+        peak
+    }
+
+    #[test]
+    fn streaming_memory_doesnt_grow_with_the_scores() {
+        let small = stream_peak(1_000);
+        let large = stream_peak(100_000);
+
+        //the decoder buffers and the objectives are the same for both
+        assert!(
+            large < small + 64 * 1024,
+            "10k scores peaked at {} bytes, 1M scores at {} bytes",
+            small,
+            large
+        );
+    }
+
+    #[test]
+    fn parsing_memory_grows_with_the_scores() {
+        //makes sure the allocations are actually counted
+        let file = fixture::generated(1_000, 10);
+        let peak = peak_memory(|| {
+            Stats::from_gzip_reader(&file[..]).unwrap();
+        });
+
+        assert!(peak > 10_000 * 10, "only peaked at {} bytes", peak);
+    }
+
+    #[test]
+    fn scores_are_streamed_in_file_order() {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("ville", "kills", 1),
+                ("anna", "deaths", 2),
+                ("ville", "deaths", 3),
+            ]
+            .into_iter(),
+        );
+        let mut entries = Vec::new();
+
+        let objectives = stream_scores(&file[..], |entry| {
+            entries.push((entry.player_name, entry.objective_name, entry.score));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(objectives.len(), 2);
+        assert_eq!(
+            entries,
+            [
+                ("ville".to_string(), "kills".to_string(), 1),
+                ("anna".to_string(), "deaths".to_string(), 2),
+                ("ville".to_string(), "deaths".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn streamed_long_csv_matches_the_parsed_one() {
+        let file = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("anna", "deaths", 2),
+                ("anna", "kills", 1),
+                ("ville", "deaths", 3),
+            ]
+            .into_iter(),
+        );
+
+        let mut streamed = Vec::new();
+        stream_csv_long(&file[..], &mut streamed, |_| true).unwrap();
+
+        let mut parsed = Vec::new();
+        Stats::from_gzip_reader(&file[..])
+            .unwrap()
+            .write_csv_long(&mut parsed)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(streamed.clone()).unwrap(),
+            "player,objective,score\nanna,deaths,2\nanna,kills,1\nville,deaths,3\n"
+        );
+        assert_eq!(parsed, streamed);
+    }
+
+    #[test]
+    fn objectives_only_reads_the_objectives() {
+        let file = fixture::generated(10, 3);
+
+        let objectives = Stats::objectives_only(&file[..]).unwrap();
+        let mut names: Vec<&String> = objectives.keys().collect();
+        names.sort_unstable();
+
+        assert_eq!(names, ["objective0", "objective1", "objective2"]);
+        assert_eq!(objectives["objective1"].display_name(), "objective1");
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Serialize;
+
+use super::Stats;
+
+/// How many players and objectives have scores
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub players: usize,
+    pub objectives: usize,
+}
+
+/// An objective and how its scores are distributed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObjectiveSummary {
+    pub objective: String,
+    pub display_name: String,
+    pub criteria: String,
+    /// The [`Criteria::category`](super::Criteria::category), usable as a
+    /// criteria selector
+    pub category: String,
+    /// `None` when nobody has a score
+    pub stats: Option<ObjectiveStats>,
+}
+
+/// How the scores of an objective are distributed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObjectiveStats {
+    /// Number of players with a score
+    pub count: usize,
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+    /// The middle score, or the mean of the two middle scores
+    pub median: f64,
+    /// Sum of every score, wide enough that it can't overflow
+    pub total: i128,
+}
+
+impl Stats {
+    pub fn summary(&self) -> Summary {
+        Summary {
+            players: self.player_count(),
+            objectives: self
+                .player_scores
+                .values()
+                .filter(|x| !x.is_empty())
+                .count(),
+        }
+    }
+
+    /// Every objective with the statistics of its scores, sorted by name
+    pub fn objective_summaries(&self) -> Vec<ObjectiveSummary> {
+        self.titles()
+            .into_iter()
+            .map(|name| {
+                let objective = &self.objectives[name];
+
+                ObjectiveSummary {
+                    objective: name.clone(),
+                    display_name: objective.display_name.clone(),
+                    criteria: objective.criteria_name.clone(),
+                    category: objective.criteria().category().to_string(),
+                    stats: self.objective_stats(name),
+                }
+            })
+            .collect()
+    }
+
+    /// Statistics of the scores in `objective`, `None` when the objective
+    /// doesn't exist or has no scores
+    pub fn objective_stats(&self, objective: &str) -> Option<ObjectiveStats> {
+        let mut scores: Vec<i64> = self
+            .player_scores
+            .get(objective)?
+            .iter()
+            .map(|x| x.score)
+            .collect();
+        scores.sort_unstable();
+
+        let min = *scores.first()?;
+        let max = *scores.last()?;
+        let count = scores.len();
+        let total: i128 = scores.iter().map(|x| *x as i128).sum();
+
+        //in f64 so the two middle scores can't overflow
+        let median = match count % 2 {
+            0 => (scores[count / 2 - 1] as f64 + scores[count / 2] as f64) / 2.0,
+            _ => scores[count / 2] as f64,
+        };
+
+        Some(ObjectiveStats {
+            count,
+            min,
+            max,
+            mean: total as f64 / count as f64,
+            median,
+            total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats() -> Stats {
+        let nbt = fixture::scoreboard(
+            &[
+                ("deaths", "deathCount"),
+                ("jumps", "minecraft.custom:minecraft.jump"),
+                ("walk", "dummy"),
+            ],
+            [
+                ("ville", "deaths", 3),
+                ("anna", "deaths", 1),
+                ("bob", "deaths", 10),
+                ("carl", "deaths", 2),
+                ("ville", "jumps", i32::MAX),
+                ("anna", "jumps", i32::MAX),
+            ]
+            .into_iter(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    #[test]
+    fn players_and_objectives_with_scores_are_counted() {
+        assert_eq!(
+            stats().summary(),
+            Summary {
+                players: 4,
+                objectives: 2
+            }
+        );
+    }
+
+    #[test]
+    fn scores_are_summarized() {
+        assert_eq!(
+            stats().objective_stats("deaths"),
+            Some(ObjectiveStats {
+                count: 4,
+                min: 1,
+                max: 10,
+                mean: 4.0,
+                median: 2.5,
+                total: 16,
+            })
+        );
+        //the middle scores and the total don't overflow
+        assert_eq!(
+            stats().objective_stats("jumps"),
+            Some(ObjectiveStats {
+                count: 2,
+                min: i32::MAX as i64,
+                max: i32::MAX as i64,
+                mean: i32::MAX as f64,
+                median: i32::MAX as f64,
+                total: i32::MAX as i128 * 2,
+            })
+        );
+        assert_eq!(stats().objective_stats("walk"), None);
+        assert_eq!(stats().objective_stats("missing"), None);
+    }
+
+    #[test]
+    fn every_objective_is_listed_by_name() {
+        let summaries = stats().objective_summaries();
+
+        let listed: Vec<(&str, &str, bool)> = summaries
+            .iter()
+            .map(|x| (x.objective.as_str(), x.category.as_str(), x.stats.is_some()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("deaths", "deathCount", true),
+                ("jumps", "minecraft.custom", true),
+                ("walk", "dummy", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use nbt::{Map, Value};
+use serde::{Deserialize, Serialize};
+
+use super::{list_items, NameInterner, PlayerScore, Stats};
+
+/// The row of players that aren't on any team in [`Stats::aggregate_by_team`]
+pub const UNASSIGNED: &str = "Unassigned";
+
+const TEAMS: &str = "Teams";
+
+/// A scoreboard team and its members
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Team {
+    name: String,
+    display_name: String,
+    players: Vec<String>,
+}
+
+impl Team {
+    /// The team name used in commands
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    /// The members, which may include players without any score
+    pub fn players(&self) -> &[String] {
+        &self.players
+    }
+}
+
+/// How the scores of a team's members are combined
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TeamAggregate {
+    #[default]
+    Sum,
+    Max,
+    /// Rounded to the nearest whole score
+    Mean,
+}
+
+/// The teams in the `data` compound of a scoreboard, teams that can't be
+/// read are skipped since the scores don't depend on them
+pub(super) fn parse_teams(data: &Map<String, Value>) -> Vec<Team> {
+    let Some(raw_teams) = data.get(TEAMS).and_then(list_items) else {
+        return Vec::new();
+    };
+
+    raw_teams
+        .iter()
+        .filter_map(|team| match team {
+            Value::Compound(team) => Some(team),
+            _ => None,
+        })
+        .filter_map(|team| {
+            let name = match team.get("Name") {
+                Some(Value::String(name)) => name.clone(),
+                _ => return None,
+            };
+
+            //display names are json text like the ones of objectives
+            let display_name = match team.get("DisplayName") {
+                Some(Value::String(s)) => {
+                    let mut chars = s.chars();
+                    chars.next();
+                    chars.next_back();
+                    chars.as_str().to_string()
+                }
+                _ => name.clone(),
+            };
+
+            let players = team
+                .get("Players")
+                .and_then(list_items)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|player| match player {
+                    Value::String(player) => Some(player.clone()),
+                    _ => None,
+                })
+                .collect();
+
+            Some(Team {
+                name,
+                display_name,
+                players,
+            })
+        })
+        .collect()
+}
+
+impl Stats {
+    /// The teams of the scoreboard, or after [`Stats::aggregate_by_team`]
+    /// a team per row with the members that had scores
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+
+    /// Collapses the scores of every team's members into a single score
+    /// per objective, so each team becomes a player named after the team.
+    /// Players on no team are combined into [`UNASSIGNED`].
+    ///
+    /// The teams of the result have the same names as its players and list
+    /// the members that had at least one score. A player on more than one
+    /// team counts for the first.
+    pub fn aggregate_by_team(&self, aggregate: TeamAggregate) -> Stats {
+        let mut team_of: HashMap<&str, &str> = HashMap::new();
+        for team in self.teams.iter() {
+            for player in team.players.iter() {
+                team_of.entry(player).or_insert(&team.name);
+            }
+        }
+        let team_of = |player: &str| team_of.get(player).copied().unwrap_or(UNASSIGNED);
+
+        let mut names = NameInterner::default();
+        let mut player_scores = Map::new();
+
+        for (objective, scores) in self.player_scores.iter() {
+            //(total, max, count) per team in the order they are first seen
+            let mut totals: Vec<(&str, i128, i64, i64)> = Vec::new();
+
+            for score in scores {
+                let team = team_of(score.player_name());
+
+                match totals.iter_mut().find(|(x, ..)| *x == team) {
+                    Some((_, total, max, count)) => {
+                        *total += score.score as i128;
+                        *max = (*max).max(score.score);
+                        *count += 1;
+                    }
+                    None => totals.push((team, score.score as i128, score.score, 1)),
+                }
+            }
+
+            let team_scores = totals
+                .into_iter()
+                .map(|(team, total, max, count)| PlayerScore {
+                    locked: 0,
+                    player_name: names.intern(team),
+                    score: match aggregate {
+                        TeamAggregate::Sum => {
+                            total.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+                        }
+                        TeamAggregate::Max => max,
+                        TeamAggregate::Mean => (total as f64 / count as f64).round() as i64,
+                    },
+                })
+                .collect();
+
+            player_scores.insert(objective.clone(), team_scores);
+        }
+
+        let player_order = names.into_order();
+
+        let teams = player_order
+            .iter()
+            .map(|row| {
+                let mut players: Vec<String> = self
+                    .player_names()
+                    .into_iter()
+                    .filter(|player| team_of(player) == &**row)
+                    .map(String::from)
+                    .collect();
+                players.sort_unstable();
+
+                let display_name = self
+                    .teams
+                    .iter()
+                    .find(|team| team.name == **row)
+                    .map_or_else(|| row.to_string(), |team| team.display_name.clone());
+
+                Team {
+                    name: row.to_string(),
+                    display_name,
+                    players,
+                }
+            })
+            .collect();
+
+        Stats {
+            objectives: self.objectives.clone(),
+            player_scores,
+            player_order,
+            teams,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats() -> Stats {
+        let nbt = fixture::scoreboard_with_teams(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            [
+                ("anna", "deaths", 1),
+                ("bob", "deaths", 4),
+                ("carl", "deaths", 10),
+                ("dave", "deaths", 3),
+                ("eve", "deaths", 4),
+                ("bob", "kills", 5),
+            ]
+            .into_iter(),
+            &[
+                ("red", &["anna", "bob"]),
+                ("blue", &["carl", "anna"]),
+                ("green", &[]),
+            ],
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    fn team(name: &str, display_name: &str, players: &[&str]) -> Team {
+        Team {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            players: players.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    /// The scores of every row as `(team, deaths, kills)`
+    fn rows(stats: &Stats) -> Vec<(String, i64, Option<i64>)> {
+        stats
+            .player_names()
+            .into_iter()
+            .map(|row| {
+                let scores = stats.scores_of(row).unwrap();
+                (
+                    row.to_string(),
+                    scores["deaths"],
+                    scores.get("kills").copied(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn teams_are_parsed() {
+        assert_eq!(
+            stats().teams(),
+            [
+                team("red", "RED", &["anna", "bob"]),
+                team("blue", "BLUE", &["carl", "anna"]),
+                team("green", "GREEN", &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn members_are_summed() {
+        //anna is on two teams and counts for the first
+        assert_eq!(
+            rows(&stats().aggregate_by_team(TeamAggregate::Sum)),
+            [
+                (UNASSIGNED.to_string(), 7, None),
+                ("blue".to_string(), 10, None),
+                ("red".to_string(), 5, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn highest_member_score_is_taken() {
+        assert_eq!(
+            rows(&stats().aggregate_by_team(TeamAggregate::Max)),
+            [
+                (UNASSIGNED.to_string(), 4, None),
+                ("blue".to_string(), 10, None),
+                ("red".to_string(), 4, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn mean_is_rounded() {
+        assert_eq!(
+            rows(&stats().aggregate_by_team(TeamAggregate::Mean)),
+            [
+                (UNASSIGNED.to_string(), 4, None),
+                ("blue".to_string(), 10, None),
+                ("red".to_string(), 3, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_list_the_members_with_scores() {
+        let teams = stats().aggregate_by_team(TeamAggregate::Sum);
+
+        //green has nobody with a score and gets no row
+        assert_eq!(
+            teams.teams(),
+            [
+                team("red", "RED", &["anna", "bob"]),
+                team("blue", "BLUE", &["carl"]),
+                team(UNASSIGNED, UNASSIGNED, &["dave", "eve"]),
+            ]
+        );
+    }
+}
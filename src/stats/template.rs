@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, io::Write, sync::Arc};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use minijinja::{
+    context, default_auto_escape_callback, value::Serde, Environment, ErrorKind, Value,
+};
+use serde::Serialize;
+
+use super::Stats;
+use crate::error::Error;
+
+/// Templates that ship with the crate as `(name, source)`, usable in place
+/// of a template file. The extension of the name decides the escaping.
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("leaderboard.txt", include_str!("templates/leaderboard.txt")),
+    (
+        "leaderboard.html",
+        include_str!("templates/leaderboard.html"),
+    ),
+];
+
+#[derive(Serialize)]
+struct TemplateObjective<'a> {
+    name: &'a str,
+    display: &'a str,
+    criteria: &'a str,
+}
+
+#[derive(Serialize)]
+struct LeaderboardEntry<'a> {
+    player: &'a str,
+    score: i64,
+}
+
+impl Stats {
+    /// Renders a minijinja template with the scores. The template sees
+    ///
+    /// - `objectives`, a list of objects with the `name`, `display` name
+    ///   and `criteria` of every objective, in the order of the csv columns
+    /// - `players`, the sorted player names
+    /// - `scores`, the score of a player in an objective as
+    ///   `scores[objective][player]`, undefined when they have none
+    /// - `leaderboard(objective, n)`, the `n` highest scores of an objective
+    ///   as objects with the `player` and `score`
+    /// - `generated_at`, the timestamp in RFC 3339
+    ///
+    /// The name is used in errors and its extension decides the escaping,
+    /// `.html` templates escape every value. A `.j2` or `.tera` extension
+    /// is skipped. Errors report the line and column in the template.
+    pub fn render_template(
+        &self,
+        name: &str,
+        source: &str,
+        generated_at: DateTime<Utc>,
+    ) -> Result<String, Error> {
+        let mut env = Environment::new();
+        env.set_debug(true);
+        env.set_auto_escape_callback(|name| {
+            default_auto_escape_callback(name.strip_suffix(".tera").unwrap_or(name))
+        });
+
+        let stats = Arc::new(self.clone());
+        env.add_function("leaderboard", move |objective: &str, n: usize| match stats
+            .leaderboard(objective, n)
+        {
+            Some(entries) => Ok(Value::from(Serde(
+                entries
+                    .into_iter()
+                    .map(|(player, score)| LeaderboardEntry { player, score })
+                    .collect::<Vec<_>>(),
+            ))),
+            None => Err(minijinja::Error::new(
+                ErrorKind::InvalidOperation,
+                format!("No objective named {}", objective),
+            )),
+        });
+
+        let objectives: Vec<TemplateObjective> = self
+            .titles()
+            .into_iter()
+            .map(|x| &self.objectives[x])
+            .map(|x| TemplateObjective {
+                name: &x.name,
+                display: &x.display_name,
+                criteria: &x.criteria_name,
+            })
+            .collect();
+
+        let scores: BTreeMap<&str, BTreeMap<&str, i64>> = self
+            .player_scores
+            .iter()
+            .map(|(objective, scores)| {
+                (
+                    objective.as_str(),
+                    scores.iter().map(|x| (x.player_name(), x.score)).collect(),
+                )
+            })
+            .collect();
+
+        let ctx = context! {
+            objectives => Serde(objectives),
+            players => Serde(self.player_names()),
+            scores => Serde(scores),
+            generated_at => generated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+
+        env.add_template(name, source)
+            .and_then(|_| env.get_template(name)?.render(ctx))
+            .map_err(|e| template_error(name, source, e))
+    }
+
+    /// Writes [`Stats::render_template`]
+    pub fn write_template(
+        &self,
+        mut w: impl Write,
+        name: &str,
+        source: &str,
+        generated_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        w.write_all(self.render_template(name, source, generated_at)?.as_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+/// The source of a builtin template by name
+pub fn builtin_template(name: &str) -> Option<&'static str> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(x, _)| *x == name)
+        .map(|(_, source)| *source)
+}
+
+fn template_error(name: &str, source: &str, e: minijinja::Error) -> Error {
+    //the engine only knows the byte range, the column is counted from it
+    let column = e.range().map(|range| {
+        let line_start = source[..range.start].rfind('\n').map_or(0, |x| x + 1);
+        source[line_start..range.start].chars().count() + 1
+    });
+
+    Error::Template {
+        name: name.to_string(),
+        line: e.line(),
+        column,
+        message: match e.detail() {
+            Some(detail) => format!("{}: {}", e.kind(), detail),
+            None => e.kind().to_string(),
+        },
+    }
+}
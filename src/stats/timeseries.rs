@@ -0,0 +1,118 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
+
+use chrono::NaiveDate;
+use csv::ByteRecord;
+
+use super::Stats;
+use crate::error::Error;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The scores of a single objective over dated snapshots, kept in date
+/// order whatever order the snapshots are added in
+#[derive(Debug, Clone)]
+pub struct TimeSeries {
+    objective: String,
+    /// `None` for snapshots that couldn't be read
+    snapshots: BTreeMap<NaiveDate, Option<HashMap<String, i64>>>,
+}
+
+impl TimeSeries {
+    pub fn new(objective: impl Into<String>) -> Self {
+        Self {
+            objective: objective.into(),
+            snapshots: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the scores of the objective in `stats`, replacing any snapshot
+    /// already added for `date`
+    pub fn add(&mut self, date: NaiveDate, stats: &Stats) {
+        let scores = stats
+            .player_scores
+            .get(&self.objective)
+            .into_iter()
+            .flatten()
+            .map(|x| (x.player_name().to_string(), x.score))
+            .collect();
+
+        self.snapshots.insert(date, Some(scores));
+    }
+
+    /// Adds a snapshot that couldn't be read, which is written as an empty column
+    pub fn add_missing(&mut self, date: NaiveDate) {
+        self.snapshots.entry(date).or_insert(None);
+    }
+
+    /// Writes one row per player and one column per snapshot date, scores
+    /// missing from a snapshot are left empty
+    pub fn write_csv(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+
+        record.push_field(b"Players");
+        for date in self.snapshots.keys() {
+            record.push_field(date.format(DATE_FORMAT).to_string().as_bytes());
+        }
+        w.write_byte_record(&record)?;
+
+        for player in self.players() {
+            record.clear();
+            record.push_field(player.as_bytes());
+
+            for scores in self.snapshots.values() {
+                match scores.as_ref().and_then(|x| x.get(player)) {
+                    Some(score) => record.push_field(score_buffer.format(*score).as_bytes()),
+                    None => record.push_field(b""),
+                }
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes a `player,date,score` row for every score, ordered by player
+    /// and then by date
+    pub fn write_csv_long(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+
+        w.write_record(["player", "date", "score"])?;
+
+        for player in self.players() {
+            for (date, scores) in self.snapshots.iter() {
+                let Some(score) = scores.as_ref().and_then(|x| x.get(player)) else {
+                    continue;
+                };
+
+                record.clear();
+                record.push_field(player.as_bytes());
+                record.push_field(date.format(DATE_FORMAT).to_string().as_bytes());
+                record.push_field(score_buffer.format(*score).as_bytes());
+                w.write_byte_record(&record)?;
+            }
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+
+    fn players(&self) -> BTreeSet<&str> {
+        self.snapshots
+            .values()
+            .flatten()
+            .flat_map(|x| x.keys())
+            .map(String::as_str)
+            .collect()
+    }
+}
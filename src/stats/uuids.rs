@@ -0,0 +1,23 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// Player names mapped to their UUIDs
+pub type UuidMap = HashMap<String, String>;
+
+/// An entry of the server's usercache.json
+#[derive(Deserialize)]
+struct UsercacheEntry {
+    name: String,
+    uuid: String,
+}
+
+/// Reads the player UUIDs from a usercache.json in the format the server
+/// writes it, a list of `{"name": ..., "uuid": ..., "expiresOn": ...}` objects
+pub fn read_usercache(path: &Path) -> Result<UuidMap, Error> {
+    let entries: Vec<UsercacheEntry> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    Ok(entries.into_iter().map(|x| (x.name, x.uuid)).collect())
+}
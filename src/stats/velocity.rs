@@ -0,0 +1,199 @@
+use std::{collections::HashMap, fmt::Write as _, io, io::Write};
+
+use chrono::{DateTime, Utc};
+use csv::ByteRecord;
+
+use super::{csv_export::resolve_header_collisions, ColumnType, CsvColumn, HeaderCollision, Stats};
+use crate::error::Error;
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+impl Stats {
+    /// Writes a csv with the players of the newest snapshot in `snapshots`
+    /// and, for every objective, their score in it followed by an
+    /// `{objective}_per_day` column with how much the score changed per day.
+    ///
+    /// The change is measured from the oldest snapshot the player has a
+    /// score of the objective in, so players who joined later aren't
+    /// compared to a score of 0. The velocity is left empty when there is
+    /// no older score or no time passed since it, and is written with
+    /// `float_precision` decimal places.
+    pub fn write_csv_with_velocity(
+        w: impl Write,
+        snapshots: &[(DateTime<Utc>, &Stats)],
+        float_precision: u8,
+    ) -> Result<(), Error> {
+        let mut snapshots: Vec<&(DateTime<Utc>, &Stats)> = snapshots.iter().collect();
+        snapshots.sort_by_key(|(timestamp, _)| *timestamp);
+
+        let Some((now, current)) = snapshots.pop() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the velocity needs at least one snapshot",
+            )
+            .into());
+        };
+
+        //the oldest score of every objective and player with its time
+        let mut earliest: HashMap<(&str, &str), (DateTime<Utc>, i64)> = HashMap::new();
+        for (timestamp, stats) in snapshots.iter() {
+            for (objective, scores) in stats.player_scores.iter() {
+                for score in scores {
+                    earliest
+                        .entry((objective, score.player_name()))
+                        .or_insert((*timestamp, score.score));
+                }
+            }
+        }
+
+        let titles = current.titles();
+
+        let mut columns = Vec::new();
+        for title in titles.iter() {
+            columns.push(CsvColumn {
+                name: current.objectives[*title].display_name().to_string(),
+                column_type: ColumnType::Integer,
+                objective: Some(title.to_string()),
+                criteria: None,
+            });
+            columns.push(CsvColumn {
+                name: format!("{}_per_day", title),
+                column_type: ColumnType::Number,
+                objective: None,
+                criteria: None,
+            });
+        }
+        resolve_header_collisions(&mut columns, HeaderCollision::Disambiguate)?;
+
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+        let mut formatted_buffer = String::new();
+
+        record.push_field(b"Players");
+        for column in &columns {
+            record.push_field(column.name.as_bytes());
+        }
+        w.write_byte_record(&record)?;
+
+        let current_scores: HashMap<(&str, &str), i64> = current
+            .player_scores
+            .iter()
+            .flat_map(|(objective, scores)| {
+                scores
+                    .iter()
+                    .map(move |x| ((objective.as_str(), x.player_name()), x.score))
+            })
+            .collect();
+
+        for player in current.player_names() {
+            record.clear();
+            record.push_field(player.as_bytes());
+
+            for title in titles.iter() {
+                let key = (title.as_str(), player);
+                let score = current_scores.get(&key).copied();
+
+                record.push_field(score_buffer.format(score.unwrap_or(0)).as_bytes());
+
+                let oldest = earliest
+                    .get(&key)
+                    .map(|(then, earliest)| {
+                        (
+                            (*now - *then).num_seconds() as f64 / SECONDS_PER_DAY,
+                            *earliest,
+                        )
+                    })
+                    .filter(|(days, _)| *days > 0.0);
+
+                formatted_buffer.clear();
+                if let (Some(score), Some((days, earliest))) = (score, oldest) {
+                    write!(
+                        formatted_buffer,
+                        "{:.prec$}",
+                        (score as i128 - earliest as i128) as f64 / days,
+                        prec = float_precision as usize
+                    )
+                    .unwrap();
+                }
+                record.push_field(formatted_buffer.as_bytes());
+            }
+
+            w.write_byte_record(&record)?;
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::fixture;
+
+    fn stats(scores: &[(&str, &str, i32)]) -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.iter().copied(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap()
+    }
+
+    fn velocity(snapshots: &[(DateTime<Utc>, &Stats)]) -> String {
+        let mut out = Vec::new();
+        Stats::write_csv_with_velocity(&mut out, snapshots, 2).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn changes_are_measured_from_the_oldest_score() {
+        let first = stats(&[("ville", "deaths", 0)]);
+        let second = stats(&[("ville", "deaths", 4), ("anna", "kills", 10)]);
+        let newest = stats(&[
+            ("ville", "deaths", 8),
+            ("anna", "kills", 30),
+            ("otto", "kills", 5),
+        ]);
+
+        //anna is first seen on the 3rd and otto only in the newest snapshot
+        assert_eq!(
+            velocity(&[(day(5), &newest), (day(1), &first), (day(3), &second)]),
+            "Players,deaths,deaths_per_day,kills,kills_per_day\n\
+            anna,0,,30,10.00\n\
+            otto,0,,5,\n\
+            ville,8,2.00,0,\n"
+        );
+    }
+
+    #[test]
+    fn no_velocity_without_elapsed_time() {
+        let older = stats(&[("ville", "deaths", 1)]);
+        let newer = stats(&[("ville", "deaths", 5)]);
+
+        assert_eq!(
+            velocity(&[(day(1), &older), (day(1), &newer)]),
+            "Players,deaths,deaths_per_day,kills,kills_per_day\nville,5,,0,\n"
+        );
+        assert_eq!(
+            velocity(&[(day(1), &newer)]),
+            "Players,deaths,deaths_per_day,kills,kills_per_day\nville,5,,0,\n"
+        );
+    }
+
+    #[test]
+    fn no_snapshots_is_an_error() {
+        let err = Stats::write_csv_with_velocity(Vec::new(), &[], 2).unwrap_err();
+
+        assert_eq!(err.to_string(), "the velocity needs at least one snapshot");
+    }
+}
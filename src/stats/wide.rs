@@ -0,0 +1,167 @@
+use std::{collections::HashMap, io};
+
+use async_std::future::timeout;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_scalar, Connection, MySqlConnection};
+
+use super::{schema::TENANT_COLUMN, sql::execute, SqlOptions, Stats};
+use crate::error::Error;
+
+/// Columns of `player_scores_wide` that aren't objectives
+const KEY_COLUMNS: [&str; 2] = ["player_name", "timestamp"];
+
+impl Stats {
+    /// Inserts a row per player into `player_scores_wide` with a BIGINT
+    /// column per objective, NULL where the player has no score.
+    ///
+    /// The table is created when it is missing and gets a column added for
+    /// every objective it doesn't have yet, so rows written before an
+    /// objective existed have NULL in it. Column names compare without
+    /// case, objectives only differing in case or named like the
+    /// `player_name`, `timestamp` and `tenant_id` columns are refused.
+    ///
+    /// Writing the same timestamp again replaces the scores of its rows.
+    pub async fn write_to_sql_wide(
+        &self,
+        conn: &mut MySqlConnection,
+        timestamp: DateTime<Utc>,
+        opts: &SqlOptions,
+    ) -> Result<(), Error> {
+        let table = opts.tables()?.player_scores_wide;
+
+        let mut objectives: Vec<&String> = self.objectives.keys().collect();
+        objectives.sort_unstable();
+
+        let mut seen: Vec<String> = KEY_COLUMNS
+            .iter()
+            .chain(&["tenant_id"])
+            .map(|x| x.to_string())
+            .collect();
+        for objective in objectives.iter() {
+            let lowercase = objective.to_lowercase();
+            if seen.contains(&lowercase) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "the objective {} has the same column name as another column",
+                        objective
+                    ),
+                )
+                .into());
+            }
+            seen.push(lowercase);
+        }
+
+        execute(
+            query(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+    player_name VARCHAR(255) NOT NULL,
+    timestamp DATETIME NOT NULL,
+    {},
+    PRIMARY KEY (player_name, timestamp),
+    INDEX (timestamp)
+) ENGINE InnoDB",
+                table, TENANT_COLUMN
+            )),
+            &mut *conn,
+            opts,
+        )
+        .await?;
+
+        let existing: Vec<String> = timeout(
+            opts.query_timeout,
+            query_scalar(
+                "SELECT CAST(column_name AS CHAR) FROM information_schema.columns \
+                WHERE table_schema = DATABASE() AND table_name = ?",
+            )
+            .bind(&table)
+            .fetch_all(&mut *conn),
+        )
+        .await
+        .map_err(|_| Error::SQLTimeout("query"))??;
+        let existing: Vec<String> = existing.iter().map(|x| x.to_lowercase()).collect();
+
+        //schema changes commit on their own, so they happen before the transaction
+        if opts.tenant_id.is_some() && !existing.iter().any(|x| x == "tenant_id") {
+            execute(
+                query(&format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    table, TENANT_COLUMN
+                )),
+                &mut *conn,
+                opts,
+            )
+            .await?;
+        }
+
+        for objective in objectives.iter() {
+            if !existing.contains(&objective.to_lowercase()) {
+                execute(
+                    query(&format!(
+                        "ALTER TABLE {} ADD COLUMN {} BIGINT NULL",
+                        table,
+                        column_name(objective)
+                    )),
+                    &mut *conn,
+                    opts,
+                )
+                .await?;
+            }
+        }
+
+        let columns: Vec<String> = KEY_COLUMNS
+            .iter()
+            .map(|x| x.to_string())
+            .chain(objectives.iter().map(|x| column_name(x)))
+            .collect();
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+
+        //re-running an import for the same timestamp updates its rows
+        let updated: Vec<String> = columns[KEY_COLUMNS.len()..]
+            .iter()
+            .copied()
+            .chain(opts.tenant_id.as_ref().map(|_| "tenant_id"))
+            .map(|x| format!("{} = VALUES({})", x, x))
+            .collect();
+        let insert = format!(
+            "{} ON DUPLICATE KEY UPDATE {}",
+            opts.insert_sql("INSERT INTO", &table, &columns),
+            match updated.is_empty() {
+                true => "player_name = VALUES(player_name)".to_string(),
+                false => updated.join(", "),
+            }
+        );
+
+        let scores: HashMap<(&str, &str), i64> = self
+            .player_scores
+            .iter()
+            .flat_map(|(objective, scores)| {
+                scores
+                    .iter()
+                    .map(move |x| ((objective.as_str(), x.player_name()), x.score))
+            })
+            .collect();
+
+        let mut transaction = conn.begin().await?;
+
+        for player in self.players_in_order(opts.player_order) {
+            let mut row = query(&insert)
+                .bind(opts.stored_player_name(player))
+                .bind(timestamp);
+
+            for objective in objectives.iter() {
+                row = row.bind(scores.get(&(objective.as_str(), player)).copied());
+            }
+
+            execute(opts.bind_tenant(row), &mut *transaction, opts).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+fn column_name(objective: &str) -> String {
+    format!("`{}`", objective.replace('`', "``"))
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use csv::ByteRecord;
+use serde::Serialize;
+
+use super::Stats;
+use crate::error::Error;
+
+/// The scoreboards of several worlds of a server, exported together with
+/// a world column. Worlds are kept in name order.
+#[derive(Debug, Clone, Default)]
+pub struct Worlds {
+    worlds: BTreeMap<String, Stats>,
+}
+
+/// An objective that has different display names in different worlds.
+/// Every world keeps its own display name in the exports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ObjectiveConflict {
+    pub objective: String,
+    /// `(world, display name)` for every world with the objective
+    pub display_names: Vec<(String, String)>,
+}
+
+#[derive(Serialize)]
+struct WorldScoreLine<'a> {
+    world: &'a str,
+    objective_name: &'a str,
+    display_name: &'a str,
+    player_name: &'a str,
+    score: i64,
+}
+
+/// World directories keep the scoreboard in `data/scoreboard.dat`
+pub fn scoreboard_path(input: &Path) -> PathBuf {
+    match input.is_dir() {
+        true => input.join("data").join("scoreboard.dat"),
+        false => input.to_path_buf(),
+    }
+}
+
+impl Worlds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The worlds directly beneath a server root as `(directory name,
+    /// scoreboard path)`, sorted by name. Directories without a
+    /// `data/scoreboard.dat` are skipped.
+    pub fn discover(root: &Path) -> Result<Vec<(String, PathBuf)>, Error> {
+        let mut worlds = Vec::new();
+
+        for entry in fs::read_dir(root)? {
+            let path = entry?.path();
+            let scoreboard = path.join("data").join("scoreboard.dat");
+
+            if !scoreboard.is_file() {
+                continue;
+            }
+
+            if let Some(name) = path.file_name() {
+                worlds.push((name.to_string_lossy().into_owned(), scoreboard));
+            }
+        }
+
+        worlds.sort();
+
+        Ok(worlds)
+    }
+
+    /// Adds the scores of a world, replacing any world already added with
+    /// the same name
+    pub fn add(&mut self, name: impl Into<String>, stats: Stats) {
+        self.worlds.insert(name.into(), stats);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Stats)> {
+        self.worlds
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.worlds.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.worlds.len()
+    }
+
+    /// The objectives whose display name isn't the same in every world
+    /// that has them, sorted by objective name
+    pub fn conflicts(&self) -> Vec<ObjectiveConflict> {
+        let mut display_names: BTreeMap<&str, Vec<(String, String)>> = BTreeMap::new();
+
+        for (world, stats) in self.worlds.iter() {
+            for (name, objective) in stats.objectives.iter() {
+                display_names
+                    .entry(name)
+                    .or_default()
+                    .push((world.clone(), objective.display_name().to_string()));
+            }
+        }
+
+        display_names
+            .into_iter()
+            .filter(|(_, names)| names.iter().any(|(_, x)| *x != names[0].1))
+            .map(|(objective, display_names)| ObjectiveConflict {
+                objective: objective.to_string(),
+                display_names,
+            })
+            .collect()
+    }
+
+    /// Writes a world,player,objective,display_name,score row for every
+    /// score, with the display name the objective has in that world
+    pub fn write_csv_long(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = csv::Writer::from_writer(w);
+        let mut record = ByteRecord::new();
+        let mut score_buffer = itoa::Buffer::new();
+
+        w.write_record(["world", "player", "objective", "display_name", "score"])?;
+
+        for (world, stats) in self.worlds.iter() {
+            for (objective, scores) in stats.player_scores.iter() {
+                let display_name = display_name(stats, objective);
+
+                for score in scores {
+                    record.clear();
+                    record.push_field(world.as_bytes());
+                    record.push_field(score.player_name().as_bytes());
+                    record.push_field(objective.as_bytes());
+                    record.push_field(display_name.as_bytes());
+                    record.push_field(score_buffer.format(score.score).as_bytes());
+                    w.write_byte_record(&record)?;
+                }
+            }
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the rows of [`Worlds::write_csv_long`] as one json object per line
+    pub fn write_ndjson(&self, mut w: impl Write) -> Result<(), Error> {
+        for (world, stats) in self.worlds.iter() {
+            for (objective_name, scores) in stats.player_scores.iter() {
+                let display_name = display_name(stats, objective_name);
+
+                for score in scores {
+                    serde_json::to_writer(
+                        &mut w,
+                        &WorldScoreLine {
+                            world,
+                            objective_name,
+                            display_name,
+                            player_name: score.player_name(),
+                            score: score.score,
+                        },
+                    )?;
+                    w.write_all(b"\n")?;
+                }
+            }
+        }
+
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+//scores of objectives missing from the objectives list fall back to the name
+fn display_name<'a>(stats: &'a Stats, objective: &'a str) -> &'a str {
+    stats
+        .objectives
+        .get(objective)
+        .map(|x| x.display_name())
+        .unwrap_or(objective)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    fn stats(scores: &[(&str, &str, i32)]) -> Stats {
+        let nbt = fixture::scoreboard(
+            &[("deaths", "deathCount"), ("kills", "playerKillCount")],
+            scores.iter().copied(),
+        );
+
+        Stats::from_gzip_reader(&nbt[..]).unwrap()
+    }
+
+    fn worlds() -> Worlds {
+        let mut nether = stats(&[("ville", "deaths", 2)]);
+        nether.objectives.get_mut("deaths").unwrap().display_name = "Nether deaths".to_string();
+
+        let mut worlds = Worlds::new();
+        worlds.add(
+            "world",
+            stats(&[("ville", "deaths", 3), ("anna", "kills", 1)]),
+        );
+        worlds.add("nether", nether);
+        worlds
+    }
+
+    #[test]
+    fn worlds_with_a_scoreboard_are_discovered() {
+        let root = fixture::temp_dir("worlds_discover");
+        let nbt = fixture::scoreboard_without_scores(&[("deaths", "deathCount")]);
+        for world in ["world_b", "world_a"] {
+            fs::create_dir_all(root.join(world).join("data")).unwrap();
+            fs::write(root.join(world).join("data").join("scoreboard.dat"), &nbt).unwrap();
+        }
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("server.properties"), "").unwrap();
+
+        let scoreboard = |world: &str| root.join(world).join("data").join("scoreboard.dat");
+        assert_eq!(
+            Worlds::discover(&root).unwrap(),
+            [
+                ("world_a".to_string(), scoreboard("world_a")),
+                ("world_b".to_string(), scoreboard("world_b")),
+            ]
+        );
+        assert_eq!(
+            scoreboard_path(&root.join("world_a")),
+            scoreboard("world_a")
+        );
+        assert_eq!(
+            scoreboard_path(&scoreboard("world_a")),
+            scoreboard("world_a")
+        );
+    }
+
+    #[test]
+    fn differing_display_names_are_conflicts() {
+        let worlds = worlds();
+
+        assert_eq!(worlds.len(), 2);
+        assert_eq!(
+            worlds.conflicts(),
+            [ObjectiveConflict {
+                objective: "deaths".to_string(),
+                display_names: vec![
+                    ("nether".to_string(), "Nether deaths".to_string()),
+                    ("world".to_string(), "deaths".to_string()),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn rows_keep_the_display_name_of_their_world() {
+        let worlds = worlds();
+
+        //objectives come out in hash order, so compare the rows sorted
+        let sorted = |output: Vec<u8>| {
+            let mut lines: Vec<String> = String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            lines.sort();
+            lines
+        };
+
+        let mut csv = Vec::new();
+        worlds.write_csv_long(&mut csv).unwrap();
+        assert_eq!(
+            sorted(csv),
+            [
+                "nether,ville,deaths,Nether deaths,2",
+                "world,anna,kills,kills,1",
+                "world,player,objective,display_name,score",
+                "world,ville,deaths,deaths,3",
+            ]
+        );
+
+        let mut ndjson = Vec::new();
+        worlds.write_ndjson(&mut ndjson).unwrap();
+        assert_eq!(
+            sorted(ndjson)[0],
+            r#"{"world":"nether","objective_name":"deaths","display_name":"Nether deaths","player_name":"ville","score":2}"#
+        );
+    }
+}